@@ -0,0 +1,37 @@
+use nalgebra_glm as glm;
+use std::str::FromStr;
+use wgpu_robotic_simulator::physics::{Body, PhysicsProgram, PhysicsWorld, Shape};
+use wgpu_robotic_simulator::raycast::RaycastScene;
+use wgpu_robotic_simulator::sensor::Lidar;
+use wgpu_robotic_simulator::urdf::RobotDescriptor;
+
+// sweeps the lidar declared on the racecar around a yard of crates, and
+// prints the ranges every 45 degrees
+fn main() -> anyhow::Result<()> {
+    let robot = RobotDescriptor::from_str(include_str!("../assets/racecar.urdf"))
+        .expect("unable to read urdf");
+    let mut lidar = Lidar::from_urdf(&robot, "lidar")?;
+    lidar.config.min_range = 0.1;
+    lidar.config.max_range = 8.0;
+    lidar.config.noise_stddev = 0.01;
+
+    let mut world = PhysicsWorld::new();
+    world.add_body(Body::fixed(Shape::Plane, glm::vec3(0.0, 0.0, -0.2)));
+    for (x, y) in [(3.0, 0.0), (2.0, 2.5), (-1.5, -2.0), (-4.0, 1.0)] {
+        world.add_body(Body::fixed(
+            Shape::Cuboid {
+                half_extents: glm::vec3(0.5, 0.5, 0.5),
+            },
+            glm::vec3(x, y, 0.3),
+        ));
+    }
+    let scene = RaycastScene::from_world(&world);
+
+    let scan = lidar.scan(&robot, &scene);
+    println!("{} rays, {} returns", lidar.ray_count(), scan.points.len());
+    for i in (0..scan.width).step_by(scan.width / 8) {
+        let angle = -180.0 + 360.0 * i as f32 / (scan.width - 1) as f32;
+        println!("{:6.1} deg: {:.3} m", angle, scan.range_at(i, 0));
+    }
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use std::str::FromStr;
+use wgpu_robotic_simulator::graphics::GraphicsProgram;
+use wgpu_robotic_simulator::robot::RobotGraphics;
+use wgpu_robotic_simulator::shader::CreatePipeline;
+use wgpu_robotic_simulator::urdf::*;
+use wgpu_robotic_simulator::wgpu_program::OffscreenGraphics;
+
+// renders LittleDog into a PNG file without opening a window; pass
+// `--fallback` to use a software adapter
+pub fn run() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let fallback = args.iter().any(|a| a == "--fallback");
+    let path = args
+        .iter()
+        .find(|a| !a.starts_with("--"))
+        .map_or("littledog.png", String::as_str);
+
+    let mut program = OffscreenGraphics::new_offscreen(1240, 860, fallback)?;
+    program.get_backend_info();
+
+    let mut robot = RobotDescriptor::from_str(include_str!("../assets/LittleDog.urdf"))
+        .expect("unable to read urdf");
+    robot
+        .set_joint_positions_by_name([("front_left_knee", -1.0), ("back_right_knee", 1.0)])
+        .expect("unknown joint");
+    robot.build();
+
+    //Initialize uniform buffers
+    let camera_buffer = program.create_camera_buffer();
+    let light_buffer = program.create_light_buffer();
+    let transform_buffers = program.robot_create_transform_buffers(&robot);
+    let mesh_buffers = program.robot_create_mesh_buffers(&robot);
+    program.create_bindings(&light_buffer, &camera_buffer, &transform_buffers);
+
+    // Create pipeline from vertex, fragment shaders
+    let pipeline = program.create_render_pipeline(include_str!("../shaders/shader.wgsl"))?;
+
+    program.draw_robot(&robot, &mesh_buffers, &pipeline);
+    program.save_png(path)?;
+    println!("saved {}", path);
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    run()
+}
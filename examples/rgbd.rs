@@ -0,0 +1,75 @@
+use nalgebra_glm as glm;
+use std::f32::consts::PI;
+use std::str::FromStr;
+use wgpu_robotic_simulator::geometry::Transform;
+use wgpu_robotic_simulator::graphics::GraphicsProgram;
+use wgpu_robotic_simulator::robot::RobotGraphics;
+use wgpu_robotic_simulator::sensor::{CameraIntrinsics, RgbdCamera, RgbdFrame};
+use wgpu_robotic_simulator::shader::CreatePipeline;
+use wgpu_robotic_simulator::urdf::*;
+use wgpu_robotic_simulator::wgpu_program::OffscreenGraphics;
+
+// grey levels from white at `near` to black at `far`
+fn depth_image(frame: &RgbdFrame) -> image::GrayImage {
+    let CameraIntrinsics {
+        width,
+        height,
+        near,
+        far,
+        ..
+    } = frame.intrinsics;
+    image::GrayImage::from_fn(width, height, |u, v| {
+        let d = frame.depth_at(u, v).min(far);
+        image::Luma([(255.0 * (far - d) / (far - near)) as u8])
+    })
+}
+
+// looks at LittleDog from a camera held to the left of its body while a
+// front leg bends, saving the first color and depth images; pass `--fallback` to
+// use a software adapter
+pub fn run() -> anyhow::Result<()> {
+    let fallback = std::env::args().any(|a| a == "--fallback");
+    let mut program = OffscreenGraphics::new_offscreen(320, 240, fallback)?;
+    program.get_backend_info();
+
+    let mut robot = RobotDescriptor::from_str(include_str!("../assets/LittleDog.urdf"))
+        .expect("unable to read urdf");
+
+    let camera_buffer = program.create_camera_buffer();
+    let light_buffer = program.create_light_buffer();
+    let transform_buffers = program.robot_create_transform_buffers(&robot);
+    let mesh_buffers = program.robot_create_mesh_buffers(&robot);
+    program.create_bindings(&light_buffer, &camera_buffer, &transform_buffers);
+    let pipeline = program.create_render_pipeline(include_str!("../shaders/shader.wgsl"))?;
+
+    // 0.5 m to the left of the body, looking at it with its top up
+    let offset = Transform::new(glm::vec3(0.0, 0.5, 0.0), glm::vec3(-PI / 2.0, PI, 0.0));
+    let intrinsics = CameraIntrinsics::from_fov(320, 240, PI / 2.0, 0.05, 2.0);
+    let camera = RgbdCamera::new(&mut program, &robot, "body", offset, intrinsics)?;
+
+    for step in 0..10 {
+        let knee = -1.5 * step as f32 / 9.0;
+        robot
+            .set_joint_positions_by_name([("front_left_knee", knee)])
+            .expect("unknown joint");
+        robot.build();
+        program.robot_assign_transform_buffers(&robot, &transform_buffers);
+        let frame = camera.capture(&program, &robot, &pipeline, &mesh_buffers)?;
+        let cloud = frame.point_cloud();
+        let nearest = frame.depth.iter().cloned().fold(f32::INFINITY, f32::min);
+        println!(
+            "step {step}: {} points, nearest {:.3} m",
+            cloud.len(),
+            nearest
+        );
+        if step == 0 {
+            frame.color.save("rgbd_color.png")?;
+            depth_image(&frame).save("rgbd_depth.png")?;
+        }
+    }
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    run()
+}
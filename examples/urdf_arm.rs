@@ -69,23 +69,13 @@ pub fn run() -> anyhow::Result<()> {
                             p.update_camera(&camera_buffer);
                             p.update_light(&light_buffer);
                             increment = (increment + 0.02) % (2.0 * PI);
-                            robot.set_joint_position(
-                                &[
-                                    0.,
-                                    0.,
-                                    increment.cos(),
-                                    -increment.cos(),
-                                    -increment.cos(),
-                                    0.,
-                                    0.,
-                                    0.,
-                                    0.,
-                                    0.,
-                                    0.,
-                                    0.,
-                                ],
-                                false,
-                            );
+                            robot
+                                .set_joint_positions_by_name([
+                                    ("shoulder_joint", increment.cos()),
+                                    ("elbow_joint", -increment.cos()),
+                                    ("wrist_joint", -increment.cos()),
+                                ])
+                                .expect("unknown joint");
                             robot.build();
                             p.robot_assign_transform_buffers(&robot, &transform_buffers);
                         });
@@ -0,0 +1,121 @@
+extern crate nalgebra_glm as glm;
+use std::str::FromStr;
+use wgpu_robotic_simulator::geometry::Transform;
+use wgpu_robotic_simulator::graphics::GraphicsProgram;
+use wgpu_robotic_simulator::ik::{IkMode, IkSolver};
+use wgpu_robotic_simulator::robot::RobotGraphics;
+use wgpu_robotic_simulator::shader::CreatePipeline;
+use wgpu_robotic_simulator::urdf::*;
+use wgpu_robotic_simulator::wgpu_program::WGPUGraphics;
+use winit::event::*;
+use winit::keyboard::{KeyCode, PhysicalKey};
+
+const TIP: &str = "hand_link";
+const BASE: &str = "world";
+const STEP: f32 = 0.005;
+
+// Moves the hand of the arm towards a target point steered with the keyboard:
+// J/L along x, K/I along y, U/O along z
+pub fn run() -> anyhow::Result<()> {
+    let event_loop = winit::event_loop::EventLoop::new()?;
+    let window = winit::window::Window::new(&event_loop)?;
+    let mut program = WGPUGraphics::new(1240, 860, &window);
+    program.get_backend_info();
+
+    let mut robot = RobotDescriptor::from_str(include_str!("../assets/xarm.urdf"))
+        .expect("unable to read urdf");
+    robot
+        .set_joint_positions_by_name([("shoulder_joint", 0.5), ("elbow_joint", 0.8)])
+        .expect("unknown joint");
+    robot.build();
+    let mut target = robot
+        .link_pose(&robot.joint_state(), TIP)
+        .expect("unknown link")
+        .translation();
+    let solver = IkSolver::new(IkMode::Position).with_max_iterations(20);
+
+    //Initialize uniform buffers
+    let camera_buffer = program.create_camera_buffer();
+    let light_buffer = program.create_light_buffer();
+    let transform_buffers = program.robot_create_transform_buffers(&robot);
+    let mesh_buffers = program.robot_create_mesh_buffers(&robot);
+    program.create_bindings(&light_buffer, &camera_buffer, &transform_buffers);
+
+    // Create pipeline from vertex, fragment shaders
+    let pipeline = program
+        .create_render_pipeline(include_str!("../shaders/shader.wgsl"))
+        .expect("failed to get render pipeline!");
+
+    program.preloop(&mut |_| {
+        println!("Move the target with J/L (x), K/I (y) and U/O (z)");
+    });
+    event_loop.run(move |event, control_flow| {
+        match event {
+            // INPUT
+            Event::WindowEvent {
+                ref event,
+                window_id,
+            } if window_id == program.window.id() => {
+                match event {
+                    WindowEvent::CloseRequested => control_flow.exit(),
+                    WindowEvent::KeyboardInput {
+                        event:
+                            KeyEvent {
+                                state: ElementState::Pressed,
+                                physical_key: PhysicalKey::Code(keycode),
+                                ..
+                            },
+                        ..
+                    } => match keycode {
+                        KeyCode::Escape | KeyCode::KeyQ => control_flow.exit(),
+                        KeyCode::KeyJ => target += glm::vec3(-STEP, 0.0, 0.0),
+                        KeyCode::KeyL => target += glm::vec3(STEP, 0.0, 0.0),
+                        KeyCode::KeyK => target += glm::vec3(0.0, -STEP, 0.0),
+                        KeyCode::KeyI => target += glm::vec3(0.0, STEP, 0.0),
+                        KeyCode::KeyU => target += glm::vec3(0.0, 0.0, -STEP),
+                        KeyCode::KeyO => target += glm::vec3(0.0, 0.0, STEP),
+                        keycode => {
+                            program.process_keyboard(keycode);
+                        }
+                    },
+                    WindowEvent::RedrawRequested => {
+                        program.window.request_redraw();
+                        //UPDATE
+                        program.update(&mut |p| {
+                            p.update_camera(&camera_buffer);
+                            p.update_light(&light_buffer);
+                            let goal = Transform::new(target, glm::Vec3::zeros());
+                            let solution = solver
+                                .solve(&robot, TIP, BASE, &goal, &robot.joint_state())
+                                .expect("unknown link");
+                            if !solution.converged() {
+                                println!(
+                                    "{:?}: {:.4} m away from the target",
+                                    solution.status, solution.position_error
+                                );
+                            }
+                            robot
+                                .set_joint_state(&solution.state)
+                                .expect("unknown joint");
+                            robot.build();
+                            p.robot_assign_transform_buffers(&robot, &transform_buffers);
+                        });
+
+                        // RENDER
+                        program.render(&mut |p| {
+                            p.draw_robot(&robot, &mesh_buffers, &pipeline);
+                        });
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    })?;
+    Ok(())
+}
+
+pub fn main() -> anyhow::Result<()> {
+    run()?;
+    Ok(())
+}
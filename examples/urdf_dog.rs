@@ -1,42 +1,111 @@
-use physics_engine::graphics::GraphicsProgram;
-use physics_engine::shader::CreatePipeline;
-use physics_engine::urdf::*;
-use physics_engine::wgpu_program::WGPUGraphics;
-use std::f32::consts::PI;
+use nalgebra_glm as glm;
 use std::str::FromStr;
-use winit::{
-    event::*,
-    event_loop::{ControlFlow, EventLoop},
-};
+use std::time::Instant;
+use wgpu_robotic_simulator::articulated::ArticulatedBody;
+use wgpu_robotic_simulator::broad_phase::Aabb;
+use wgpu_robotic_simulator::geometry::Transform;
+use wgpu_robotic_simulator::graphics::GraphicsProgram;
+use wgpu_robotic_simulator::physics::*;
+use wgpu_robotic_simulator::shader::CreatePipeline;
+use wgpu_robotic_simulator::urdf::*;
+use wgpu_robotic_simulator::wgpu_program::WGPUGraphics;
+use winit::event::*;
+use winit::keyboard::{KeyCode, PhysicalKey};
 
-pub fn run_loop(mut program: WGPUGraphics, event_loop: EventLoop<()>) {
-    let shader_string = include_str!("../shaders/shader.wgsl");
+// LittleDog.urdf only gives the lower legs collision elements, so the body
+// gets the box around its mesh
+fn add_body_collision(robot: &mut RobotDescriptor) {
+    let root = robot.tree().root();
+    let body = &mut robot.links[root];
+    let Some(bounds) = Aabb::from_polyhedron(&body.visual.geometry, &Transform::default()) else {
+        return;
+    };
+    let origin = Origin {
+        xyz: bounds.center(),
+        rpy: None,
+    };
+    body.collisions.push(CollisionBody {
+        origin,
+        transform: origin.into(),
+        geometry: Default::default(),
+        shape: Some(Geometry::Box {
+            size: bounds.half_extents() * 2.0,
+        }),
+    });
+}
+
+const LEGS: [&str; 4] = ["front_left", "front_right", "back_left", "back_right"];
 
+fn drop_dog(world: &mut PhysicsWorld, robot: &RobotDescriptor) -> ArticulatedBody {
+    world.setup("");
+    world.substeps = 4;
+    world.add_body(Body::fixed(Shape::Plane, glm::Vec3::zeros()));
+    let start = Transform::new(glm::vec3(0.0, 0.0, 0.4), glm::vec3(0.2, 0.1, 0.0));
+    let dog = ArticulatedBody::spawn(world, robot, start);
+    for name in dog.joint_state(world).names() {
+        dog.set_gains(world, name, 5.0, 0.1).expect("unknown joint");
+    }
+    dog
+}
+
+// bends the legs by `crouch`, the front and back knees pointing towards
+// each other
+fn stance(world: &mut PhysicsWorld, dog: &ArticulatedBody, crouch: f32) {
+    for leg in LEGS {
+        let side = if leg.starts_with("front") { 1.0 } else { -1.0 };
+        let hip_roll = format!("{leg}_hip_roll");
+        let hip_pitch = format!("{leg}_hip_pitch");
+        let knee = format!("{leg}_knee");
+        dog.set_position_targets(
+            world,
+            [
+                (hip_roll.as_str(), 0.0),
+                (hip_pitch.as_str(), crouch * side),
+                (knee.as_str(), -2.0 * crouch * side),
+            ],
+        )
+        .expect("unknown joint");
+    }
+}
+
+pub fn run() -> anyhow::Result<()> {
+    let event_loop = winit::event_loop::EventLoop::new()?;
+    let window = winit::window::Window::new(&event_loop)?;
+    let mut program = WGPUGraphics::new(1240, 860, &window);
     program.get_backend_info();
 
-    // NOT WORKING !!
     let mut robot = RobotDescriptor::from_str(include_str!("../assets/LittleDog.urdf"))
         .expect("unable to read urdf");
+    add_body_collision(&mut robot);
+    // `robot` follows the simulation, `standing` is where it starts from
+    let standing = robot.clone();
+    let mut world = PhysicsWorld::new();
+    let mut dog = drop_dog(&mut world, &standing);
+    let ground = Shape::Plane.polyhedron();
 
     //Initialize uniform buffers
     let camera_buffer = program.create_camera_buffer();
     let light_buffer = program.create_light_buffer();
-    let transform_buffers = program.robot_create_transform_buffers(&robot);
-    let mesh_buffers = program.robot_create_mesh_buffers(&robot);
-    //
+    let transforms = |robot: &RobotDescriptor| {
+        let links = robot.links.iter().map(|l| l.visual.transform);
+        links.chain([Transform::default()]).collect::<Vec<_>>()
+    };
+    let transform_buffers = program.create_transform_buffers(transforms(&robot));
+    let meshes = robot.links.iter().map(|l| &l.visual.geometry);
+    let mesh_buffers = program.create_mesh_buffers(meshes.chain([&ground]));
     program.create_bindings(&light_buffer, &camera_buffer, &transform_buffers);
 
     // Create pipeline from vertex, fragment shaders
     let pipeline = program
-        .create_render_pipeline(shader_string)
+        .create_render_pipeline(include_str!("../shaders/shader.wgsl"))
         .expect("unable to create render pipeline");
 
-    let mut increment = 0.0;
     program.preloop(&mut |_| {
-        println!("Called one time before the loop!");
+        println!("Press R to drop the robot again, P to print its joint state");
     });
-    event_loop.run(move |event, _, control_flow| {
-        *control_flow = ControlFlow::Poll;
+    let start = Instant::now();
+    let mut last_frame = start;
+    event_loop.run(move |event, control_flow| {
         match event {
             // INPUT
             Event::WindowEvent {
@@ -44,54 +113,61 @@ pub fn run_loop(mut program: WGPUGraphics, event_loop: EventLoop<()>) {
                 window_id,
             } if window_id == program.window.id() => {
                 match event {
-                    WindowEvent::CloseRequested
-                     => *control_flow = ControlFlow::Exit,
-                    WindowEvent::KeyboardInput { input, .. } => {
-                        match input.virtual_keycode {
-                            Some(VirtualKeyCode::Escape) => if input.state == ElementState::Pressed {*control_flow = ControlFlow::Exit},
-                            // Some(VirtualKeyCode::Q) => *control_flow = ControlFlow::Exit,
-                            _ => {}
+                    WindowEvent::CloseRequested => control_flow.exit(),
+                    WindowEvent::KeyboardInput {
+                        event:
+                            KeyEvent {
+                                state: ElementState::Pressed,
+                                physical_key: PhysicalKey::Code(keycode),
+                                ..
+                            },
+                        ..
+                    } => match keycode {
+                        KeyCode::Escape | KeyCode::KeyQ => control_flow.exit(),
+                        KeyCode::KeyR => dog = drop_dog(&mut world, &standing),
+                        KeyCode::KeyP => {
+                            let state = dog.joint_state(&world);
+                            for (i, name) in state.names().iter().enumerate() {
+                                println!(
+                                    "{name}: position {:.3} velocity {:.3} effort {:.3}",
+                                    state.positions[i], state.velocities[i], state.efforts[i]
+                                );
+                            }
+                        }
+                        keycode => {
+                            program.process_keyboard(keycode);
                         }
+                    },
+                    WindowEvent::RedrawRequested => {
+                        program.window.request_redraw();
+                        //UPDATE
+                        let now = Instant::now();
+                        let t = (now - start).as_secs_f32();
+                        stance(&mut world, &dog, 0.6 + 0.25 * t.sin());
+                        world.advance((now - last_frame).as_secs_f32());
+                        last_frame = now;
+                        dog.sync(&world, &mut robot);
+                        program.update(&mut |p| {
+                            p.update_camera(&camera_buffer);
+                            p.update_light(&light_buffer);
+                            p.update_transforms(&transform_buffers, transforms(&robot));
+                        });
+
+                        // RENDER
+                        program.render(&mut |p| {
+                            p.draw_mesh_list(&pipeline, &mesh_buffers);
+                        });
                     }
-                    _ => {},
+                    _ => {}
                 }
-                if program.process_keyboard(event){}
-                
-            },
-            Event::DeviceEvent {
-                event: DeviceEvent::MouseMotion{ delta, },
-                .. // We're not using device_id currently
-            } =>  {
-                // program.mouse_look(
-                //     delta.0 as f32,
-                //     0.0
-                //     // delta.1 as f32
-                //     )
-            },
-            Event::RedrawRequested(window_id) if window_id == program.window.id() => {
-                //UPDATE
-                program.update(&mut |p| {
-                    p.update_camera(&camera_buffer);
-                    p.update_light(&light_buffer);
-                    increment = (increment + 0.02) % (2.0*PI);
-                    robot.set_joint_position(&[0.,0.,increment.cos(),-increment.cos(),-increment.cos(),0.,0.,0.,0.,0.,0.,0.,], false);
-                    robot.build();
-                    p.robot_assign_transform_buffers(&robot, &transform_buffers);
-                });
-
-                // RENDER
-                program.render(&mut |p| {
-                    p.draw_robot(&robot, &mesh_buffers, &pipeline);
-                });
             }
-            Event::MainEventsCleared => program.window.request_redraw(),
             _ => {}
         }
-    });
+    })?;
+    Ok(())
 }
-fn main() {
-    let event_loop = winit::event_loop::EventLoop::new();
-    let program = WGPUGraphics::new(1240, 860, &event_loop);
 
-    run_loop(program, event_loop);
+fn main() -> anyhow::Result<()> {
+    run()?;
+    Ok(())
 }
@@ -0,0 +1,21 @@
+// Copies a depth texture into a color target, for adapters that cannot copy
+// depth textures into buffers.
+
+@group(0) @binding(0)
+var depth: texture_2d<f32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // one triangle covering the viewport
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// the bits of the depth, lowest byte first, so that they are read back
+// exactly from an 8-bit target that every adapter can render to
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let bits = bitcast<u32>(textureLoad(depth, vec2<i32>(position.xy), 0).x);
+    let bytes = vec4<u32>(bits, bits >> 8u, bits >> 16u, bits >> 24u) & vec4<u32>(255u);
+    return vec4<f32>(bytes) / 255.0;
+}
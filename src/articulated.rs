@@ -0,0 +1,338 @@
+use crate::constraint::{JointConstraint, JointHandle, JointKind, JointMotor, MotorMode};
+use crate::geometry::Transform;
+use crate::headless::WorldState;
+use crate::joint_state::{JointError, JointState};
+use crate::physics::{Body, BodyHandle, BodyType, FreeBody, PhysicsWorld, Shape};
+use crate::urdf::{CollisionBody, Geometry, Joint, JointType, RobotDescriptor};
+use nalgebra::UnitQuaternion;
+use std::collections::HashMap;
+
+/// Mass given to links that declare none, so that they can still move.
+pub const DEFAULT_LINK_MASS: f32 = 0.01;
+
+/// A robot simulated as one rigid body per link, held together by joint
+/// constraints. Each body sits at the centre of mass of its link, i.e. at
+/// the link's inertial origin, and collides with the link's collision
+/// elements.
+///
+/// Every actuated joint has a motor, passive until given a command by joint
+/// name, and saturated by the effort and velocity limits of the URDF joint.
+/// Mimic joints are not actuated: their motor, in `MotorMode::Mimic`, holds
+/// them at the position the joint they mimic asks for, within the same
+/// limits, and cannot be commanded.
+#[derive(Debug, Clone)]
+pub struct ArticulatedBody {
+    links: Vec<BodyHandle>,
+    // inertial origin of every link: the body frame in the link frame
+    offsets: Vec<Transform>,
+    // `None` for floating joints, which leave the child free
+    joints: Vec<Option<JointHandle>>,
+    // index in `joints` of every joint name
+    joint_names: HashMap<String, usize>,
+    // the actuated joints, for measurements
+    state: JointState,
+}
+
+// the collision elements of a link as one shape in the body frame
+fn link_shape(collisions: &[CollisionBody], offset: &Transform) -> Shape {
+    let parts = collisions
+        .iter()
+        .map(|c| {
+            let shape = match c.shape {
+                Some(Geometry::Box { size }) => Shape::Cuboid {
+                    half_extents: size * 0.5,
+                },
+                Some(Geometry::Cylinder { radius, length }) => Shape::Cylinder {
+                    radius,
+                    height: length,
+                },
+                Some(Geometry::Sphere { radius }) => Shape::Sphere { radius },
+                Some(Geometry::Capsule { radius, length }) => Shape::Capsule {
+                    radius,
+                    height: length,
+                },
+                None => Shape::convex_hull(c.geometry.clone()),
+            };
+            (offset.inverse() * Transform::from(c.origin), shape)
+        })
+        .collect();
+    Shape::Compound { parts }
+}
+
+// a passive motor saturated by the effort and velocity limits of `joint`
+fn motor_limits(joint: &Joint) -> JointMotor {
+    // zero limits leave the motor unbounded
+    let bound = |limit: f32| if limit > 0.0 { limit } else { f32::INFINITY };
+    let limits = joint.limits().copied().unwrap_or_default();
+    JointMotor {
+        max_effort: bound(limits.effort),
+        max_velocity: bound(limits.velocity),
+        ..Default::default()
+    }
+}
+
+impl ArticulatedBody {
+    /// Adds the links and joints of `robot` to `world`, with the links where
+    /// the robot's last `build` placed them, moved by `base`. A root link
+    /// without mass is static, bolting the robot to the world.
+    pub fn spawn(world: &mut PhysicsWorld, robot: &RobotDescriptor, base: Transform) -> Self {
+        let root = robot.tree().root();
+        let mut links = Vec::new();
+        let mut offsets = Vec::new();
+        for (l, link) in robot.links.iter().enumerate() {
+            let offset: Transform = link.inertial.origin.into();
+            let pose = base * link.transform * offset;
+            let i = &link.inertial;
+            let massless = i.mass <= 0.0;
+            let mass = if massless { DEFAULT_LINK_MASS } else { i.mass };
+            let orientation = UnitQuaternion::from_matrix(&pose.rotation());
+            let dynamics = FreeBody::at(pose.translation(), mass).with_orientation(orientation);
+            let mut body = Body::new(link_shape(&link.collisions, &offset), dynamics);
+            let inertia = glm::mat3(
+                i.ixx, i.ixy, i.ixz, i.ixy, i.iyy, i.iyz, i.ixz, i.iyz, i.izz,
+            );
+            // fall back on the inertia of the collision shape, or of a small
+            // ball when there is none
+            let inertia = [inertia, body.dynamics.inertia]
+                .into_iter()
+                .find(|m| m.try_inverse().is_some())
+                .unwrap_or_else(|| Shape::Sphere { radius: 0.01 }.inertia(mass));
+            body.dynamics = body.dynamics.with_inertia(inertia);
+            if massless && l == root {
+                body.body_type = BodyType::Static;
+            }
+            links.push(world.add_body(body));
+            offsets.push(offset);
+        }
+
+        let joints = robot
+            .joints
+            .iter()
+            .zip(robot.joint_positions())
+            .map(|(joint, &q)| {
+                let kind = match joint.joint_type() {
+                    JointType::Revolute | JointType::Continuous => {
+                        JointKind::Revolute { axis: joint.axis() }
+                    }
+                    JointType::Prismatic => JointKind::Prismatic { axis: joint.axis() },
+                    JointType::Fixed => JointKind::Fixed,
+                    JointType::Floating => return None,
+                };
+                let (p, c) = (joint.parent(), joint.child());
+                let origin: Transform = joint.origin().into();
+                // the joint frame is the child link frame moved back to
+                // position zero
+                let frame_a = offsets[p].inverse() * origin;
+                let frame_b = offsets[c].inverse() * joint.transform_at(q).inverse() * origin;
+                let mut constraint =
+                    JointConstraint::new(kind, links[p], frame_a, links[c], frame_b);
+                if let (JointType::Revolute | JointType::Prismatic, Some(limits)) =
+                    (joint.joint_type(), joint.limits())
+                {
+                    if limits.lower < limits.upper {
+                        constraint = constraint.with_limits(limits.lower, limits.upper);
+                    }
+                }
+                if joint.is_actuated() {
+                    constraint = constraint.with_motor(motor_limits(joint));
+                }
+                Some(world.add_joint(constraint))
+            })
+            .collect::<Vec<_>>();
+        for (joint, &handle) in robot.joints.iter().zip(&joints) {
+            let Some((m, handle)) = joint.mimic().zip(handle) else {
+                continue;
+            };
+            let Some(source) = robot.joint_index(&m.joint).and_then(|k| joints[k]) else {
+                continue;
+            };
+            if let Some(constraint) = world.joint_mut(handle) {
+                constraint.motor = Some(JointMotor {
+                    mode: MotorMode::Mimic {
+                        joint: source,
+                        multiplier: m.multiplier,
+                        offset: m.offset,
+                    },
+                    ..motor_limits(joint)
+                });
+            }
+        }
+        let joint_names = robot
+            .joints
+            .iter()
+            .enumerate()
+            .map(|(i, j)| (j.name().to_owned(), i))
+            .collect();
+        Self {
+            links,
+            offsets,
+            joints,
+            joint_names,
+            state: JointState::new(robot),
+        }
+    }
+    /// Body simulating the link at `index` in `RobotDescriptor::links`.
+    pub fn link_body(&self, index: usize) -> BodyHandle {
+        self.links[index]
+    }
+    /// Index of the link simulated by `body`, e.g. to tell which link a ray
+    /// hit; `None` for bodies of other objects.
+    pub fn link_of(&self, body: BodyHandle) -> Option<usize> {
+        self.links.iter().position(|&h| h == body)
+    }
+    /// Constraint simulating the joint at `index` in
+    /// `RobotDescriptor::joints`, `None` for floating joints.
+    pub fn joint(&self, index: usize) -> Option<JointHandle> {
+        self.joints[index]
+    }
+    /// World transform of every link frame.
+    pub fn link_transforms(&self, world: &PhysicsWorld) -> Vec<Transform> {
+        std::iter::zip(&self.links, &self.offsets)
+            .map(|(&h, offset)| match world.body(h) {
+                Some(body) => body.transform * offset.inverse(),
+                None => Transform::default(),
+            })
+            .collect()
+    }
+    /// Position of every joint in joint order, 0 for joints without a
+    /// single degree of freedom.
+    pub fn joint_positions(&self, world: &PhysicsWorld) -> Vec<f32> {
+        self.joints
+            .iter()
+            .map(|j| {
+                j.and_then(|j| world.joint(j)?.position(world))
+                    .unwrap_or(0.0)
+            })
+            .collect()
+    }
+    /// Velocity of every joint in joint order, 0 for joints without a
+    /// single degree of freedom.
+    pub fn joint_velocities(&self, world: &PhysicsWorld) -> Vec<f32> {
+        self.joints
+            .iter()
+            .map(|j| {
+                j.and_then(|j| world.joint(j)?.velocity(world))
+                    .unwrap_or(0.0)
+            })
+            .collect()
+    }
+    /// Motor of the named actuated joint.
+    pub fn motor_mut<'w>(
+        &self,
+        world: &'w mut PhysicsWorld,
+        joint_name: &str,
+    ) -> Result<&'w mut JointMotor, JointError> {
+        let i = *self
+            .joint_names
+            .get(joint_name)
+            .ok_or_else(|| JointError::UnknownJoint(joint_name.to_owned()))?;
+        // mimic joints have a motor, driven by the joint they mimic
+        if self.state.index(joint_name).is_none() {
+            return Err(JointError::NotActuated(joint_name.to_owned()));
+        }
+        self.joints[i]
+            .and_then(|h| world.joint_mut(h)?.motor.as_mut())
+            .ok_or_else(|| JointError::NotActuated(joint_name.to_owned()))
+    }
+    /// Sets the PD gains of the named joint's position control.
+    pub fn set_gains(
+        &self,
+        world: &mut PhysicsWorld,
+        joint_name: &str,
+        stiffness: f32,
+        damping: f32,
+    ) -> Result<(), JointError> {
+        let motor = self.motor_mut(world, joint_name)?;
+        motor.stiffness = stiffness;
+        motor.damping = damping;
+        Ok(())
+    }
+    /// Drives the named joint towards `position` with its PD gains.
+    pub fn set_position_target(
+        &self,
+        world: &mut PhysicsWorld,
+        joint_name: &str,
+        position: f32,
+    ) -> Result<(), JointError> {
+        self.motor_mut(world, joint_name)?.mode = MotorMode::Position {
+            position,
+            velocity: 0.0,
+        };
+        Ok(())
+    }
+    /// Drives the named joints towards their positions, stopping at the
+    /// first unknown or unactuated joint.
+    pub fn set_position_targets<'a, I>(
+        &self,
+        world: &mut PhysicsWorld,
+        positions: I,
+    ) -> Result<(), JointError>
+    where
+        I: IntoIterator<Item = (&'a str, f32)>,
+    {
+        positions
+            .into_iter()
+            .try_for_each(|(name, position)| self.set_position_target(world, name, position))
+    }
+    /// Spins the named joint at `velocity`.
+    pub fn set_velocity_target(
+        &self,
+        world: &mut PhysicsWorld,
+        joint_name: &str,
+        velocity: f32,
+    ) -> Result<(), JointError> {
+        self.motor_mut(world, joint_name)?.mode = MotorMode::Velocity(velocity);
+        Ok(())
+    }
+    /// Applies `effort` at the named joint; zero lets it move freely.
+    pub fn set_effort(
+        &self,
+        world: &mut PhysicsWorld,
+        joint_name: &str,
+        effort: f32,
+    ) -> Result<(), JointError> {
+        self.motor_mut(world, joint_name)?.mode = MotorMode::Effort(effort);
+        Ok(())
+    }
+    /// Measured position and velocity of every actuated joint, with the
+    /// effort its motor applied over the last substep.
+    pub fn joint_state(&self, world: &PhysicsWorld) -> JointState {
+        let mut state = self.state.clone();
+        for (i, name) in self.state.names().iter().enumerate() {
+            let Some(joint) = self.joints[self.joint_names[name]].and_then(|h| world.joint(h))
+            else {
+                continue;
+            };
+            state.positions[i] = joint.position(world).unwrap_or(0.0);
+            state.velocities[i] = joint.velocity(world).unwrap_or(0.0);
+            state.efforts[i] = joint.applied_effort();
+        }
+        state
+    }
+    /// The actuated joints as `state` recorded them, e.g. one of the states
+    /// returned by `PhysicsWorld::run_headless`.
+    pub fn recorded_joint_state(&self, state: &WorldState) -> JointState {
+        let mut joints = self.state.clone();
+        for (i, name) in self.state.names().iter().enumerate() {
+            let Some(sample) = self.joints[self.joint_names[name]].and_then(|h| state.joint(h))
+            else {
+                continue;
+            };
+            joints.positions[i] = sample.position.unwrap_or(0.0);
+            joints.velocities[i] = sample.velocity.unwrap_or(0.0);
+            joints.efforts[i] = sample.effort;
+        }
+        joints
+    }
+    /// Copies the simulated state into `robot`, the descriptor this was
+    /// spawned from, so that it renders where the simulation put it.
+    pub fn sync(&self, world: &PhysicsWorld, robot: &mut RobotDescriptor) {
+        robot.place_links(&self.link_transforms(world), &self.joint_positions(world));
+    }
+    /// Takes the robot out of `world`, along with its joints.
+    pub fn remove(self, world: &mut PhysicsWorld) {
+        for h in self.links {
+            world.remove_body(h);
+        }
+    }
+}
@@ -0,0 +1,460 @@
+use crate::geometry::{Polyhedron, Transform};
+use crate::urdf::RobotDescriptor;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+/// Axis-aligned bounding box in world space.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: glm::Vec3,
+    pub max: glm::Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: glm::Vec3, max: glm::Vec3) -> Self {
+        Self { min, max }
+    }
+    /// Smallest box containing every point, `None` without points.
+    pub fn from_points<I>(points: I) -> Option<Self>
+    where
+        I: IntoIterator<Item = glm::Vec3>,
+    {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(Self::new(first, first), |aabb, p| Self {
+            min: glm::min2(&aabb.min, &p),
+            max: glm::max2(&aabb.max, &p),
+        }))
+    }
+    /// Box around a mesh placed at `transform`.
+    pub fn from_polyhedron(mesh: &Polyhedron, transform: &Transform) -> Option<Self> {
+        Self::from_points(mesh.verts.iter().map(|v| (*transform * *v).position))
+    }
+    /// Box around a box of half size `half_extents` centred at `transform`.
+    pub fn from_box(half_extents: &glm::Vec3, transform: &Transform) -> Self {
+        let r = transform.rotation().abs() * half_extents;
+        let c = transform.translation();
+        Self::new(c - r, c + r)
+    }
+    pub fn center(&self) -> glm::Vec3 {
+        (self.min + self.max) * 0.5
+    }
+    pub fn half_extents(&self) -> glm::Vec3 {
+        (self.max - self.min) * 0.5
+    }
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        (0..3).all(|i| self.min[i] <= other.max[i] && other.min[i] <= self.max[i])
+    }
+    pub fn contains(&self, other: &Aabb) -> bool {
+        (0..3).all(|i| self.min[i] <= other.min[i] && other.max[i] <= self.max[i])
+    }
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Self::new(
+            glm::min2(&self.min, &other.min),
+            glm::max2(&self.max, &other.max),
+        )
+    }
+    /// The box grown by `margin` on every side.
+    pub fn fattened(&self, margin: f32) -> Aabb {
+        let m = glm::Vec3::repeat(margin);
+        Self::new(self.min - m, self.max + m)
+    }
+    pub fn surface_area(&self) -> f32 {
+        let d = self.max - self.min;
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+}
+
+/// Bodies collide only when each one's group is in the other's mask.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CollisionFilter {
+    pub group: u32,
+    pub mask: u32,
+}
+
+impl Default for CollisionFilter {
+    fn default() -> Self {
+        Self {
+            group: 1,
+            mask: u32::MAX,
+        }
+    }
+}
+
+impl CollisionFilter {
+    pub fn new(group: u32, mask: u32) -> Self {
+        Self { group, mask }
+    }
+    pub fn can_collide(&self, other: &CollisionFilter) -> bool {
+        self.group & other.mask != 0 && other.group & self.mask != 0
+    }
+}
+
+/// Identifies a leaf of an `AabbTree`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ProxyId(usize);
+
+#[derive(Debug, Clone)]
+struct Node<T> {
+    aabb: Aabb,
+    parent: Option<usize>,
+    children: Option<[usize; 2]>,
+    height: usize,
+    data: Option<T>,
+}
+
+/// Dynamic bounding volume hierarchy over fattened boxes, so objects moving
+/// less than `margin` between updates do not touch the tree. Insertion picks
+/// the sibling of least surface area cost and rotations keep it balanced.
+#[derive(Debug, Clone)]
+pub struct AabbTree<T> {
+    nodes: Vec<Node<T>>,
+    free: Vec<usize>,
+    root: Option<usize>,
+    pub margin: f32,
+}
+
+impl<T> Default for AabbTree<T> {
+    fn default() -> Self {
+        Self::new(0.05)
+    }
+}
+
+impl<T> AabbTree<T> {
+    pub fn new(margin: f32) -> Self {
+        Self {
+            nodes: Vec::new(),
+            free: Vec::new(),
+            root: None,
+            margin,
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+    pub fn get(&self, id: ProxyId) -> Option<&T> {
+        self.nodes.get(id.0).and_then(|n| n.data.as_ref())
+    }
+    /// The fattened box stored for a leaf.
+    pub fn fat_aabb(&self, id: ProxyId) -> Aabb {
+        self.nodes[id.0].aabb
+    }
+    pub fn insert(&mut self, aabb: Aabb, data: T) -> ProxyId {
+        let leaf = self.allocate(Node {
+            aabb: aabb.fattened(self.margin),
+            parent: None,
+            children: None,
+            height: 0,
+            data: Some(data),
+        });
+        self.insert_leaf(leaf);
+        ProxyId(leaf)
+    }
+    pub fn remove(&mut self, id: ProxyId) -> Option<T> {
+        let data = self.nodes.get_mut(id.0)?.data.take()?;
+        self.remove_leaf(id.0);
+        self.free.push(id.0);
+        Some(data)
+    }
+    /// Moves a leaf to `aabb`; returns whether the tree had to change.
+    pub fn update(&mut self, id: ProxyId, aabb: Aabb) -> bool {
+        if self.nodes[id.0].aabb.contains(&aabb) {
+            return false;
+        }
+        self.remove_leaf(id.0);
+        self.nodes[id.0].aabb = aabb.fattened(self.margin);
+        self.insert_leaf(id.0);
+        true
+    }
+    /// Calls `visit` for every leaf whose box overlaps `aabb`.
+    pub fn query<F>(&self, aabb: &Aabb, mut visit: F)
+    where
+        F: FnMut(ProxyId, &T),
+    {
+        self.traverse(|node| node.overlaps(aabb), |id, data| visit(id, data));
+    }
+    /// Every pair of leaves with overlapping boxes, each pair once.
+    pub fn overlapping_pairs(&self) -> Vec<(ProxyId, ProxyId)> {
+        let mut pairs = Vec::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            if node.data.is_none() {
+                continue;
+            }
+            self.query(&node.aabb, |other, _| {
+                if other.0 > i {
+                    pairs.push((ProxyId(i), other));
+                }
+            });
+        }
+        pairs
+    }
+    /// Depth first walk into every node whose box `enter` accepts, calling
+    /// `visit` on the leaves reached.
+    pub fn traverse<E, F>(&self, mut enter: E, mut visit: F)
+    where
+        E: FnMut(&Aabb) -> bool,
+        F: FnMut(ProxyId, &T),
+    {
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+            if !enter(&node.aabb) {
+                continue;
+            }
+            match (node.children, &node.data) {
+                (Some(children), _) => stack.extend(children),
+                (None, Some(data)) => visit(ProxyId(i), data),
+                (None, None) => {}
+            }
+        }
+    }
+
+    fn allocate(&mut self, node: Node<T>) -> usize {
+        match self.free.pop() {
+            Some(i) => {
+                self.nodes[i] = node;
+                i
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+    fn insert_leaf(&mut self, leaf: usize) {
+        let Some(root) = self.root else {
+            self.nodes[leaf].parent = None;
+            self.root = Some(leaf);
+            return;
+        };
+        let aabb = self.nodes[leaf].aabb;
+
+        // descend towards the cheapest sibling
+        let mut index = root;
+        while let Some([left, right]) = self.nodes[index].children {
+            let area = self.nodes[index].aabb.surface_area();
+            let combined = self.nodes[index].aabb.union(&aabb).surface_area();
+            let cost = 2.0 * combined;
+            let inheritance = 2.0 * (combined - area);
+            let child_cost = |c: usize| {
+                let node = &self.nodes[c];
+                let grown = node.aabb.union(&aabb).surface_area();
+                match node.children {
+                    None => grown + inheritance,
+                    Some(_) => grown - node.aabb.surface_area() + inheritance,
+                }
+            };
+            let (cost_left, cost_right) = (child_cost(left), child_cost(right));
+            if cost < cost_left && cost < cost_right {
+                break;
+            }
+            index = if cost_left < cost_right { left } else { right };
+        }
+
+        let sibling = index;
+        let old_parent = self.nodes[sibling].parent;
+        let parent = self.allocate(Node {
+            aabb: self.nodes[sibling].aabb.union(&aabb),
+            parent: old_parent,
+            children: Some([sibling, leaf]),
+            height: self.nodes[sibling].height + 1,
+            data: None,
+        });
+        self.nodes[sibling].parent = Some(parent);
+        self.nodes[leaf].parent = Some(parent);
+        match old_parent {
+            Some(p) => self.replace_child(p, sibling, parent),
+            None => self.root = Some(parent),
+        }
+        self.refit(Some(parent));
+    }
+    fn remove_leaf(&mut self, leaf: usize) {
+        if self.root == Some(leaf) {
+            self.root = None;
+            return;
+        }
+        let parent = self.nodes[leaf].parent.unwrap();
+        let [left, right] = self.nodes[parent].children.unwrap();
+        let sibling = if left == leaf { right } else { left };
+        let grandparent = self.nodes[parent].parent;
+        self.nodes[sibling].parent = grandparent;
+        match grandparent {
+            Some(g) => {
+                self.replace_child(g, parent, sibling);
+                self.refit(Some(g));
+            }
+            None => self.root = Some(sibling),
+        }
+        self.nodes[parent].children = None;
+        self.free.push(parent);
+    }
+    fn replace_child(&mut self, parent: usize, old: usize, new: usize) {
+        if let Some(children) = self.nodes[parent].children.as_mut() {
+            for c in children.iter_mut().filter(|c| **c == old) {
+                *c = new;
+            }
+        }
+    }
+    // walk up from `index` rebalancing and recomputing boxes and heights
+    fn refit(&mut self, mut index: Option<usize>) {
+        while let Some(i) = index {
+            let i = self.balance(i);
+            let [left, right] = self.nodes[i].children.unwrap();
+            self.nodes[i].height = 1 + self.nodes[left].height.max(self.nodes[right].height);
+            self.nodes[i].aabb = self.nodes[left].aabb.union(&self.nodes[right].aabb);
+            index = self.nodes[i].parent;
+        }
+    }
+    // rotates the taller grandchild above `a` when its children differ in
+    // height by more than one; returns the node now in `a`'s place
+    fn balance(&mut self, a: usize) -> usize {
+        let Some([b, c]) = self.nodes[a].children else {
+            return a;
+        };
+        if self.nodes[a].height < 2 {
+            return a;
+        }
+        let (hb, hc) = (self.nodes[b].height, self.nodes[c].height);
+        if hc > hb + 1 {
+            self.rotate(a, c, b)
+        } else if hb > hc + 1 {
+            self.rotate(a, b, c)
+        } else {
+            a
+        }
+    }
+    // lifts `up`, the taller child of `a`, into the place of `a`
+    fn rotate(&mut self, a: usize, up: usize, other: usize) -> usize {
+        let [f, g] = self.nodes[up].children.unwrap();
+        let a_parent = self.nodes[a].parent;
+        self.nodes[up].parent = a_parent;
+        self.nodes[a].parent = Some(up);
+        match a_parent {
+            Some(p) => self.replace_child(p, a, up),
+            None => self.root = Some(up),
+        }
+        // the taller grandchild stays under `up`, the other moves to `a`
+        let (keep, give) = if self.nodes[f].height > self.nodes[g].height {
+            (f, g)
+        } else {
+            (g, f)
+        };
+        self.nodes[up].children = Some([a, keep]);
+        self.nodes[a].children = Some([other, give]);
+        self.nodes[give].parent = Some(a);
+        for i in [a, up] {
+            let [l, r] = self.nodes[i].children.unwrap();
+            self.nodes[i].aabb = self.nodes[l].aabb.union(&self.nodes[r].aabb);
+            self.nodes[i].height = 1 + self.nodes[l].height.max(self.nodes[r].height);
+        }
+        up
+    }
+}
+
+/// Candidate pairs of objects whose boxes overlap, honouring collision
+/// filters and explicitly excluded pairs.
+#[derive(Debug, Clone)]
+pub struct BroadPhase<K> {
+    tree: AabbTree<(K, CollisionFilter)>,
+    proxies: HashMap<K, ProxyId>,
+    excluded: HashSet<(K, K)>,
+}
+
+impl<K: Copy + Eq + Hash> Default for BroadPhase<K> {
+    fn default() -> Self {
+        Self {
+            tree: AabbTree::default(),
+            proxies: HashMap::new(),
+            excluded: HashSet::new(),
+        }
+    }
+}
+
+impl<K: Copy + Eq + Hash> BroadPhase<K> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn len(&self) -> usize {
+        self.proxies.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.proxies.is_empty()
+    }
+    pub fn contains(&self, key: K) -> bool {
+        self.proxies.contains_key(&key)
+    }
+    /// Adds `key`, or moves it if already present.
+    pub fn insert(&mut self, key: K, aabb: Aabb, filter: CollisionFilter) {
+        match self.proxies.get(&key) {
+            Some(&id) => {
+                self.tree.update(id, aabb);
+                self.tree.nodes[id.0].data = Some((key, filter));
+            }
+            None => {
+                let id = self.tree.insert(aabb, (key, filter));
+                self.proxies.insert(key, id);
+            }
+        }
+    }
+    pub fn remove(&mut self, key: K) {
+        if let Some(id) = self.proxies.remove(&key) {
+            self.tree.remove(id);
+        }
+        self.excluded.retain(|(a, b)| *a != key && *b != key);
+    }
+    /// Never report `a` and `b` as a pair, e.g. links sharing a joint.
+    pub fn exclude_pair(&mut self, a: K, b: K) {
+        self.excluded.insert((a, b));
+        self.excluded.insert((b, a));
+    }
+    pub fn is_excluded(&self, a: K, b: K) -> bool {
+        self.excluded.contains(&(a, b))
+    }
+    /// Keys whose boxes overlap `aabb`.
+    pub fn query(&self, aabb: &Aabb) -> Vec<K> {
+        let mut found = Vec::new();
+        self.tree.query(aabb, |_, (key, _)| found.push(*key));
+        found
+    }
+    pub fn pairs(&self) -> Vec<(K, K)> {
+        self.tree
+            .overlapping_pairs()
+            .into_iter()
+            .filter_map(|(a, b)| {
+                let (ka, fa) = self.tree.get(a)?;
+                let (kb, fb) = self.tree.get(b)?;
+                (fa.can_collide(fb) && !self.is_excluded(*ka, *kb)).then_some((*ka, *kb))
+            })
+            .collect()
+    }
+}
+
+impl RobotDescriptor {
+    /// World space box around the collision geometry of every link, as
+    /// placed by the last `build`; `None` for links without geometry.
+    pub fn link_aabbs(&self) -> Vec<Option<Aabb>> {
+        self.links
+            .iter()
+            .map(|l| {
+                l.collisions
+                    .iter()
+                    .filter_map(|c| Aabb::from_polyhedron(&c.geometry, &c.transform))
+                    .reduce(|a, b| a.union(&b))
+            })
+            .collect()
+    }
+    /// Pairs of links whose collision boxes overlap, leaving out links
+    /// connected by a joint.
+    pub fn self_collision_pairs(&self) -> Vec<(usize, usize)> {
+        let mut broad_phase = BroadPhase::new();
+        for (l, aabb) in self.link_aabbs().into_iter().enumerate() {
+            if let Some(aabb) = aabb {
+                broad_phase.insert(l, aabb, CollisionFilter::default());
+            }
+        }
+        for j in &self.joints {
+            broad_phase.exclude_pair(j.parent(), j.child());
+        }
+        broad_phase.pairs()
+    }
+}
@@ -0,0 +1,205 @@
+use crate::geometry::Transform;
+use crate::physics::{BodyHandle, PhysicsWorld};
+use nalgebra::UnitQuaternion;
+
+/// Motion a joint leaves free between its two bodies.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum JointKind {
+    /// No relative motion.
+    Fixed,
+    /// Rotation about `axis`, given in the joint frame.
+    Revolute { axis: glm::Vec3 },
+    /// Translation along `axis`, given in the joint frame.
+    Prismatic { axis: glm::Vec3 },
+    /// Any rotation about the joint origin.
+    Spherical,
+}
+
+/// What a joint motor does each step.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MotorMode {
+    /// Applies a torque, or a force along a prismatic joint.
+    Effort(f32),
+    /// Holds the joint at a speed, as far as the effort allows.
+    Velocity(f32),
+    /// PD control towards a position, moving at `velocity` once there.
+    Position { position: f32, velocity: f32 },
+    /// Holds the joint at `multiplier * q + offset`, with `q` the position
+    /// of `joint`, as firmly as the effort allows; the gains are not used.
+    /// Only this joint is driven, `joint` does not feel the coupling.
+    Mimic {
+        joint: JointHandle,
+        multiplier: f32,
+        offset: f32,
+    },
+}
+
+/// Actuator driving the free axis of a revolute or prismatic joint. The
+/// PD law is applied implicitly by the solver, so stiff gains stay stable.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct JointMotor {
+    pub mode: MotorMode,
+    /// Proportional gain of `MotorMode::Position`, effort per unit of
+    /// position error.
+    pub stiffness: f32,
+    /// Derivative gain of `MotorMode::Position`, effort per unit of
+    /// velocity error.
+    pub damping: f32,
+    /// Largest effort the motor applies.
+    pub max_effort: f32,
+    /// Fastest the motor drives the joint.
+    pub max_velocity: f32,
+}
+
+impl Default for JointMotor {
+    fn default() -> Self {
+        Self {
+            mode: MotorMode::Effort(0.0),
+            stiffness: 0.0,
+            damping: 0.0,
+            max_effort: f32::INFINITY,
+            max_velocity: f32::INFINITY,
+        }
+    }
+}
+
+impl JointMotor {
+    /// Effort to apply under `MotorMode::Effort` for a joint moving at
+    /// `velocity`: saturated, and nothing pushing it past `max_velocity`.
+    pub(crate) fn effort(&self, velocity: f32) -> f32 {
+        let MotorMode::Effort(effort) = self.mode else {
+            return 0.0;
+        };
+        let effort = effort.clamp(-self.max_effort, self.max_effort);
+        if velocity.abs() >= self.max_velocity && effort * velocity > 0.0 {
+            0.0
+        } else {
+            effort
+        }
+    }
+}
+
+/// Identifies a joint of a `PhysicsWorld`. Handles of removed joints are
+/// never reused.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JointHandle(pub(crate) usize);
+
+/// Holds two bodies together at a joint frame fixed to each of them. The
+/// two frames coincide when the joint is at position zero.
+#[derive(Debug, Clone)]
+pub struct JointConstraint {
+    pub a: BodyHandle,
+    pub b: BodyHandle,
+    /// Joint frame in the frame of body `a`.
+    pub frame_a: Transform,
+    /// Joint frame in the frame of body `b`.
+    pub frame_b: Transform,
+    pub kind: JointKind,
+    /// Lowest and highest position of a revolute or prismatic joint.
+    pub limits: Option<(f32, f32)>,
+    /// Drives a revolute or prismatic joint; without one it moves freely.
+    pub motor: Option<JointMotor>,
+    pub(crate) handle: JointHandle,
+    // impulses of the last step for warm starting: three linear rows, three
+    // angular rows, the limit, signed by the side it pushes from, and the
+    // motor
+    pub(crate) impulses: [f32; 8],
+    // effort the motor applied over the last substep
+    pub(crate) effort: f32,
+}
+
+impl JointConstraint {
+    pub fn new(
+        kind: JointKind,
+        a: BodyHandle,
+        frame_a: Transform,
+        b: BodyHandle,
+        frame_b: Transform,
+    ) -> Self {
+        Self {
+            a,
+            b,
+            frame_a,
+            frame_b,
+            kind,
+            limits: None,
+            motor: None,
+            handle: JointHandle(0),
+            impulses: [0.0; 8],
+            effort: 0.0,
+        }
+    }
+    pub fn with_limits(mut self, lower: f32, upper: f32) -> Self {
+        self.limits = Some((lower, upper));
+        self
+    }
+    pub fn with_motor(mut self, motor: JointMotor) -> Self {
+        self.motor = Some(motor);
+        self
+    }
+    /// Torque, or force, the motor applied over the last substep.
+    pub fn applied_effort(&self) -> f32 {
+        self.effort
+    }
+    pub fn handle(&self) -> JointHandle {
+        self.handle
+    }
+    /// World transforms of the joint frame on each body.
+    pub fn world_frames(&self, world: &PhysicsWorld) -> Option<(Transform, Transform)> {
+        let (a, b) = (world.body(self.a)?, world.body(self.b)?);
+        Some((a.transform * self.frame_a, b.transform * self.frame_b))
+    }
+    /// Free axis of a revolute or prismatic joint in world space, as seen
+    /// from body `a`.
+    pub fn world_axis(&self, world: &PhysicsWorld) -> Option<glm::Vec3> {
+        let (fa, _) = self.world_frames(world)?;
+        match self.kind {
+            JointKind::Revolute { axis } | JointKind::Prismatic { axis } => {
+                Some(fa.rotation() * axis.normalize())
+            }
+            JointKind::Fixed | JointKind::Spherical => None,
+        }
+    }
+    /// Angle of a revolute joint, in (-pi, pi], or offset of a prismatic one.
+    /// `None` for joints without a single degree of freedom.
+    pub fn position(&self, world: &PhysicsWorld) -> Option<f32> {
+        let (fa, fb) = self.world_frames(world)?;
+        match self.kind {
+            JointKind::Revolute { axis } => {
+                // twist of the relative rotation about the axis
+                let relative = fa.rotation().transpose() * fb.rotation();
+                let q = UnitQuaternion::from_matrix(&relative);
+                let twist = q.imag().dot(&axis.normalize());
+                let angle = 2.0 * twist.atan2(q.w);
+                Some(if angle > std::f32::consts::PI {
+                    angle - std::f32::consts::TAU
+                } else if angle <= -std::f32::consts::PI {
+                    angle + std::f32::consts::TAU
+                } else {
+                    angle
+                })
+            }
+            JointKind::Prismatic { axis } => {
+                let n = fa.rotation() * axis.normalize();
+                Some((fb.translation() - fa.translation()).dot(&n))
+            }
+            JointKind::Fixed | JointKind::Spherical => None,
+        }
+    }
+    /// Rate of change of `position`.
+    pub fn velocity(&self, world: &PhysicsWorld) -> Option<f32> {
+        let (a, b) = (&world.body(self.a)?.dynamics, &world.body(self.b)?.dynamics);
+        let n = self.world_axis(world)?;
+        match self.kind {
+            JointKind::Revolute { .. } => Some((b.omega - a.omega).dot(&n)),
+            JointKind::Prismatic { .. } => {
+                let (fa, fb) = self.world_frames(world)?;
+                let (pa, pb) = (fa.translation(), fb.translation());
+                let relative = b.velocity_at(pb) - a.velocity_at(pa);
+                // the axis turns with body a
+                Some(relative.dot(&n) + (pb - pa).dot(&a.omega.cross(&n)))
+            }
+            JointKind::Fixed | JointKind::Spherical => None,
+        }
+    }
+}
@@ -0,0 +1,380 @@
+use crate::geometry::Transform;
+use crate::joint_state::{JointError, JointState};
+use crate::urdf::{JointDynamics, JointType, Link, RobotDescriptor};
+use nalgebra::{DMatrix, DVector, Matrix3, Matrix6, Vector6};
+
+// Spatial vectors are (angular, linear) pairs expressed in world coordinates
+// about the world origin, so quantities of different links add directly.
+type SpatialVector = Vector6<f32>;
+type SpatialInertia = Matrix6<f32>;
+
+fn skew(v: &glm::Vec3) -> Matrix3<f32> {
+    Matrix3::new(0.0, -v.z, v.y, v.z, 0.0, -v.x, -v.y, v.x, 0.0)
+}
+fn spatial(angular: glm::Vec3, linear: glm::Vec3) -> SpatialVector {
+    SpatialVector::new(
+        angular.x, angular.y, angular.z, linear.x, linear.y, linear.z,
+    )
+}
+fn angular(v: &SpatialVector) -> glm::Vec3 {
+    v.fixed_rows::<3>(0).into_owned()
+}
+fn linear(v: &SpatialVector) -> glm::Vec3 {
+    v.fixed_rows::<3>(3).into_owned()
+}
+// motion cross product, v x m
+fn cross_motion(v: &SpatialVector, m: &SpatialVector) -> SpatialVector {
+    let (w, v0) = (angular(v), linear(v));
+    spatial(
+        w.cross(&angular(m)),
+        w.cross(&linear(m)) + v0.cross(&angular(m)),
+    )
+}
+// force cross product, v x* f
+fn cross_force(v: &SpatialVector, f: &SpatialVector) -> SpatialVector {
+    let (w, v0) = (angular(v), linear(v));
+    spatial(
+        w.cross(&angular(f)) + v0.cross(&linear(f)),
+        w.cross(&linear(f)),
+    )
+}
+
+// viscous damping and Coulomb friction opposing the joint velocity
+fn passive_force(dynamics: &JointDynamics, velocity: f32) -> f32 {
+    let friction = if velocity == 0.0 {
+        0.0
+    } else {
+        dynamics.friction * velocity.signum()
+    };
+    -dynamics.damping * velocity - friction
+}
+
+/// Spatial inertia of a link placed at `frame`, about the world origin.
+fn spatial_inertia(link: &Link, frame: &Transform) -> SpatialInertia {
+    let body = &link.inertial;
+    let com = *frame * Transform::from(body.origin);
+    let r = com.rotation();
+    let c = skew(&com.translation());
+    let local = Matrix3::new(
+        body.ixx, body.ixy, body.ixz, body.ixy, body.iyy, body.iyz, body.ixz, body.iyz, body.izz,
+    );
+    let rotational = r * local * r.transpose() + body.mass * c * c.transpose();
+    let mut inertia = SpatialInertia::zeros();
+    inertia.fixed_view_mut::<3, 3>(0, 0).copy_from(&rotational);
+    inertia
+        .fixed_view_mut::<3, 3>(0, 3)
+        .copy_from(&(body.mass * c));
+    inertia
+        .fixed_view_mut::<3, 3>(3, 0)
+        .copy_from(&(body.mass * c.transpose()));
+    inertia
+        .fixed_view_mut::<3, 3>(3, 3)
+        .copy_from(&(Matrix3::identity() * body.mass));
+    inertia
+}
+
+/// Everything the recursive algorithms need about the robot at one
+/// configuration: world frames, joint motion axes and link inertias.
+pub(crate) struct Articulation {
+    /// Link indices, parents first.
+    pub order: Vec<usize>,
+    /// For each link, the joint to its parent and the parent link.
+    pub parent: Vec<Option<(usize, usize)>>,
+    /// Motion subspace of each joint, zero for fixed and floating joints.
+    pub axes: Vec<SpatialVector>,
+    /// State index driving each joint and the factor applied to it.
+    pub dofs: Vec<Option<(usize, f32)>>,
+    pub inertias: Vec<SpatialInertia>,
+}
+
+impl Articulation {
+    pub fn new(robot: &RobotDescriptor, state: &JointState) -> Self {
+        let frames = robot.forward_kinematics(state);
+        let tree = robot.tree();
+        let parent = (0..robot.links.len())
+            .map(|l| tree.parent_joint(l).map(|j| (j, robot.joints[j].parent())))
+            .collect();
+        let axes = robot
+            .joints
+            .iter()
+            .map(|j| {
+                let frame = frames[j.child()];
+                let z = frame.rotation() * j.axis();
+                match j.joint_type() {
+                    JointType::Revolute | JointType::Continuous => {
+                        spatial(z, frame.translation().cross(&z))
+                    }
+                    JointType::Prismatic => spatial(glm::Vec3::zeros(), z),
+                    JointType::Fixed | JointType::Floating => SpatialVector::zeros(),
+                }
+            })
+            .collect();
+        let dofs = robot
+            .joints
+            .iter()
+            .map(|j| match j.mimic() {
+                Some(m) => state.index(&m.joint).map(|i| (i, m.multiplier)),
+                None => state.index(j.name()).map(|i| (i, 1.0)),
+            })
+            .collect();
+        let inertias = robot
+            .links
+            .iter()
+            .zip(&frames)
+            .map(|(l, f)| spatial_inertia(l, f))
+            .collect();
+        Self {
+            order: tree.order().to_vec(),
+            parent,
+            axes,
+            dofs,
+            inertias,
+        }
+    }
+    /// Joint space value of joint `j` given per-dof values `q`.
+    pub fn joint_value(&self, j: usize, q: &[f32]) -> f32 {
+        self.dofs[j].map_or(0.0, |(i, scale)| scale * q[i])
+    }
+
+    /// Recursive Newton-Euler: generalized forces producing accelerations
+    /// `qdd` at velocities `qd` under `gravity`, one entry per dof.
+    pub fn rnea(&self, qd: &[f32], qdd: &[f32], gravity: glm::Vec3) -> Vec<f32> {
+        let n = self.inertias.len();
+        // the fixed base accelerates upwards instead of applying gravity to
+        // every link
+        let base = spatial(glm::Vec3::zeros(), -gravity);
+        let mut v = vec![SpatialVector::zeros(); n];
+        let mut a = vec![base; n];
+        let mut f = vec![SpatialVector::zeros(); n];
+        for &l in &self.order {
+            let Some((j, p)) = self.parent[l] else {
+                continue;
+            };
+            let s = self.axes[j];
+            v[l] = v[p] + s * self.joint_value(j, qd);
+            a[l] = a[p]
+                + s * self.joint_value(j, qdd)
+                + cross_motion(&v[l], &(s * self.joint_value(j, qd)));
+            let inertia = &self.inertias[l];
+            f[l] = inertia * a[l] + cross_force(&v[l], &(inertia * v[l]));
+        }
+        let mut tau = vec![0.0; qd.len()];
+        for &l in self.order.iter().rev() {
+            let Some((j, p)) = self.parent[l] else {
+                continue;
+            };
+            if let Some((i, scale)) = self.dofs[j] {
+                tau[i] += scale * self.axes[j].dot(&f[l]);
+            }
+            let fl = f[l];
+            f[p] += fl;
+        }
+        tau
+    }
+
+    /// Composite rigid body algorithm: joint space inertia matrix, one row
+    /// and column per dof.
+    pub fn crba(&self, n_dofs: usize) -> DMatrix<f32> {
+        let mut composite = self.inertias.clone();
+        for &l in self.order.iter().rev() {
+            if let Some((_, p)) = self.parent[l] {
+                let c = composite[l];
+                composite[p] += c;
+            }
+        }
+        let mut mass = DMatrix::zeros(n_dofs, n_dofs);
+        for &l in &self.order {
+            let Some((j, _)) = self.parent[l] else {
+                continue;
+            };
+            let Some((dj, sj)) = self.dofs[j] else {
+                continue;
+            };
+            let force = composite[l] * self.axes[j];
+            // the force transmitted across joint j loads every joint above it
+            let mut ancestor = Some((j, l));
+            while let Some((k, child)) = ancestor {
+                if let Some((dk, sk)) = self.dofs[k] {
+                    let h = sj * sk * self.axes[k].dot(&force);
+                    mass[(dk, dj)] += h;
+                    if k != j {
+                        mass[(dj, dk)] += h;
+                    }
+                }
+                let p = self.parent[child].unwrap().1;
+                ancestor = self.parent[p].map(|(k, _)| (k, p));
+            }
+        }
+        mass
+    }
+
+    /// Articulated body algorithm: joint accelerations under `joint_efforts`
+    /// (one per joint) at velocities `qd` (one per dof). Joints without a dof
+    /// are rigid. Only valid without mimic joints, which couple dofs.
+    pub fn aba(&self, qd: &[f32], joint_efforts: &[f32], gravity: glm::Vec3) -> Vec<f32> {
+        let n = self.inertias.len();
+        let mut v = vec![SpatialVector::zeros(); n];
+        let mut c = vec![SpatialVector::zeros(); n];
+        let mut inertia = self.inertias.clone();
+        let mut bias = vec![SpatialVector::zeros(); n];
+        for &l in &self.order {
+            if let Some((j, p)) = self.parent[l] {
+                let vj = self.axes[j] * self.joint_value(j, qd);
+                v[l] = v[p] + vj;
+                c[l] = cross_motion(&v[l], &vj);
+            }
+            bias[l] = cross_force(&v[l], &(self.inertias[l] * v[l]));
+        }
+
+        let mut u = vec![SpatialVector::zeros(); self.axes.len()];
+        let mut d = vec![0.0; self.axes.len()];
+        let mut residual = vec![0.0; self.axes.len()];
+        for &l in self.order.iter().rev() {
+            let Some((j, p)) = self.parent[l] else {
+                continue;
+            };
+            let s = self.axes[j];
+            let mut ia = inertia[l];
+            let mut pa = bias[l];
+            if self.dofs[j].is_some() {
+                u[j] = inertia[l] * s;
+                d[j] = s.dot(&u[j]);
+                residual[j] = joint_efforts[j] - s.dot(&bias[l]);
+                ia -= u[j] * u[j].transpose() / d[j];
+                pa += u[j] * residual[j] / d[j];
+            }
+            pa += ia * c[l];
+            inertia[p] += ia;
+            bias[p] += pa;
+        }
+
+        let mut qdd = vec![0.0; self.axes.len()];
+        let mut a = vec![spatial(glm::Vec3::zeros(), -gravity); n];
+        for &l in &self.order {
+            let Some((j, p)) = self.parent[l] else {
+                continue;
+            };
+            a[l] = a[p] + c[l];
+            if self.dofs[j].is_some() {
+                qdd[j] = (residual[j] - u[j].dot(&a[l])) / d[j];
+                a[l] += self.axes[j] * qdd[j];
+            }
+        }
+        qdd
+    }
+}
+
+impl RobotDescriptor {
+    /// Joint efforts (N m or N) needed to reach `accelerations` from the
+    /// positions and velocities in `state` under `gravity`, e.g.
+    /// `glm::vec3(0.0, 0.0, -9.81)`. Accelerations and the returned efforts
+    /// are in state order. The root link is fixed in the world.
+    pub fn inverse_dynamics(
+        &self,
+        state: &JointState,
+        accelerations: &[f32],
+        gravity: glm::Vec3,
+    ) -> Result<Vec<f32>, JointError> {
+        if accelerations.len() != state.dof() {
+            return Err(JointError::WrongLength {
+                expected: state.dof(),
+                got: accelerations.len(),
+            });
+        }
+        Ok(Articulation::new(self, state).rnea(&state.velocities, accelerations, gravity))
+    }
+    /// Joint space inertia matrix at the positions in `state`, in state order.
+    pub fn mass_matrix(&self, state: &JointState) -> DMatrix<f32> {
+        Articulation::new(self, state).crba(state.dof())
+    }
+    /// Coriolis, centrifugal and gravity efforts at the positions and
+    /// velocities in `state`, so that `M qdd + bias = efforts`.
+    pub fn bias_forces(&self, state: &JointState, gravity: glm::Vec3) -> Vec<f32> {
+        let zeros = vec![0.0; state.dof()];
+        Articulation::new(self, state).rnea(&state.velocities, &zeros, gravity)
+    }
+    /// Efforts from the `<dynamics>` damping and Coulomb friction of every
+    /// joint at the velocities in `state`, in state order.
+    pub fn passive_forces(&self, state: &JointState) -> Vec<f32> {
+        let articulation = Articulation::new(self, state);
+        let mut tau = vec![0.0; state.dof()];
+        for (j, joint) in self.joints.iter().enumerate() {
+            if let (Some((i, scale)), Some(dynamics)) = (articulation.dofs[j], joint.dynamics()) {
+                tau[i] +=
+                    scale * passive_force(dynamics, articulation.joint_value(j, &state.velocities));
+            }
+        }
+        tau
+    }
+    /// Joint accelerations, in state order, produced by the efforts in
+    /// `state` together with gravity, joint damping and friction.
+    pub fn forward_dynamics(&self, state: &JointState, gravity: glm::Vec3) -> Vec<f32> {
+        let articulation = Articulation::new(self, state);
+        if self.joints.iter().any(|j| j.mimic().is_some()) {
+            // coupled dofs: solve M qdd = efforts + passive - bias directly
+            let mass = articulation.crba(state.dof());
+            let zeros = vec![0.0; state.dof()];
+            let bias = articulation.rnea(&state.velocities, &zeros, gravity);
+            let rhs = DVector::from_iterator(
+                state.dof(),
+                state
+                    .efforts
+                    .iter()
+                    .zip(self.passive_forces(state))
+                    .zip(bias)
+                    .map(|((e, p), b)| e + p - b),
+            );
+            return match mass.clone().cholesky() {
+                Some(cholesky) => cholesky.solve(&rhs),
+                None => mass
+                    .lu()
+                    .solve(&rhs)
+                    .unwrap_or_else(|| DVector::zeros(state.dof())),
+            }
+            .iter()
+            .copied()
+            .collect();
+        }
+        let efforts: Vec<f32> = self
+            .joints
+            .iter()
+            .enumerate()
+            .map(|(j, joint)| match articulation.dofs[j] {
+                Some((i, _)) => {
+                    state.efforts[i]
+                        + joint
+                            .dynamics()
+                            .map_or(0.0, |d| passive_force(d, state.velocities[i]))
+                }
+                None => 0.0,
+            })
+            .collect();
+        let qdd = articulation.aba(&state.velocities, &efforts, gravity);
+        let mut accelerations = vec![0.0; state.dof()];
+        for (j, a) in qdd.into_iter().enumerate() {
+            if let Some((i, _)) = articulation.dofs[j] {
+                accelerations[i] = a;
+            }
+        }
+        accelerations
+    }
+    /// Advances `state` by `dt` seconds under its efforts with semi-implicit
+    /// Euler integration. Joint limits are not enforced.
+    pub fn step_dynamics(&self, state: &mut JointState, gravity: glm::Vec3, dt: f32) {
+        let accelerations = self.forward_dynamics(state, gravity);
+        for ((q, qd), qdd) in state
+            .positions
+            .iter_mut()
+            .zip(state.velocities.iter_mut())
+            .zip(accelerations)
+        {
+            *qd += qdd * dt;
+            *q += *qd * dt;
+        }
+    }
+    /// Joint efforts holding the robot still at the positions in `state`.
+    pub fn gravity_compensation(&self, state: &JointState, gravity: glm::Vec3) -> Vec<f32> {
+        let zeros = vec![0.0; state.dof()];
+        Articulation::new(self, state).rnea(&zeros, &zeros, gravity)
+    }
+}
@@ -122,6 +122,17 @@ impl Transform {
         let t = glm::translate(&self.tmatrix, &xyz);
         self.tmatrix = t;
     }
+    pub fn translation(&self) -> glm::Vec3 {
+        self.tmatrix.column(3).xyz()
+    }
+    pub fn rotation(&self) -> glm::Mat3 {
+        glm::mat4_to_mat3(&self.tmatrix)
+    }
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            tmatrix: glm::inverse(&self.tmatrix),
+        }
+    }
 }
 
 impl fmt::Display for Transform {
@@ -185,6 +196,9 @@ pub trait PlaneMesh: Default {
 pub trait SphereMesh: Default {
     fn create_sphere(r: f32, n_slices: usize, n_stacks: usize) -> Self;
 }
+pub trait CapsuleMesh: Default {
+    fn create_capsule(r: f32, h: f32, n_slices: usize) -> Self;
+}
 impl BoxMesh for TriMesh {
     fn create_box(sz: glm::Vec3) -> Self {
         let [side1, side2, side3]: [glm::Vec3; 3];
@@ -290,6 +304,50 @@ impl SphereMesh for TriMesh {
         mesh
     }
 }
+impl CapsuleMesh for TriMesh {
+    // cylinder of height h along z, centred, with a hemisphere on each end
+    fn create_capsule(r: f32, h: f32, n_slices: usize) -> Self {
+        use std::f32::consts::FRAC_PI_2;
+        use std::f32::consts::PI;
+        // rings per hemisphere
+        let n_stacks = (n_slices / 4).max(1);
+        // (height, radius) of every ring from the top pole down, leaving out
+        // the poles themselves
+        let mut rings = Vec::with_capacity(2 * n_stacks);
+        for i in 1..=n_stacks {
+            let phi = FRAC_PI_2 * (i as f32) / (n_stacks as f32);
+            rings.push((0.5 * h + r * phi.cos(), r * phi.sin()));
+        }
+        for i in 0..n_stacks {
+            let phi = FRAC_PI_2 * (i as f32) / (n_stacks as f32);
+            rings.push((-0.5 * h - r * phi.sin(), r * phi.cos()));
+        }
+        let point = |ring: usize, j: usize| {
+            let theta = 2.0 * PI * (j as f32) / (n_slices as f32);
+            let (z, radius) = rings[ring];
+            glm::vec3(radius * theta.cos(), radius * theta.sin(), z)
+        };
+        let top = glm::vec3(0.0, 0.0, 0.5 * h + r);
+        let bottom = glm::vec3(0.0, 0.0, -0.5 * h - r);
+        let last = rings.len() - 1;
+
+        let mut mesh = TriMesh::default();
+        for j in 0..n_slices {
+            let k = (j + 1) % n_slices;
+            mesh.add_triangle([top, point(0, j), point(0, k)]);
+            for ring in 0..last {
+                mesh.add_rectangle([
+                    point(ring, j),
+                    point(ring + 1, j),
+                    point(ring + 1, k),
+                    point(ring, k),
+                ]);
+            }
+            mesh.add_triangle([point(last, j), bottom, point(last, k)]);
+        }
+        mesh
+    }
+}
 impl PlaneMesh for TriMesh {
     fn create_plane() -> Self {
         static SIZE: f32 = 100.0;
@@ -446,6 +504,8 @@ fn parse_obj(fname: String) -> TriMesh {
     let mut vertices: Vec<glm::Vec3> = Vec::new();
     let mut normals: Vec<glm::Vec3> = Vec::new();
     let mut faces: Vec<Triangle> = Vec::new();
+    // face vertices as v, v/vt, v//vn or v/vt/vn
+    let re = regex::Regex::new(r"^(\d+)(?:/\d*(?:/(\d+))?)?").unwrap();
 
     for line in reader.lines() {
         if let Ok(line) = line {
@@ -468,15 +528,15 @@ fn parse_obj(fname: String) -> TriMesh {
                         let mut face = Triangle {
                             vertices: [Vertex::default(); 3],
                         };
-                        let re =
-                            regex::Regex::new(r"v?((?:[0-9])*)\/\/(?:vn)?((?:[0-9])*)").unwrap();
-
                         for (k, token) in tokens.enumerate().take(3) {
                             if let Some(captures) = re.captures(token) {
                                 let vidx = captures[1].parse::<usize>().unwrap_or_default() - 1;
-                                let nidx = captures[2].parse::<usize>().unwrap_or_default() - 1;
                                 face.vertices[k].position = vertices.get(vidx).unwrap().clone();
-                                face.vertices[k].normal = normals.get(nidx).unwrap().clone();
+                                if let Some(n) = captures.get(2) {
+                                    let nidx = n.as_str().parse::<usize>().unwrap_or_default() - 1;
+                                    face.vertices[k].normal =
+                                        normals.get(nidx).unwrap().normalize();
+                                }
                             }
                         }
                         faces.push(face);
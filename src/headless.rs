@@ -0,0 +1,96 @@
+use crate::constraint::JointHandle;
+use crate::physics::{BodyHandle, PhysicsProgram, PhysicsWorld};
+use nalgebra::UnitQuaternion;
+
+/// Pose and velocity of one body.
+#[derive(Debug, Copy, Clone)]
+pub struct BodyState {
+    pub handle: BodyHandle,
+    pub posn: glm::Vec3,
+    pub orientation: UnitQuaternion<f32>,
+    pub vel: glm::Vec3,
+    pub omega: glm::Vec3,
+}
+
+/// Position, velocity and motor effort of one joint; position and velocity
+/// are `None` for joints without a single degree of freedom.
+#[derive(Debug, Copy, Clone)]
+pub struct JointSample {
+    pub handle: JointHandle,
+    pub position: Option<f32>,
+    pub velocity: Option<f32>,
+    pub effort: f32,
+}
+
+/// Every body and joint of a world at one instant.
+#[derive(Default, Debug, Clone)]
+pub struct WorldState {
+    /// Seconds since the start of the run.
+    pub time: f32,
+    pub bodies: Vec<BodyState>,
+    pub joints: Vec<JointSample>,
+}
+
+impl WorldState {
+    pub fn body(&self, handle: BodyHandle) -> Option<&BodyState> {
+        self.bodies.iter().find(|b| b.handle == handle)
+    }
+    pub fn joint(&self, handle: JointHandle) -> Option<&JointSample> {
+        self.joints
+            .binary_search_by_key(&handle, |j| j.handle)
+            .ok()
+            .map(|i| &self.joints[i])
+    }
+}
+
+impl PhysicsWorld {
+    /// Current state of the world, stamped with `time`.
+    pub fn state(&self, time: f32) -> WorldState {
+        let bodies = self
+            .bodies()
+            .map(|(handle, body)| BodyState {
+                handle,
+                posn: body.dynamics.posn,
+                orientation: body.dynamics.orientation,
+                vel: body.dynamics.vel,
+                omega: body.dynamics.omega,
+            })
+            .collect();
+        let joints = self
+            .joints()
+            .map(|joint| JointSample {
+                handle: joint.handle(),
+                position: joint.position(self),
+                velocity: joint.velocity(self),
+                effort: joint.applied_effort(),
+            })
+            .collect();
+        WorldState {
+            time,
+            bodies,
+            joints,
+        }
+    }
+    /// Takes `steps` fixed steps of `dt` seconds, which becomes the world's
+    /// step, without any window or GPU. Returns the state before the first
+    /// step and after each one.
+    pub fn run_headless(&mut self, steps: usize, dt: f32) -> Vec<WorldState> {
+        self.run_headless_with(steps, dt, |_, _| {})
+    }
+    /// Like `run_headless`, calling `control` with the time before every
+    /// step, e.g. to write joint targets.
+    pub fn run_headless_with<F>(&mut self, steps: usize, dt: f32, mut control: F) -> Vec<WorldState>
+    where
+        F: FnMut(&mut PhysicsWorld, f32),
+    {
+        self.dt = dt;
+        let mut states = Vec::with_capacity(steps + 1);
+        states.push(self.state(0.0));
+        for step in 0..steps {
+            control(self, step as f32 * dt);
+            self.step();
+            states.push(self.state((step + 1) as f32 * dt));
+        }
+        states
+    }
+}
@@ -0,0 +1,217 @@
+use crate::geometry::Transform;
+use crate::joint_state::JointState;
+use crate::urdf::{JointType, RobotDescriptor};
+use nalgebra::{DMatrix, DVector, Rotation3, UnitQuaternion};
+use std::fmt;
+
+/// Error raised before the solver gets to iterate.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IkError {
+    /// No link with this name exists on the robot.
+    UnknownLink(String),
+}
+
+impl fmt::Display for IkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IkError::UnknownLink(l) => write!(f, "no known link with name \"{}\"", l),
+        }
+    }
+}
+
+impl std::error::Error for IkError {}
+
+/// Which part of the target pose the solver tries to reach.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum IkMode {
+    /// Position and orientation of the tip.
+    #[default]
+    Pose,
+    /// Position of the tip only, its orientation is free.
+    Position,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum IkStatus {
+    /// The residual error is within tolerance.
+    Converged,
+    /// The iteration budget ran out first.
+    MaxIterations,
+    /// The damped system could not be inverted; only possible without damping.
+    Singular,
+}
+
+/// Outcome of a solve. `state` holds the last iterate even when the solver
+/// did not converge.
+#[derive(Debug, Clone)]
+pub struct IkSolution {
+    pub state: JointState,
+    pub status: IkStatus,
+    pub iterations: usize,
+    /// Distance from the tip to the target position.
+    pub position_error: f32,
+    /// Angle in radians between the tip and the target orientation, zero in
+    /// position-only mode.
+    pub orientation_error: f32,
+}
+
+impl IkSolution {
+    pub fn converged(&self) -> bool {
+        self.status == IkStatus::Converged
+    }
+}
+
+/// Damped least-squares (Levenberg-Marquardt) inverse kinematics solver.
+/// Every step is clamped to the position limits of the joints.
+#[derive(Debug, Copy, Clone)]
+pub struct IkSolver {
+    pub mode: IkMode,
+    /// Damping factor; larger values are slower but stable near singularities.
+    pub damping: f32,
+    /// Tolerance on the position error (m) and the orientation error (rad).
+    pub tolerance: f32,
+    pub max_iterations: usize,
+    /// Largest change of any joint in a single iteration.
+    pub max_step: f32,
+}
+
+impl Default for IkSolver {
+    fn default() -> Self {
+        Self {
+            mode: IkMode::Pose,
+            damping: 0.05,
+            tolerance: 1e-3,
+            max_iterations: 100,
+            max_step: 0.2,
+        }
+    }
+}
+
+impl IkSolver {
+    pub fn new(mode: IkMode) -> Self {
+        Self {
+            mode,
+            ..Default::default()
+        }
+    }
+    pub fn with_damping(mut self, damping: f32) -> Self {
+        self.damping = damping;
+        self
+    }
+    pub fn with_tolerance(mut self, tolerance: f32) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Moves `tip_link` to `target`, given in the frame of `base_link`,
+    /// starting from the joint positions in `initial`.
+    pub fn solve(
+        &self,
+        robot: &RobotDescriptor,
+        tip_link: &str,
+        base_link: &str,
+        target: &Transform,
+        initial: &JointState,
+    ) -> Result<IkSolution, IkError> {
+        for link in [tip_link, base_link] {
+            if robot.link_index(link).is_none() {
+                return Err(IkError::UnknownLink(link.to_owned()));
+            }
+        }
+        let rows = match self.mode {
+            IkMode::Pose => 6,
+            IkMode::Position => 3,
+        };
+        let mut state = initial.clone();
+        clamp_to_limits(robot, &mut state);
+        let mut iterations = 0;
+        loop {
+            let pose = robot.relative_pose(&state, base_link, tip_link).unwrap();
+            let (error, position_error, orientation_error) = self.residual(&pose, target);
+            let mut solution = IkSolution {
+                state: state.clone(),
+                status: IkStatus::Converged,
+                iterations,
+                position_error,
+                orientation_error,
+            };
+            if position_error <= self.tolerance && orientation_error <= self.tolerance {
+                return Ok(solution);
+            }
+            if iterations == self.max_iterations {
+                solution.status = IkStatus::MaxIterations;
+                return Ok(solution);
+            }
+
+            // dq = J^T (J J^T + damping^2 I)^-1 e
+            let jac = robot.jacobian(&state, tip_link, base_link).unwrap();
+            let jac = jac.rows(0, rows).into_owned();
+            let damped = &jac * jac.transpose()
+                + DMatrix::identity(rows, rows) * self.damping * self.damping;
+            let Some(inverse) = damped.try_inverse() else {
+                solution.status = IkStatus::Singular;
+                return Ok(solution);
+            };
+            let error = DVector::from_iterator(rows, error.into_iter().take(rows));
+            let mut dq = jac.transpose() * inverse * error;
+            let largest = dq.amax();
+            if largest > self.max_step {
+                dq *= self.max_step / largest;
+            }
+            state
+                .positions
+                .iter_mut()
+                .zip(dq.iter())
+                .for_each(|(q, dq)| *q += dq);
+            clamp_to_limits(robot, &mut state);
+            iterations += 1;
+        }
+    }
+
+    // error twist (linear then angular) taking `pose` to `target`, with the
+    // position and orientation error norms
+    fn residual(&self, pose: &Transform, target: &Transform) -> ([f32; 6], f32, f32) {
+        let dp = target.translation() - pose.translation();
+        let dr = match self.mode {
+            IkMode::Pose => {
+                let r = target.rotation() * pose.rotation().transpose();
+                UnitQuaternion::from_rotation_matrix(&Rotation3::from_matrix_unchecked(r))
+                    .scaled_axis()
+            }
+            IkMode::Position => glm::Vec3::zeros(),
+        };
+        ([dp.x, dp.y, dp.z, dr.x, dr.y, dr.z], dp.norm(), dr.norm())
+    }
+}
+
+// keep bounded joints within [lower, upper], as under `LimitPolicy::Clamp`
+fn clamp_to_limits(robot: &RobotDescriptor, state: &mut JointState) {
+    for j in robot.actuated_joints() {
+        let Some(limits) = j.limits() else {
+            continue;
+        };
+        let bounded = matches!(j.joint_type(), JointType::Revolute | JointType::Prismatic)
+            && limits.lower < limits.upper;
+        if let (true, Some(i)) = (bounded, state.index(j.name())) {
+            state.positions[i] = state.positions[i].clamp(limits.lower, limits.upper);
+        }
+    }
+}
+
+impl RobotDescriptor {
+    /// Solves for `tip_link` reaching `target` (in the frame of `base_link`)
+    /// from the current joint positions with a default `IkSolver`.
+    pub fn inverse_kinematics(
+        &self,
+        tip_link: &str,
+        base_link: &str,
+        target: &Transform,
+        mode: IkMode,
+    ) -> Result<IkSolution, IkError> {
+        IkSolver::new(mode).solve(self, tip_link, base_link, target, &self.joint_state())
+    }
+}
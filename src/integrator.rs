@@ -0,0 +1,161 @@
+use crate::physics::FreeBody;
+use nalgebra::{Quaternion, UnitQuaternion};
+
+/// Numerical scheme used to advance bodies over a step. Forces and torques
+/// are held constant over the step; the gyroscopic torque is re-evaluated
+/// at every stage.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Integrator {
+    /// Positions and velocities both from the state at the start of the
+    /// step. Gains energy; mostly useful for comparison.
+    ExplicitEuler,
+    /// Velocities first, then positions from the new velocities.
+    #[default]
+    SemiImplicitEuler,
+    /// Second order, with the acceleration averaged over the step.
+    VelocityVerlet,
+    /// Classic fourth order Runge-Kutta.
+    Rk4,
+}
+
+// the part of a FreeBody that changes during integration
+#[derive(Debug, Copy, Clone)]
+struct State {
+    posn: glm::Vec3,
+    vel: glm::Vec3,
+    orientation: Quaternion<f32>,
+    omega: glm::Vec3,
+}
+
+impl State {
+    fn of(body: &FreeBody) -> Self {
+        Self {
+            posn: body.posn,
+            vel: body.vel,
+            orientation: body.orientation.into_inner(),
+            omega: body.omega,
+        }
+    }
+    // this state plus `derivative` times `dt`
+    fn advanced(&self, derivative: &State, dt: f32) -> Self {
+        Self {
+            posn: self.posn + derivative.posn * dt,
+            vel: self.vel + derivative.vel * dt,
+            orientation: self.orientation + derivative.orientation * dt,
+            omega: self.omega + derivative.omega * dt,
+        }
+    }
+}
+
+struct Dynamics<'a> {
+    body: &'a FreeBody,
+    force: glm::Vec3,
+    torque: glm::Vec3,
+}
+
+impl Dynamics<'_> {
+    fn linear_acceleration(&self) -> glm::Vec3 {
+        self.force * self.body.inverse_mass()
+    }
+    // Euler's equations with the inertia rotated to `orientation`
+    fn angular_acceleration(&self, orientation: &Quaternion<f32>, omega: &glm::Vec3) -> glm::Vec3 {
+        if self.body.inverse_mass() == 0.0 {
+            return glm::Vec3::zeros();
+        }
+        let r = UnitQuaternion::from_quaternion(*orientation)
+            .to_rotation_matrix()
+            .into_inner();
+        let inertia = r * self.body.inertia * r.transpose();
+        let Some(inverse) = inertia.try_inverse() else {
+            return glm::Vec3::zeros();
+        };
+        inverse * (self.torque - omega.cross(&(inertia * omega)))
+    }
+    fn derivative(&self, s: &State) -> State {
+        let spin = Quaternion::from_imag(s.omega) * s.orientation * 0.5;
+        State {
+            posn: s.vel,
+            vel: self.linear_acceleration(),
+            orientation: spin,
+            omega: self.angular_acceleration(&s.orientation, &s.omega),
+        }
+    }
+}
+
+impl Integrator {
+    /// Fraction of the velocity change over a step that already moves the
+    /// body within that step, assuming constant forces.
+    pub(crate) fn position_weight(&self) -> f32 {
+        match self {
+            Integrator::ExplicitEuler => 0.0,
+            Integrator::SemiImplicitEuler => 1.0,
+            Integrator::VelocityVerlet | Integrator::Rk4 => 0.5,
+        }
+    }
+    /// Advances `body` by `dt` seconds under `force` and `torque`, both in
+    /// world space and applied at the centre of mass.
+    pub fn integrate(&self, body: &mut FreeBody, force: glm::Vec3, torque: glm::Vec3, dt: f32) {
+        let dynamics = Dynamics {
+            body,
+            force,
+            torque,
+        };
+        let s = State::of(body);
+        let next = match self {
+            Integrator::ExplicitEuler => s.advanced(&dynamics.derivative(&s), dt),
+            Integrator::SemiImplicitEuler => {
+                let d = dynamics.derivative(&s);
+                let vel = s.vel + d.vel * dt;
+                let omega = s.omega + d.omega * dt;
+                State {
+                    posn: s.posn + vel * dt,
+                    vel,
+                    orientation: (UnitQuaternion::from_scaled_axis(omega * dt)
+                        * UnitQuaternion::from_quaternion(s.orientation))
+                    .into_inner(),
+                    omega,
+                }
+            }
+            Integrator::VelocityVerlet => {
+                let d = dynamics.derivative(&s);
+                let rotation = s.omega * dt + 0.5 * d.omega * dt * dt;
+                let mut next = State {
+                    posn: s.posn + s.vel * dt + 0.5 * d.vel * dt * dt,
+                    vel: s.vel,
+                    orientation: (UnitQuaternion::from_scaled_axis(rotation)
+                        * UnitQuaternion::from_quaternion(s.orientation))
+                    .into_inner(),
+                    // predicted, for the acceleration at the end of the step
+                    omega: s.omega + d.omega * dt,
+                };
+                let end = dynamics.derivative(&next);
+                next.vel = s.vel + 0.5 * (d.vel + end.vel) * dt;
+                next.omega = s.omega + 0.5 * (d.omega + end.omega) * dt;
+                next
+            }
+            Integrator::Rk4 => {
+                let k1 = dynamics.derivative(&s);
+                let k2 = dynamics.derivative(&s.advanced(&k1, 0.5 * dt));
+                let k3 = dynamics.derivative(&s.advanced(&k2, 0.5 * dt));
+                let k4 = dynamics.derivative(&s.advanced(&k3, dt));
+                State {
+                    posn: s.posn + (k1.posn + 2.0 * k2.posn + 2.0 * k3.posn + k4.posn) * dt / 6.0,
+                    vel: s.vel + (k1.vel + 2.0 * k2.vel + 2.0 * k3.vel + k4.vel) * dt / 6.0,
+                    orientation: s.orientation
+                        + (k1.orientation
+                            + k2.orientation * 2.0
+                            + k3.orientation * 2.0
+                            + k4.orientation)
+                            * (dt / 6.0),
+                    omega: s.omega
+                        + (k1.omega + 2.0 * k2.omega + 2.0 * k3.omega + k4.omega) * dt / 6.0,
+                }
+            }
+        };
+        body.posn = next.posn;
+        body.vel = next.vel;
+        body.orientation = UnitQuaternion::from_quaternion(next.orientation);
+        body.omega = next.omega;
+        body.update_inertia();
+    }
+}
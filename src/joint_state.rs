@@ -0,0 +1,263 @@
+use crate::urdf::{Joint, JointType, RobotDescriptor};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Error raised when addressing or driving joints of a robot.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JointError {
+    /// No joint with this name exists on the robot.
+    UnknownJoint(String),
+    /// The joint exists but has no degree of freedom of its own
+    /// (fixed, floating or mimic joints).
+    NotActuated(String),
+    /// A positional command did not have one entry per joint.
+    WrongLength { expected: usize, got: usize },
+    /// A joint limit was exceeded under `LimitPolicy::Reject`.
+    LimitExceeded(LimitViolation),
+}
+
+impl fmt::Display for JointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JointError::UnknownJoint(j) => write!(f, "no known joint with name \"{}\"", j),
+            JointError::NotActuated(j) => write!(f, "joint \"{}\" is not actuated", j),
+            JointError::WrongLength { expected, got } => {
+                write!(f, "expected {} joint values, got {}", expected, got)
+            }
+            JointError::LimitExceeded(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+impl std::error::Error for JointError {}
+
+/// How joint limits are enforced when joints are moved.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LimitPolicy {
+    /// Move the joint anyway and report the violation. The default, so that
+    /// files with careless limits still move as commanded.
+    #[default]
+    Allow,
+    /// Saturate the command at the limit and report the violation.
+    Clamp,
+    /// Refuse the whole command, leaving every joint where it was.
+    Reject,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LimitKind {
+    Position,
+    Velocity,
+}
+
+/// A commanded joint value outside of `[lower, upper]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LimitViolation {
+    pub joint: String,
+    pub kind: LimitKind,
+    pub value: f32,
+    pub lower: f32,
+    pub upper: f32,
+}
+
+impl fmt::Display for LimitViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "joint \"{}\" {:?} {} outside of [{}, {}]",
+            self.joint, self.kind, self.value, self.lower, self.upper
+        )
+    }
+}
+
+/// Position, velocity and effort of every actuated joint of a robot, addressed
+/// by joint name. Joints are ordered as they appear in the URDF file.
+#[derive(Default, Debug, Clone)]
+pub struct JointState {
+    names: Vec<String>,
+    index: HashMap<String, usize>,
+    pub positions: Vec<f32>,
+    pub velocities: Vec<f32>,
+    pub efforts: Vec<f32>,
+}
+
+impl JointState {
+    /// Zero state for the actuated joints of `robot`.
+    pub fn new(robot: &RobotDescriptor) -> Self {
+        Self::from_names(robot.actuated_joints().map(|j| j.name().to_owned()))
+    }
+    pub fn from_names<I>(names: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        let names: Vec<String> = names.into_iter().map(Into::into).collect();
+        let index = names
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.clone(), i))
+            .collect();
+        let dof = names.len();
+        Self {
+            names,
+            index,
+            positions: vec![0.0; dof],
+            velocities: vec![0.0; dof],
+            efforts: vec![0.0; dof],
+        }
+    }
+    /// Number of degrees of freedom.
+    pub fn dof(&self) -> usize {
+        self.names.len()
+    }
+    /// Joint names in state order.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+    pub fn index(&self, joint_name: &str) -> Option<usize> {
+        self.index.get(joint_name).copied()
+    }
+    fn try_index(&self, joint_name: &str) -> Result<usize, JointError> {
+        self.index(joint_name)
+            .ok_or_else(|| JointError::UnknownJoint(joint_name.to_owned()))
+    }
+
+    pub fn position(&self, joint_name: &str) -> Option<f32> {
+        self.index(joint_name).map(|i| self.positions[i])
+    }
+    pub fn velocity(&self, joint_name: &str) -> Option<f32> {
+        self.index(joint_name).map(|i| self.velocities[i])
+    }
+    pub fn effort(&self, joint_name: &str) -> Option<f32> {
+        self.index(joint_name).map(|i| self.efforts[i])
+    }
+    pub fn set_position(&mut self, joint_name: &str, position: f32) -> Result<(), JointError> {
+        let i = self.try_index(joint_name)?;
+        self.positions[i] = position;
+        Ok(())
+    }
+    pub fn set_velocity(&mut self, joint_name: &str, velocity: f32) -> Result<(), JointError> {
+        let i = self.try_index(joint_name)?;
+        self.velocities[i] = velocity;
+        Ok(())
+    }
+    pub fn set_effort(&mut self, joint_name: &str, effort: f32) -> Result<(), JointError> {
+        let i = self.try_index(joint_name)?;
+        self.efforts[i] = effort;
+        Ok(())
+    }
+    /// Iterates over `(name, position)` pairs in state order.
+    pub fn iter_positions(&self) -> impl Iterator<Item = (&str, f32)> {
+        self.names
+            .iter()
+            .map(String::as_str)
+            .zip(self.positions.iter().copied())
+    }
+}
+
+impl RobotDescriptor {
+    /// Joints with a degree of freedom of their own, in file order.
+    pub fn actuated_joints(&self) -> impl Iterator<Item = &Joint> {
+        self.joints.iter().filter(|j| j.is_actuated())
+    }
+    pub fn dof(&self) -> usize {
+        self.actuated_joints().count()
+    }
+    /// Current positions of the actuated joints.
+    pub fn joint_state(&self) -> JointState {
+        let mut state = JointState::new(self);
+        for (j, position) in self.joints.iter().zip(self.joint_positions()) {
+            if let Some(i) = state.index(j.name()) {
+                state.positions[i] = *position;
+            }
+        }
+        state
+    }
+    /// Sets the named joints, leaving every other joint where it is.
+    pub fn set_joint_positions_by_name<'a, I>(
+        &mut self,
+        positions: I,
+    ) -> Result<Vec<LimitViolation>, JointError>
+    where
+        I: IntoIterator<Item = (&'a str, f32)>,
+    {
+        let mut theta = self.joint_positions().to_vec();
+        for (name, position) in positions {
+            let i = self
+                .joint_index(name)
+                .ok_or_else(|| JointError::UnknownJoint(name.to_owned()))?;
+            if !self.joints[i].is_actuated() {
+                return Err(JointError::NotActuated(name.to_owned()));
+            }
+            theta[i] = position;
+        }
+        self.set_joint_position(&theta, false)
+    }
+    pub fn set_joint_state(
+        &mut self,
+        state: &JointState,
+    ) -> Result<Vec<LimitViolation>, JointError> {
+        self.set_joint_positions_by_name(state.iter_positions())
+    }
+
+    /// Resolves the mimic joints of `target` (one position per joint) from
+    /// the joints they follow, then checks every joint against its limits,
+    /// clamping it in place under `LimitPolicy::Clamp`. A joint is resolved
+    /// and limited before any mimic joint following it, so mimic chains see
+    /// the limited position. Velocity limits are only checked when the move
+    /// takes `dt` seconds.
+    pub(crate) fn enforce_limits(
+        &self,
+        target: &mut [f32],
+        dt: Option<f32>,
+    ) -> Result<Vec<LimitViolation>, JointError> {
+        let mut violations = Vec::new();
+        for (i, source) in self.mimic_order() {
+            let j = &self.joints[i];
+            target[i] = self.mimic_position(i, source, target);
+            let (q, current) = (&mut target[i], self.joint_positions()[i]);
+            let Some(limits) = j.limits() else {
+                continue;
+            };
+            let mut found = Vec::new();
+            if let Some(dt) = dt.filter(|&dt| dt > 0.0 && limits.velocity > 0.0) {
+                let velocity = (*q - current) / dt;
+                if velocity.abs() > limits.velocity {
+                    found.push(LimitViolation {
+                        joint: j.name().to_owned(),
+                        kind: LimitKind::Velocity,
+                        value: velocity,
+                        lower: -limits.velocity,
+                        upper: limits.velocity,
+                    });
+                    if self.limit_policy == LimitPolicy::Clamp {
+                        *q = current + velocity.clamp(-limits.velocity, limits.velocity) * dt;
+                    }
+                }
+            }
+            // a file leaving both bounds at zero does not bound the joint
+            let bounded = matches!(j.joint_type(), JointType::Revolute | JointType::Prismatic)
+                && limits.lower < limits.upper;
+            if bounded && (*q < limits.lower || *q > limits.upper) {
+                found.push(LimitViolation {
+                    joint: j.name().to_owned(),
+                    kind: LimitKind::Position,
+                    value: *q,
+                    lower: limits.lower,
+                    upper: limits.upper,
+                });
+                if self.limit_policy == LimitPolicy::Clamp {
+                    *q = q.clamp(limits.lower, limits.upper);
+                }
+            }
+            if self.limit_policy == LimitPolicy::Reject {
+                if let Some(v) = found.into_iter().next() {
+                    return Err(JointError::LimitExceeded(v));
+                }
+                continue;
+            }
+            violations.extend(found);
+        }
+        Ok(violations)
+    }
+}
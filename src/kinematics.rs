@@ -0,0 +1,255 @@
+use crate::geometry::Transform;
+use crate::joint_state::JointState;
+use crate::urdf::{Joint, JointType, RobotDescriptor};
+use nalgebra::Matrix6xX;
+use rayon::prelude::*;
+use std::collections::VecDeque;
+
+/// Problems found when assembling links and joints into a tree.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TreeError {
+    /// The robot has no links at all.
+    NoLinks,
+    /// A link is the child of more than one joint; `joint` is the second one.
+    MultipleParents { link: usize, joint: usize },
+    /// More than one link has no parent joint.
+    MultipleRoots(Vec<usize>),
+    /// A link is attached to no joint at all.
+    Disconnected(usize),
+    /// The joints between these links form a loop.
+    Cycle(Vec<usize>),
+}
+
+/// Frames attached to a link.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LinkFrame {
+    Link,
+    Visual,
+    /// The first collision element of the link.
+    Collision,
+    Inertial,
+}
+
+/// Parent/child structure of a robot, built once after parsing.
+/// Links and joints are referred to by their index in the descriptor.
+#[derive(Default, Debug, Clone)]
+pub struct KinematicTree {
+    root: usize,
+    parent_joint: Vec<Option<usize>>,
+    child_joints: Vec<Vec<usize>>,
+    order: Vec<usize>,
+}
+
+impl KinematicTree {
+    pub fn new(n_links: usize, joints: &[Joint]) -> Result<Self, TreeError> {
+        if n_links == 0 {
+            return Err(TreeError::NoLinks);
+        }
+        let mut parent_joint = vec![None; n_links];
+        let mut child_joints = vec![Vec::new(); n_links];
+        for (i, j) in joints.iter().enumerate() {
+            if parent_joint[j.child()].is_some() {
+                return Err(TreeError::MultipleParents {
+                    link: j.child(),
+                    joint: i,
+                });
+            }
+            parent_joint[j.child()] = Some(i);
+            child_joints[j.parent()].push(i);
+        }
+
+        let roots: Vec<usize> = (0..n_links)
+            .filter(|&l| parent_joint[l].is_none())
+            .collect();
+        if let Some(&lonely) = roots
+            .iter()
+            .find(|&&l| n_links > 1 && child_joints[l].is_empty())
+        {
+            return Err(TreeError::Disconnected(lonely));
+        }
+        let root = match roots.as_slice() {
+            [root] => *root,
+            [] => return Err(TreeError::Cycle((0..n_links).collect())),
+            _ => return Err(TreeError::MultipleRoots(roots)),
+        };
+
+        // breadth first from the root; as every link has at most one parent,
+        // whatever is not reached hangs off a loop
+        let mut order = Vec::with_capacity(n_links);
+        let mut queue = VecDeque::from([root]);
+        while let Some(link) = queue.pop_front() {
+            order.push(link);
+            queue.extend(child_joints[link].iter().map(|&j| joints[j].child()));
+        }
+        if order.len() < n_links {
+            let mut reached = vec![false; n_links];
+            order.iter().for_each(|&l| reached[l] = true);
+            return Err(TreeError::Cycle(
+                (0..n_links).filter(|&l| !reached[l]).collect(),
+            ));
+        }
+
+        Ok(Self {
+            root,
+            parent_joint,
+            child_joints,
+            order,
+        })
+    }
+    /// Index of the root link.
+    pub fn root(&self) -> usize {
+        self.root
+    }
+    /// The joint connecting `link` to its parent, `None` for the root.
+    pub fn parent_joint(&self, link: usize) -> Option<usize> {
+        self.parent_joint[link]
+    }
+    /// The joints connecting `link` to its children.
+    pub fn child_joints(&self, link: usize) -> &[usize] {
+        &self.child_joints[link]
+    }
+    /// Links ordered so that every parent comes before its children.
+    pub fn order(&self) -> &[usize] {
+        &self.order
+    }
+}
+
+impl RobotDescriptor {
+    /// World transform of every link frame for the joint positions in `state`,
+    /// in link order. Joints missing from `state` are taken at zero and mimic
+    /// joints follow the joint they mimic. The descriptor itself is left
+    /// untouched, so many states can be evaluated at once.
+    pub fn forward_kinematics(&self, state: &JointState) -> Vec<Transform> {
+        let theta: Vec<f32> = self
+            .joints
+            .iter()
+            .map(|j| state.position(j.name()).unwrap_or(0.0))
+            .collect();
+        self.link_frames(&self.apply_mimic(&theta))
+    }
+    /// `forward_kinematics` over many states in parallel.
+    pub fn par_forward_kinematics(&self, states: &[JointState]) -> Vec<Vec<Transform>> {
+        states
+            .par_iter()
+            .map(|s| self.forward_kinematics(s))
+            .collect()
+    }
+    /// World pose of the named link frame.
+    pub fn link_pose(&self, state: &JointState, link_name: &str) -> Option<Transform> {
+        let l = self.link_index(link_name)?;
+        Some(self.forward_kinematics(state)[l])
+    }
+    /// World pose of the visual, collision or inertial frame of a link.
+    pub fn frame_pose(
+        &self,
+        state: &JointState,
+        link_name: &str,
+        frame: LinkFrame,
+    ) -> Option<Transform> {
+        let l = self.link_index(link_name)?;
+        let link = &self.links[l];
+        let offset = match frame {
+            LinkFrame::Link => Transform::default(),
+            LinkFrame::Visual => link.visual.origin.into(),
+            LinkFrame::Collision => link
+                .collisions
+                .first()
+                .map(|c| c.origin.into())
+                .unwrap_or_default(),
+            LinkFrame::Inertial => link.inertial.origin.into(),
+        };
+        Some(self.forward_kinematics(state)[l] * offset)
+    }
+    /// Pose of link `to` expressed in the frame of link `from`.
+    pub fn relative_pose(&self, state: &JointState, from: &str, to: &str) -> Option<Transform> {
+        let (f, t) = (self.link_index(from)?, self.link_index(to)?);
+        let frames = self.forward_kinematics(state);
+        Some(frames[f].inverse() * frames[t])
+    }
+    // world transform of every link with the joints at `theta`, one entry per joint
+    pub(crate) fn link_frames(&self, theta: &[f32]) -> Vec<Transform> {
+        let mut frames = vec![Transform::default(); self.links.len()];
+        for &l in self.tree().order() {
+            if let Some(j) = self.tree().parent_joint(l) {
+                let joint = &self.joints[j];
+                frames[l] = frames[joint.parent()] * joint.transform_at(theta[j]);
+            }
+        }
+        frames
+    }
+}
+
+impl RobotDescriptor {
+    /// Geometric Jacobian of `tip_link` relative to `base_link` at `state`,
+    /// expressed in the base frame with the tip frame origin as reference
+    /// point. Rows are linear then angular velocity; there is one column per
+    /// joint of `state`, zero for joints off the chain. Mimic joints add to
+    /// the column of the joint they follow.
+    pub fn jacobian(
+        &self,
+        state: &JointState,
+        tip_link: &str,
+        base_link: &str,
+    ) -> Option<Matrix6xX<f32>> {
+        let (tip, base) = (self.link_index(tip_link)?, self.link_index(base_link)?);
+        let frames = self.forward_kinematics(state);
+        let tip_path = self.path_to_root(tip);
+        let base_path = self.path_to_root(base);
+        let p = frames[tip].translation();
+        let mut jac = Matrix6xX::zeros(state.dof());
+        // joints shared by both paths move tip and base together
+        let chain = tip_path
+            .iter()
+            .filter(|j| !base_path.contains(j))
+            .map(|&j| (j, 1.0))
+            .chain(
+                base_path
+                    .iter()
+                    .filter(|j| !tip_path.contains(j))
+                    .map(|&j| (j, -1.0)),
+            );
+        for (j, sign) in chain {
+            let joint = &self.joints[j];
+            let (column, scale) = match joint.mimic() {
+                Some(m) => (state.index(&m.joint), m.multiplier),
+                None => (state.index(joint.name()), 1.0),
+            };
+            let Some(column) = column else {
+                continue;
+            };
+            let frame = frames[joint.child()];
+            let z = frame.rotation() * joint.axis();
+            let (linear, angular) = match joint.joint_type() {
+                JointType::Revolute | JointType::Continuous => {
+                    (z.cross(&(p - frame.translation())), z)
+                }
+                JointType::Prismatic => (z, glm::Vec3::zeros()),
+                JointType::Fixed | JointType::Floating => continue,
+            };
+            let s = sign * scale;
+            let mut col = jac.column_mut(column);
+            for k in 0..3 {
+                col[k] += s * linear[k];
+                col[k + 3] += s * angular[k];
+            }
+        }
+        let r = frames[base].rotation().transpose();
+        for mut col in jac.column_iter_mut() {
+            let linear = r * glm::vec3(col[0], col[1], col[2]);
+            let angular = r * glm::vec3(col[3], col[4], col[5]);
+            col.copy_from_slice(&[
+                linear.x, linear.y, linear.z, angular.x, angular.y, angular.z,
+            ]);
+        }
+        Some(jac)
+    }
+    // joints from `link` up to the root
+    fn path_to_root(&self, mut link: usize) -> Vec<usize> {
+        let mut path = Vec::new();
+        while let Some(j) = self.tree().parent_joint(link) {
+            path.push(j);
+            link = self.joints[j].parent();
+        }
+        path
+    }
+}
@@ -1,15 +1,29 @@
 #![allow(dead_code)]
 
+pub mod articulated;
 pub mod bindings;
+pub mod broad_phase;
 pub mod camera;
+pub mod constraint;
+pub mod dynamics;
 pub mod geometry;
 pub mod graphics;
+pub mod headless;
+pub mod joint_state;
+pub mod ik;
+pub mod integrator;
+pub mod kinematics;
 pub mod light;
+pub mod narrow_phase;
 pub mod urdf;
 pub mod physics;
+pub mod raycast;
+pub mod resource;
 pub mod shader;
+pub mod solver;
 pub mod texture;
 pub mod util;
 pub mod wgpu_program;
 pub mod robot;
+pub mod sensor;
 extern crate nalgebra_glm as glm;
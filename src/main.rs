@@ -1,7 +1,61 @@
+use std::str::FromStr;
+use wgpu_robotic_simulator::articulated::ArticulatedBody;
+use wgpu_robotic_simulator::geometry::Transform;
 use wgpu_robotic_simulator::graphics::GraphicsProgram;
+use wgpu_robotic_simulator::physics::*;
+use wgpu_robotic_simulator::urdf::RobotDescriptor;
 use wgpu_robotic_simulator::wgpu_program::WGPUGraphics;
+extern crate nalgebra_glm as glm;
+
+// drops LittleDog holding a crouch and prints where it ends up, without
+// opening a window
+fn headless(steps: usize) {
+    let robot = RobotDescriptor::from_str(include_str!("../assets/LittleDog.urdf"))
+        .expect("unable to read urdf");
+    let mut world = PhysicsWorld::new();
+    world.substeps = 4;
+    world.add_body(Body::fixed(Shape::Plane, glm::Vec3::zeros()));
+    let start = Transform::new(glm::vec3(0.0, 0.0, 0.3), glm::Vec3::zeros());
+    let dog = ArticulatedBody::spawn(&mut world, &robot, start);
+    for name in robot.actuated_joints().map(|j| j.name()) {
+        let side = if name.starts_with("front") { 1.0 } else { -1.0 };
+        let target = match name {
+            n if n.ends_with("hip_pitch") => 0.6 * side,
+            n if n.ends_with("knee") => -1.2 * side,
+            _ => 0.0,
+        };
+        dog.set_gains(&mut world, name, 5.0, 0.1).unwrap();
+        dog.set_position_target(&mut world, name, target).unwrap();
+    }
+    let states = world.run_headless(steps, 1.0 / 60.0);
+    let base = dog.link_body(robot.tree().root());
+    // every second, and the end
+    let shown = states
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| i % 60 == 0 || i + 1 == states.len());
+    for (_, state) in shown {
+        let height = state.body(base).map_or(0.0, |b| b.posn.z);
+        println!("t = {:.2} s: base at {:.3} m", state.time, height);
+    }
+    if let Some(last) = states.last() {
+        let joints = dog.recorded_joint_state(last);
+        for (i, name) in joints.names().iter().enumerate() {
+            println!(
+                "{name}: position {:.3} velocity {:.3} effort {:.3}",
+                joints.positions[i], joints.velocities[i], joints.efforts[i]
+            );
+        }
+    }
+}
 
 fn main() {
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() == Some("--headless") {
+        let steps = args.next().and_then(|s| s.parse().ok()).unwrap_or(300);
+        headless(steps);
+        return;
+    }
     let event_loop = winit::event_loop::EventLoop::new().unwrap();
     let window = winit::window::Window::new(&event_loop).unwrap();
     let program = WGPUGraphics::new(200, 200, &window);
@@ -0,0 +1,882 @@
+use crate::geometry::Transform;
+use crate::physics::{BodyHandle, Shape};
+
+/// Most points kept in a manifold.
+pub const MAX_MANIFOLD_POINTS: usize = 4;
+/// Distance within which a contact point is taken to be the same as one
+/// from the previous step.
+pub const PERSISTENCE_THRESHOLD: f32 = 0.02;
+const GJK_ITERATIONS: usize = 64;
+const EPA_ITERATIONS: usize = 64;
+const TOLERANCE: f32 = 1e-5;
+
+/// One point of contact between two shapes. `point_a` and `point_b` are the
+/// deepest points of each shape inside the other, in world space.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ContactPoint {
+    pub point_a: glm::Vec3,
+    pub point_b: glm::Vec3,
+    /// Penetration along the manifold normal, positive when overlapping.
+    pub depth: f32,
+}
+
+impl ContactPoint {
+    fn new(point_a: glm::Vec3, point_b: glm::Vec3, normal: &glm::Vec3) -> Self {
+        Self {
+            point_a,
+            point_b,
+            depth: (point_a - point_b).dot(normal),
+        }
+    }
+    /// Midpoint between the two witness points.
+    pub fn point(&self) -> glm::Vec3 {
+        (self.point_a + self.point_b) * 0.5
+    }
+}
+
+/// Contact points between two shapes sharing a normal pointing from the
+/// first shape to the second.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifold {
+    pub normal: glm::Vec3,
+    pub points: Vec<ContactPoint>,
+}
+
+impl Manifold {
+    fn flipped(mut self) -> Self {
+        self.normal = -self.normal;
+        for p in &mut self.points {
+            std::mem::swap(&mut p.point_a, &mut p.point_b);
+        }
+        self
+    }
+    fn reduced(mut self) -> Self {
+        self.points = reduce(self.points);
+        self
+    }
+}
+
+/// A contact point that survives between steps, carrying the impulses the
+/// solver found for it last time.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PersistentPoint {
+    pub contact: ContactPoint,
+    /// Witness points in the local frames of body `a` and `b`.
+    pub local_a: glm::Vec3,
+    pub local_b: glm::Vec3,
+    pub normal_impulse: f32,
+    pub tangent_impulse: [f32; 2],
+}
+
+/// Contact between two bodies, kept from one step to the next.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContactManifold {
+    pub a: BodyHandle,
+    pub b: BodyHandle,
+    /// Parts of `Shape::Compound` bodies touching, 0 for other shapes.
+    pub parts: (usize, usize),
+    pub normal: glm::Vec3,
+    pub points: Vec<PersistentPoint>,
+}
+
+impl ContactManifold {
+    pub fn new(a: BodyHandle, b: BodyHandle) -> Self {
+        Self {
+            a,
+            b,
+            parts: (0, 0),
+            normal: glm::Vec3::zeros(),
+            points: Vec::new(),
+        }
+    }
+    pub fn max_depth(&self) -> f32 {
+        self.points
+            .iter()
+            .map(|p| p.contact.depth)
+            .fold(0.0, f32::max)
+    }
+    /// Merges freshly detected contacts with the ones from the last step.
+    /// Old points that still hold, moved with their bodies, are kept when
+    /// only a single new point was found; a point close to an old one
+    /// inherits its impulses.
+    pub fn update(&mut self, manifold: Manifold, ta: &Transform, tb: &Transform) {
+        let threshold = PERSISTENCE_THRESHOLD;
+        let (inv_a, inv_b) = (ta.inverse(), tb.inverse());
+        let normal = manifold.normal;
+        let old: Vec<PersistentPoint> = self
+            .points
+            .iter()
+            .map(|p| {
+                let point_a = transform_point(ta, &p.local_a);
+                let point_b = transform_point(tb, &p.local_b);
+                PersistentPoint {
+                    contact: ContactPoint::new(point_a, point_b, &normal),
+                    ..*p
+                }
+            })
+            .collect();
+        let mut points: Vec<PersistentPoint> = manifold
+            .points
+            .iter()
+            .map(|c| {
+                let local_a = transform_point(&inv_a, &c.point_a);
+                let local_b = transform_point(&inv_b, &c.point_b);
+                let matched = old
+                    .iter()
+                    .find(|o| glm::distance(&o.local_a, &local_a) < threshold);
+                PersistentPoint {
+                    contact: *c,
+                    local_a,
+                    local_b,
+                    normal_impulse: matched.map_or(0.0, |o| o.normal_impulse),
+                    tangent_impulse: matched.map_or([0.0; 2], |o| o.tangent_impulse),
+                }
+            })
+            .collect();
+        if manifold.points.len() == 1 {
+            // keep old points still touching that did not slide apart
+            let kept: Vec<PersistentPoint> = old
+                .into_iter()
+                .filter(|o| {
+                    let drift = (o.contact.point_a - o.contact.point_b) - normal * o.contact.depth;
+                    o.contact.depth > -threshold
+                        && drift.norm() < threshold
+                        && points
+                            .iter()
+                            .all(|p| glm::distance(&p.local_a, &o.local_a) >= threshold)
+                })
+                .collect();
+            points.extend(kept);
+        }
+        let kept = reduce(points.iter().map(|p| p.contact).collect());
+        points.retain(|p| kept.contains(&p.contact));
+        self.normal = normal;
+        self.points = points;
+    }
+}
+
+fn transform_point(t: &Transform, p: &glm::Vec3) -> glm::Vec3 {
+    (t.tmatrix * glm::vec4(p.x, p.y, p.z, 1.0)).xyz()
+}
+
+// keep the deepest point, then repeatedly the one farthest from those kept
+fn reduce(mut points: Vec<ContactPoint>) -> Vec<ContactPoint> {
+    if points.len() <= MAX_MANIFOLD_POINTS {
+        return points;
+    }
+    let deepest = (0..points.len())
+        .max_by(|&i, &j| points[i].depth.total_cmp(&points[j].depth))
+        .unwrap();
+    let mut kept = vec![points.swap_remove(deepest)];
+    while kept.len() < MAX_MANIFOLD_POINTS {
+        let spread = |p: &ContactPoint| {
+            kept.iter()
+                .map(|k| glm::distance2(&k.point(), &p.point()))
+                .fold(f32::INFINITY, f32::min)
+        };
+        let farthest = (0..points.len())
+            .max_by(|&i, &j| spread(&points[i]).total_cmp(&spread(&points[j])))
+            .unwrap();
+        kept.push(points.swap_remove(farthest));
+    }
+    kept
+}
+
+/// Contacts between every pair of parts of two shapes, with the index of
+/// the parts; shapes other than `Shape::Compound` are a single part 0.
+pub fn collide_parts(
+    a: &Shape,
+    ta: &Transform,
+    b: &Shape,
+    tb: &Transform,
+) -> Vec<((usize, usize), Manifold)> {
+    fn split<'a>(shape: &'a Shape, t: &Transform) -> Vec<(&'a Shape, Transform)> {
+        match shape {
+            Shape::Compound { parts } => parts
+                .iter()
+                .map(|(offset, part)| (part, *t * *offset))
+                .collect(),
+            _ => vec![(shape, *t)],
+        }
+    }
+    let (parts_a, parts_b) = (split(a, ta), split(b, tb));
+    let mut manifolds = Vec::new();
+    for (i, (a, ta)) in parts_a.iter().enumerate() {
+        for (j, (b, tb)) in parts_b.iter().enumerate() {
+            if let Some(m) = collide(a, ta, b, tb) {
+                manifolds.push(((i, j), m));
+            }
+        }
+    }
+    manifolds
+}
+
+/// Contacts between two shapes placed at `ta` and `tb`, `None` when they are
+/// apart. Uses closed forms for planes, spheres, capsules and boxes and
+/// GJK/EPA for every other pair of convex shapes; a compound is treated as
+/// the hull of its parts.
+pub fn collide(a: &Shape, ta: &Transform, b: &Shape, tb: &Transform) -> Option<Manifold> {
+    let manifold = match (a, b) {
+        (Shape::Plane, Shape::Plane) => None,
+        (Shape::Plane, _) => plane_shape(ta, b, tb),
+        (_, Shape::Plane) => plane_shape(tb, a, ta).map(Manifold::flipped),
+        (Shape::Sphere { radius: ra }, Shape::Sphere { radius: rb }) => {
+            sphere_sphere(&ta.translation(), *ra, &tb.translation(), *rb)
+        }
+        (Shape::Cuboid { half_extents }, Shape::Sphere { radius }) => {
+            box_sphere(half_extents, ta, &tb.translation(), *radius)
+        }
+        (Shape::Sphere { radius }, Shape::Cuboid { half_extents }) => {
+            box_sphere(half_extents, tb, &ta.translation(), *radius).map(Manifold::flipped)
+        }
+        (Shape::Cuboid { half_extents: ha }, Shape::Cuboid { half_extents: hb }) => {
+            box_box(ha, ta, hb, tb)
+        }
+        (Shape::Capsule { radius: ra, height }, Shape::Sphere { radius: rb }) => {
+            let (p, q) = capsule_segment(ta, *height);
+            let c = closest_on_segment(&p, &q, &tb.translation());
+            sphere_sphere(&c, *ra, &tb.translation(), *rb)
+        }
+        (Shape::Sphere { radius: ra }, Shape::Capsule { radius: rb, height }) => {
+            let (p, q) = capsule_segment(tb, *height);
+            let c = closest_on_segment(&p, &q, &ta.translation());
+            sphere_sphere(&ta.translation(), *ra, &c, *rb)
+        }
+        (
+            Shape::Capsule {
+                radius: ra,
+                height: ha,
+            },
+            Shape::Capsule {
+                radius: rb,
+                height: hb,
+            },
+        ) => {
+            let (pa, qa) = capsule_segment(ta, *ha);
+            let (pb, qb) = capsule_segment(tb, *hb);
+            let (ca, cb) = closest_between_segments(&pa, &qa, &pb, &qb);
+            sphere_sphere(&ca, *ra, &cb, *rb)
+        }
+        _ => gjk_epa(a, ta, b, tb),
+    };
+    manifold.map(Manifold::reduced)
+}
+
+pub fn sphere_sphere(ca: &glm::Vec3, ra: f32, cb: &glm::Vec3, rb: f32) -> Option<Manifold> {
+    let d = cb - ca;
+    let distance = d.norm();
+    if distance > ra + rb {
+        return None;
+    }
+    let normal = if distance > TOLERANCE {
+        d / distance
+    } else {
+        glm::vec3(0.0, 0.0, 1.0)
+    };
+    Some(Manifold {
+        normal,
+        points: vec![ContactPoint::new(
+            ca + normal * ra,
+            cb - normal * rb,
+            &normal,
+        )],
+    })
+}
+
+/// Box of half size `half_extents` at `tb` against a sphere; the normal
+/// points from the box to the sphere.
+pub fn box_sphere(
+    half_extents: &glm::Vec3,
+    tb: &Transform,
+    center: &glm::Vec3,
+    radius: f32,
+) -> Option<Manifold> {
+    let r = tb.rotation();
+    let local = r.transpose() * (center - tb.translation());
+    let closest = glm::clamp_vec(&local, &-half_extents, half_extents);
+    let d = local - closest;
+    let distance = d.norm();
+    let (normal_local, surface) = if distance > TOLERANCE {
+        if distance > radius {
+            return None;
+        }
+        (d / distance, closest)
+    } else {
+        // centre inside the box: leave through the nearest face
+        let gap = half_extents - local.abs();
+        let axis = (0..3).min_by(|&i, &j| gap[i].total_cmp(&gap[j])).unwrap();
+        let sign = if local[axis] < 0.0 { -1.0 } else { 1.0 };
+        let mut normal = glm::Vec3::zeros();
+        normal[axis] = sign;
+        let mut surface = local;
+        surface[axis] = sign * half_extents[axis];
+        (normal, surface)
+    };
+    let normal = r * normal_local;
+    let point_a = tb.translation() + r * surface;
+    Some(Manifold {
+        normal,
+        points: vec![ContactPoint::new(
+            point_a,
+            center - normal * radius,
+            &normal,
+        )],
+    })
+}
+
+// ends of the segment at the core of a capsule, in world space
+fn capsule_segment(t: &Transform, height: f32) -> (glm::Vec3, glm::Vec3) {
+    let half = glm::vec3(0.0, 0.0, 0.5 * height);
+    (transform_point(t, &-half), transform_point(t, &half))
+}
+
+fn closest_on_segment(p: &glm::Vec3, q: &glm::Vec3, x: &glm::Vec3) -> glm::Vec3 {
+    let d = q - p;
+    let length2 = d.norm_squared();
+    if length2 < TOLERANCE * TOLERANCE {
+        return *p;
+    }
+    p + d * ((x - p).dot(&d) / length2).clamp(0.0, 1.0)
+}
+
+// closest points between segments pa-qa and pb-qb
+fn closest_between_segments(
+    pa: &glm::Vec3,
+    qa: &glm::Vec3,
+    pb: &glm::Vec3,
+    qb: &glm::Vec3,
+) -> (glm::Vec3, glm::Vec3) {
+    let (da, db) = (qa - pa, qb - pb);
+    let (ca, _) = closest_between_lines(pa, &da, pb, &db);
+    // clamp onto a, then go back and forth once, which is exact for
+    // segments
+    let ca = closest_on_segment(pa, qa, &ca);
+    let cb = closest_on_segment(pb, qb, &ca);
+    let ca = closest_on_segment(pa, qa, &cb);
+    (ca, cb)
+}
+
+// corners of a box in world space
+fn box_corners(half_extents: &glm::Vec3, t: &Transform) -> Vec<glm::Vec3> {
+    (0..8)
+        .map(|i| {
+            let sign = |bit: usize| if i & bit == 0 { -1.0 } else { 1.0 };
+            let local = glm::vec3(
+                sign(1) * half_extents.x,
+                sign(2) * half_extents.y,
+                sign(4) * half_extents.z,
+            );
+            transform_point(t, &local)
+        })
+        .collect()
+}
+
+// points of a shape that may touch a plane first
+fn plane_candidates(shape: &Shape, t: &Transform, normal: &glm::Vec3) -> Vec<glm::Vec3> {
+    match shape {
+        Shape::Sphere { radius } => vec![t.translation() - normal * *radius],
+        Shape::Cuboid { half_extents } => box_corners(half_extents, t),
+        Shape::Cylinder { radius, height } => {
+            const RIM: usize = 12;
+            let mut points: Vec<glm::Vec3> = (0..2 * RIM)
+                .map(|i| {
+                    let angle = std::f32::consts::TAU * (i % RIM) as f32 / RIM as f32;
+                    let z = if i < RIM { -0.5 } else { 0.5 } * height;
+                    let local = glm::vec3(radius * angle.cos(), radius * angle.sin(), z);
+                    transform_point(t, &local)
+                })
+                .collect();
+            points.push(support(shape, t, &-normal));
+            points
+        }
+        Shape::Capsule { radius, height } => {
+            let (p, q) = capsule_segment(t, *height);
+            vec![p - normal * *radius, q - normal * *radius]
+        }
+        Shape::ConvexMesh { points, .. } => points.iter().map(|p| transform_point(t, p)).collect(),
+        Shape::Compound { parts } => parts
+            .iter()
+            .flat_map(|(offset, part)| plane_candidates(part, &(*t * *offset), normal))
+            .collect(),
+        Shape::Plane => Vec::new(),
+    }
+}
+
+// plane at `tp` against any bounded shape; the normal is the plane's
+fn plane_shape(tp: &Transform, shape: &Shape, t: &Transform) -> Option<Manifold> {
+    let normal = tp.rotation() * glm::vec3(0.0, 0.0, 1.0);
+    let origin = tp.translation();
+    let points: Vec<ContactPoint> = plane_candidates(shape, t, &normal)
+        .into_iter()
+        .filter_map(|p| {
+            let depth = -normal.dot(&(p - origin));
+            (depth >= 0.0).then(|| ContactPoint::new(p + normal * depth, p, &normal))
+        })
+        .collect();
+    (!points.is_empty()).then_some(Manifold { normal, points })
+}
+
+/// Separating axis test between two boxes, clipping the incident face
+/// against the reference face for up to eight points before reduction.
+pub fn box_box(ha: &glm::Vec3, ta: &Transform, hb: &glm::Vec3, tb: &Transform) -> Option<Manifold> {
+    let (ra, rb) = (ta.rotation(), tb.rotation());
+    let axes_a: [glm::Vec3; 3] = [
+        ra.column(0).into(),
+        ra.column(1).into(),
+        ra.column(2).into(),
+    ];
+    let axes_b: [glm::Vec3; 3] = [
+        rb.column(0).into(),
+        rb.column(1).into(),
+        rb.column(2).into(),
+    ];
+    let d = tb.translation() - ta.translation();
+    let project = |h: &glm::Vec3, axes: &[glm::Vec3; 3], l: &glm::Vec3| {
+        (0..3).map(|i| h[i] * axes[i].dot(l).abs()).sum::<f32>()
+    };
+
+    // (overlap, axis from a to b, kind): kind 0..3 face of a, 3..6 face of
+    // b, 6.. the edge pair (i, j) as 6 + 3 i + j
+    let mut best: Option<(f32, glm::Vec3, usize)> = None;
+    let mut test = |axis: glm::Vec3, kind: usize| -> bool {
+        let length = axis.norm();
+        if length < TOLERANCE {
+            return true;
+        }
+        let mut axis = axis / length;
+        let overlap =
+            project(ha, &axes_a, &axis) + project(hb, &axes_b, &axis) - d.dot(&axis).abs();
+        if overlap < 0.0 {
+            return false;
+        }
+        if d.dot(&axis) < 0.0 {
+            axis = -axis;
+        }
+        // prefer faces, which give a proper manifold, over nearly equal edges
+        let biased = if kind >= 6 {
+            overlap * 1.05 + 1e-4
+        } else {
+            overlap
+        };
+        if best.is_none_or(|(o, _, _)| biased < o) {
+            best = Some((biased, axis, kind));
+        }
+        true
+    };
+    for i in 0..3 {
+        if !test(axes_a[i], i) || !test(axes_b[i], 3 + i) {
+            return None;
+        }
+    }
+    for (i, a) in axes_a.iter().enumerate() {
+        for (j, b) in axes_b.iter().enumerate() {
+            if !test(a.cross(b), 6 + 3 * i + j) {
+                return None;
+            }
+        }
+    }
+    let (_, normal, kind) = best?;
+
+    if kind >= 6 {
+        let (i, j) = ((kind - 6) / 3, (kind - 6) % 3);
+        // the edges of a and b furthest into each other
+        let edge_center =
+            |c: glm::Vec3, h: &glm::Vec3, axes: &[glm::Vec3; 3], skip, dir: &glm::Vec3| {
+                (0..3)
+                    .filter(|&k| k != skip)
+                    .fold(c, |p, k| p + axes[k] * h[k] * axes[k].dot(dir).signum())
+            };
+        let pa = edge_center(ta.translation(), ha, &axes_a, i, &normal);
+        let pb = edge_center(tb.translation(), hb, &axes_b, j, &-normal);
+        let (point_a, point_b) = closest_between_lines(&pa, &axes_a[i], &pb, &axes_b[j]);
+        return Some(Manifold {
+            normal,
+            points: vec![ContactPoint::new(point_a, point_b, &normal)],
+        });
+    }
+
+    // reference face on the box owning the axis, incident face on the other
+    let (reference, incident) = if kind < 3 {
+        ((ha, ta, &axes_a, kind, normal), (hb, tb, &axes_b))
+    } else {
+        ((hb, tb, &axes_b, kind - 3, -normal), (ha, ta, &axes_a))
+    };
+    let (h_ref, t_ref, axes_ref, axis, n) = reference;
+    let (h_inc, t_inc, axes_inc) = incident;
+    let face_center = t_ref.translation() + n * h_ref[axis];
+
+    let inc_axis = (0..3)
+        .max_by(|&i, &j| {
+            axes_inc[i]
+                .dot(&n)
+                .abs()
+                .total_cmp(&axes_inc[j].dot(&n).abs())
+        })
+        .unwrap();
+    let inc_sign = -axes_inc[inc_axis].dot(&n).signum();
+    let (u, v) = ((inc_axis + 1) % 3, (inc_axis + 2) % 3);
+    let inc_center = t_inc.translation() + axes_inc[inc_axis] * h_inc[inc_axis] * inc_sign;
+    let mut polygon: Vec<glm::Vec3> = [(1.0, 1.0), (-1.0, 1.0), (-1.0, -1.0), (1.0, -1.0)]
+        .iter()
+        .map(|(su, sv)| inc_center + axes_inc[u] * h_inc[u] * *su + axes_inc[v] * h_inc[v] * *sv)
+        .collect();
+
+    // clip against the four side planes of the reference face
+    for side in [(axis + 1) % 3, (axis + 2) % 3] {
+        for sign in [1.0f32, -1.0] {
+            let plane_normal = axes_ref[side] * sign;
+            let offset = plane_normal.dot(&t_ref.translation()) + h_ref[side];
+            polygon = clip(&polygon, &plane_normal, offset);
+        }
+    }
+
+    let points: Vec<ContactPoint> = polygon
+        .into_iter()
+        .filter_map(|p| {
+            let depth = -n.dot(&(p - face_center));
+            (depth >= 0.0).then(|| {
+                let on_face = p + n * depth;
+                if kind < 3 {
+                    ContactPoint::new(on_face, p, &normal)
+                } else {
+                    ContactPoint::new(p, on_face, &normal)
+                }
+            })
+        })
+        .collect();
+    (!points.is_empty()).then_some(Manifold { normal, points })
+}
+
+// Sutherland-Hodgman: the part of `polygon` with `normal . p <= offset`
+fn clip(polygon: &[glm::Vec3], normal: &glm::Vec3, offset: f32) -> Vec<glm::Vec3> {
+    let mut out = Vec::new();
+    for (i, p) in polygon.iter().enumerate() {
+        let q = &polygon[(i + 1) % polygon.len()];
+        let (dp, dq) = (normal.dot(p) - offset, normal.dot(q) - offset);
+        if dp <= 0.0 {
+            out.push(*p);
+        }
+        if (dp < 0.0) != (dq < 0.0) && (dp - dq).abs() > f32::EPSILON {
+            out.push(p + (q - p) * (dp / (dp - dq)));
+        }
+    }
+    out
+}
+
+fn closest_between_lines(
+    pa: &glm::Vec3,
+    da: &glm::Vec3,
+    pb: &glm::Vec3,
+    db: &glm::Vec3,
+) -> (glm::Vec3, glm::Vec3) {
+    let r = pa - pb;
+    let (a, b, c) = (da.dot(da), da.dot(db), db.dot(db));
+    let (d, e) = (da.dot(&r), db.dot(&r));
+    let denominator = a * c - b * b;
+    let s = if denominator.abs() > TOLERANCE {
+        (b * e - c * d) / denominator
+    } else {
+        0.0
+    };
+    let t = (b * s + e) / c;
+    (pa + da * s, pb + db * t)
+}
+
+/// Farthest point of a bounded shape at `t` in direction `dir`.
+pub fn support(shape: &Shape, t: &Transform, dir: &glm::Vec3) -> glm::Vec3 {
+    let r = t.rotation();
+    let local = r.transpose() * dir;
+    let sign = |x: f32| if x < 0.0 { -1.0 } else { 1.0 };
+    let point = match shape {
+        Shape::Sphere { radius } => match local.try_normalize(TOLERANCE) {
+            Some(n) => n * *radius,
+            None => glm::vec3(0.0, 0.0, *radius),
+        },
+        Shape::Cuboid { half_extents } => glm::vec3(
+            sign(local.x) * half_extents.x,
+            sign(local.y) * half_extents.y,
+            sign(local.z) * half_extents.z,
+        ),
+        Shape::Cylinder { radius, height } => {
+            let radial = glm::vec2(local.x, local.y);
+            let radial = match radial.try_normalize(TOLERANCE) {
+                Some(n) => n * *radius,
+                None => glm::Vec2::zeros(),
+            };
+            glm::vec3(radial.x, radial.y, sign(local.z) * 0.5 * height)
+        }
+        Shape::Capsule { radius, height } => {
+            let round = match local.try_normalize(TOLERANCE) {
+                Some(n) => n * *radius,
+                None => glm::Vec3::zeros(),
+            };
+            round + glm::vec3(0.0, 0.0, sign(local.z) * 0.5 * height)
+        }
+        Shape::Compound { parts } => {
+            return parts
+                .iter()
+                .map(|(offset, part)| support(part, &(*t * *offset), dir))
+                .max_by(|p, q| p.dot(dir).total_cmp(&q.dot(dir)))
+                .unwrap_or_else(|| t.translation())
+        }
+        Shape::ConvexMesh { points, .. } => points
+            .iter()
+            .copied()
+            .max_by(|p, q| p.dot(&local).total_cmp(&q.dot(&local)))
+            .unwrap_or_else(glm::Vec3::zeros),
+        Shape::Plane => glm::Vec3::zeros(),
+    };
+    t.translation() + r * point
+}
+
+// vertex of the Minkowski difference a - b, with the points it came from
+#[derive(Debug, Copy, Clone)]
+struct SupportPoint {
+    w: glm::Vec3,
+    a: glm::Vec3,
+    b: glm::Vec3,
+}
+
+struct Pair<'a> {
+    a: (&'a Shape, &'a Transform),
+    b: (&'a Shape, &'a Transform),
+}
+
+impl Pair<'_> {
+    fn support(&self, dir: &glm::Vec3) -> SupportPoint {
+        let a = support(self.a.0, self.a.1, dir);
+        let b = support(self.b.0, self.b.1, &-dir);
+        SupportPoint { w: a - b, a, b }
+    }
+}
+
+/// Result of a GJK query between two convex shapes.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Proximity {
+    /// The shapes overlap.
+    Intersecting,
+    /// The shapes are apart, with the closest points on each.
+    Separated {
+        distance: f32,
+        point_a: glm::Vec3,
+        point_b: glm::Vec3,
+    },
+}
+
+/// GJK distance between two bounded convex shapes.
+pub fn gjk(a: &Shape, ta: &Transform, b: &Shape, tb: &Transform) -> Proximity {
+    let pair = Pair {
+        a: (a, ta),
+        b: (b, tb),
+    };
+    match run_gjk(&pair) {
+        Ok(_) => Proximity::Intersecting,
+        Err(separated) => separated,
+    }
+}
+
+// the simplex enclosing the origin, or the closest points
+fn run_gjk(pair: &Pair) -> Result<Vec<SupportPoint>, Proximity> {
+    let start = pair.b.1.translation() - pair.a.1.translation();
+    let mut simplex = vec![pair.support(&if start.norm() > TOLERANCE {
+        start
+    } else {
+        glm::vec3(1.0, 0.0, 0.0)
+    })];
+    let mut closest = (simplex[0].w, vec![1.0]);
+    for _ in 0..GJK_ITERATIONS {
+        let (point, weights, used) = closest_on_simplex(&simplex);
+        simplex = used.iter().map(|&i| simplex[i]).collect();
+        closest = (point, weights);
+        if point.norm() < TOLERANCE || simplex.len() == 4 {
+            return Ok(simplex);
+        }
+        let w = pair.support(&-point);
+        // no vertex gets meaningfully closer to the origin
+        let repeated = simplex.iter().any(|s| (s.w - w.w).norm_squared() < 1e-12);
+        if repeated || point.norm_squared() - point.dot(&w.w) <= 1e-6 * point.norm_squared() {
+            break;
+        }
+        simplex.push(w);
+    }
+    let (point, weights) = closest;
+    let point_a = simplex.iter().zip(&weights).map(|(s, l)| s.a * *l).sum();
+    let point_b = simplex.iter().zip(&weights).map(|(s, l)| s.b * *l).sum();
+    Err(Proximity::Separated {
+        distance: point.norm(),
+        point_a,
+        point_b,
+    })
+}
+
+// point of the simplex closest to the origin, its barycentric weights and
+// the vertices of the smallest face containing it
+fn closest_on_simplex(simplex: &[SupportPoint]) -> (glm::Vec3, Vec<f32>, Vec<usize>) {
+    let n = simplex.len();
+    let mut best: Option<(glm::Vec3, Vec<f32>, Vec<usize>)> = None;
+    for subset in 1..(1usize << n) {
+        let used: Vec<usize> = (0..n).filter(|i| subset & (1 << i) != 0).collect();
+        let points: Vec<glm::Vec3> = used.iter().map(|&i| simplex[i].w).collect();
+        let Some(weights) = barycentric_of_origin(&points) else {
+            continue;
+        };
+        if weights.iter().any(|&l| l < -TOLERANCE) {
+            continue;
+        }
+        let point: glm::Vec3 = points.iter().zip(&weights).map(|(p, l)| p * *l).sum();
+        if best
+            .as_ref()
+            .is_none_or(|(b, _, _)| point.norm_squared() < b.norm_squared() - 1e-12)
+        {
+            best = Some((point, weights, used));
+        }
+    }
+    best.unwrap_or_else(|| (simplex[0].w, vec![1.0], vec![0]))
+}
+
+// weights of the projection of the origin onto the affine hull of `points`
+fn barycentric_of_origin(points: &[glm::Vec3]) -> Option<Vec<f32>> {
+    match points {
+        [_] => Some(vec![1.0]),
+        [a, b] => {
+            let ab = b - a;
+            let length = ab.norm_squared();
+            if length < 1e-12 {
+                return None;
+            }
+            let t = -a.dot(&ab) / length;
+            Some(vec![1.0 - t, t])
+        }
+        [a, b, c] => {
+            let (ab, ac) = (b - a, c - a);
+            let normal = ab.cross(&ac);
+            let area = normal.norm_squared();
+            if area < 1e-12 {
+                return None;
+            }
+            let p = normal * normal.dot(a) / area;
+            let (u, v) = (
+                (p - a).cross(&ac).dot(&normal) / area,
+                ab.cross(&(p - a)).dot(&normal) / area,
+            );
+            Some(vec![1.0 - u - v, u, v])
+        }
+        [a, b, c, d] => {
+            let m = glm::Mat3::from_columns(&[b - a, c - a, d - a]);
+            let x = m.try_inverse()? * -a;
+            Some(vec![1.0 - x.x - x.y - x.z, x.x, x.y, x.z])
+        }
+        _ => None,
+    }
+}
+
+/// GJK to detect the overlap, then EPA for the penetration normal and depth.
+pub fn gjk_epa(a: &Shape, ta: &Transform, b: &Shape, tb: &Transform) -> Option<Manifold> {
+    let pair = Pair {
+        a: (a, ta),
+        b: (b, tb),
+    };
+    let simplex = run_gjk(&pair).ok()?;
+    let tetrahedron = inflate(&pair, simplex)?;
+    epa(&pair, tetrahedron)
+}
+
+// grows a degenerate simplex touching the origin into a tetrahedron
+fn inflate(pair: &Pair, mut simplex: Vec<SupportPoint>) -> Option<Vec<SupportPoint>> {
+    let directions = [
+        glm::vec3(1.0, 0.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        glm::vec3(0.0, 0.0, 1.0),
+        glm::vec3(-1.0, 0.0, 0.0),
+        glm::vec3(0.0, -1.0, 0.0),
+        glm::vec3(0.0, 0.0, -1.0),
+    ];
+    let spans = |s: &[SupportPoint], w: &glm::Vec3| match s {
+        [a] => (w - a.w).norm() > TOLERANCE,
+        [a, b] => (b.w - a.w).cross(&(w - a.w)).norm() > TOLERANCE,
+        [a, b, c] => (b.w - a.w).cross(&(c.w - a.w)).dot(&(w - a.w)).abs() > TOLERANCE,
+        _ => false,
+    };
+    while simplex.len() < 4 {
+        let extra = directions.iter().find_map(|d| {
+            let s = pair.support(d);
+            spans(&simplex, &s.w).then_some(s)
+        })?;
+        simplex.push(extra);
+    }
+    Some(simplex)
+}
+
+fn epa(pair: &Pair, simplex: Vec<SupportPoint>) -> Option<Manifold> {
+    let mut vertices = simplex;
+    let mut faces: Vec<[usize; 3]> = vec![[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]];
+    // wind every face so its normal points away from the inside
+    let centroid: glm::Vec3 = vertices.iter().map(|v| v.w).sum::<glm::Vec3>() / 4.0;
+    for f in &mut faces {
+        let (a, b, c) = (vertices[f[0]].w, vertices[f[1]].w, vertices[f[2]].w);
+        if (b - a).cross(&(c - a)).dot(&(a - centroid)) < 0.0 {
+            f.swap(1, 2);
+        }
+    }
+    let face_plane = |vertices: &[SupportPoint], f: &[usize; 3]| {
+        let (a, b, c) = (vertices[f[0]].w, vertices[f[1]].w, vertices[f[2]].w);
+        let normal = (b - a).cross(&(c - a)).try_normalize(1e-12)?;
+        Some((normal, normal.dot(&a)))
+    };
+
+    let mut result = None;
+    for _ in 0..EPA_ITERATIONS {
+        let (index, normal, distance) = faces
+            .iter()
+            .enumerate()
+            .filter_map(|(i, f)| face_plane(&vertices, f).map(|(n, d)| (i, n, d)))
+            .min_by(|x, y| x.2.total_cmp(&y.2))?;
+        result = Some((faces[index], normal, distance));
+        let w = pair.support(&normal);
+        if w.w.dot(&normal) - distance < 1e-4 {
+            break;
+        }
+        // replace the faces the new vertex sees with a fan to their horizon
+        let new = vertices.len();
+        vertices.push(w);
+        let mut horizon: Vec<(usize, usize)> = Vec::new();
+        faces.retain(|f| {
+            let visible = face_plane(&vertices, f)
+                .is_none_or(|(n, _)| n.dot(&(w.w - vertices[f[0]].w)) > 0.0);
+            if visible {
+                for (a, b) in [(f[0], f[1]), (f[1], f[2]), (f[2], f[0])] {
+                    match horizon.iter().position(|&e| e == (b, a)) {
+                        Some(i) => {
+                            horizon.swap_remove(i);
+                        }
+                        None => horizon.push((a, b)),
+                    }
+                }
+            }
+            !visible
+        });
+        faces.extend(horizon.into_iter().map(|(a, b)| [a, b, new]));
+    }
+
+    let (face, normal, distance) = result?;
+    let points: Vec<glm::Vec3> = face.iter().map(|&i| vertices[i].w).collect();
+    let weights = barycentric_of_origin(&points)?;
+    let point_a = face
+        .iter()
+        .zip(&weights)
+        .map(|(&i, l)| vertices[i].a * *l)
+        .sum();
+    let point_b = face
+        .iter()
+        .zip(&weights)
+        .map(|(&i, l)| vertices[i].b * *l)
+        .sum();
+    Some(Manifold {
+        normal,
+        points: vec![ContactPoint {
+            point_a,
+            point_b,
+            depth: distance,
+        }],
+    })
+}
@@ -1,3 +1,13 @@
+use crate::broad_phase::{Aabb, BroadPhase, CollisionFilter};
+use crate::constraint::{JointConstraint, JointHandle, JointKind};
+use crate::geometry::{
+    BoxMesh, CapsuleMesh, CylinderMesh, PlaneMesh, Polyhedron, SphereMesh, Transform, TriMesh,
+};
+use crate::integrator::Integrator;
+use crate::narrow_phase::{self, ContactManifold};
+use crate::solver::ContactSolver;
+use nalgebra::UnitQuaternion;
+use std::collections::HashMap;
 
 pub trait PhysicsProgram {
     fn new() -> Self;
@@ -9,10 +19,6 @@ pub trait PhysicsProgram {
     fn solve_constraints(&mut self);
 }
 
-// pub struct PhysicsWorld {
-//     objects: Vec<Polyhedron>
-// }
-
 #[derive(Debug, Copy, Clone)]
 pub struct FreeBody {
     // linear kinematic info
@@ -20,11 +26,768 @@ pub struct FreeBody {
     pub vel: glm::Vec3,
     pub force: glm::Vec3,
     pub mass: f32,
-    // acc: Vec2,
 
-    //angular kinematic info
-    pub theta: f32,
-    pub omega: f32,
-    // alpha: f32,
+    //angular kinematic info, in world space unless noted
+    pub orientation: UnitQuaternion<f32>,
+    pub omega: glm::Vec3,
+    pub torque: glm::Vec3,
+    /// Inertia tensor about the centre of mass, in the body frame.
+    pub inertia: glm::Mat3,
+    inv_inertia_world: glm::Mat3,
+}
+
+impl Default for FreeBody {
+    fn default() -> Self {
+        Self {
+            posn: glm::Vec3::zeros(),
+            vel: glm::Vec3::zeros(),
+            force: glm::Vec3::zeros(),
+            mass: 1.0,
+            orientation: UnitQuaternion::identity(),
+            omega: glm::Vec3::zeros(),
+            torque: glm::Vec3::zeros(),
+            inertia: glm::Mat3::identity(),
+            inv_inertia_world: glm::Mat3::identity(),
+        }
+    }
+}
+
+impl FreeBody {
+    pub fn at(posn: glm::Vec3, mass: f32) -> Self {
+        Self {
+            posn,
+            mass,
+            ..Default::default()
+        }
+    }
+    pub fn with_inertia(mut self, inertia: glm::Mat3) -> Self {
+        self.inertia = inertia;
+        self.update_inertia();
+        self
+    }
+    pub fn with_orientation(mut self, orientation: UnitQuaternion<f32>) -> Self {
+        self.orientation = orientation;
+        self.update_inertia();
+        self
+    }
+    /// Zero for bodies of infinite (or unset) mass.
+    pub fn inverse_mass(&self) -> f32 {
+        if self.mass > 0.0 {
+            1.0 / self.mass
+        } else {
+            0.0
+        }
+    }
+    /// Inverse inertia tensor in world space as of the last
+    /// `update_inertia`; zero for bodies of infinite (or unset) mass.
+    pub fn inverse_inertia_world(&self) -> glm::Mat3 {
+        self.inv_inertia_world
+    }
+    /// Recomputes the world space inverse inertia after the orientation or
+    /// the inertia changed.
+    pub fn update_inertia(&mut self) {
+        let r = self.orientation.to_rotation_matrix().into_inner();
+        let inverse = match self.inertia.try_inverse() {
+            Some(inverse) if self.mass > 0.0 => inverse,
+            _ => glm::Mat3::zeros(),
+        };
+        self.inv_inertia_world = r * inverse * r.transpose();
+    }
+    pub fn apply_force(&mut self, force: glm::Vec3) {
+        self.force += force;
+    }
+    /// Applies `force` at the world space `point`, which also produces a
+    /// torque unless the point is the centre of mass.
+    pub fn apply_force_at_point(&mut self, force: glm::Vec3, point: glm::Vec3) {
+        self.force += force;
+        self.torque += (point - self.posn).cross(&force);
+    }
+    pub fn apply_torque(&mut self, torque: glm::Vec3) {
+        self.torque += torque;
+    }
+    pub fn apply_impulse(&mut self, impulse: glm::Vec3) {
+        self.vel += impulse * self.inverse_mass();
+    }
+    pub fn apply_impulse_at_point(&mut self, impulse: glm::Vec3, point: glm::Vec3) {
+        self.vel += impulse * self.inverse_mass();
+        self.omega += self.inv_inertia_world * (point - self.posn).cross(&impulse);
+    }
+    /// Velocity of the body at the world space `point`.
+    pub fn velocity_at(&self, point: glm::Vec3) -> glm::Vec3 {
+        self.vel + self.omega.cross(&(point - self.posn))
+    }
+    /// Advances the orientation by the angular velocity over `dt`.
+    pub fn integrate_orientation(&mut self, dt: f32) {
+        self.orientation = UnitQuaternion::from_scaled_axis(self.omega * dt) * self.orientation;
+        self.update_inertia();
+    }
+    pub fn transform(&self) -> Transform {
+        Transform {
+            tmatrix: glm::translation(&self.posn) * self.orientation.to_homogeneous(),
+        }
+    }
+}
+
+/// Collision geometry of a body, centred on the body position.
+#[derive(Debug, Clone)]
+pub enum Shape {
+    Sphere {
+        radius: f32,
+    },
+    /// Box with the given half size along each axis.
+    Cuboid {
+        half_extents: glm::Vec3,
+    },
+    /// Cylinder along the z axis.
+    Cylinder {
+        radius: f32,
+        height: f32,
+    },
+    /// Cylinder along the z axis capped by hemispheres; `height` is the
+    /// length of the cylinder alone.
+    Capsule {
+        radius: f32,
+        height: f32,
+    },
+    /// Infinite plane through the body position with normal +z, solid below.
+    Plane,
+    /// Convex hull of `points`, given in the body frame; `mesh` is only
+    /// used for rendering.
+    ConvexMesh {
+        points: Vec<glm::Vec3>,
+        mesh: Polyhedron,
+    },
+    /// Several bounded shapes placed in the body frame, e.g. the collision
+    /// elements of a robot link. Each part collides on its own.
+    Compound {
+        parts: Vec<(Transform, Shape)>,
+    },
+}
+
+impl Shape {
+    /// Hull of a mesh assumed to be convex, e.g. a simplified collision mesh.
+    pub fn convex_hull(mesh: Polyhedron) -> Self {
+        let mut points: Vec<glm::Vec3> = Vec::new();
+        for v in mesh.verts() {
+            if !points
+                .iter()
+                .any(|p| glm::distance2(p, &v.position) < 1e-12)
+            {
+                points.push(v.position);
+            }
+        }
+        Shape::ConvexMesh { points, mesh }
+    }
+    /// Radius of a sphere around the body position enclosing the shape.
+    pub fn bounding_radius(&self) -> f32 {
+        match self {
+            Shape::Sphere { radius } => *radius,
+            Shape::Cuboid { half_extents } => half_extents.norm(),
+            Shape::Cylinder { radius, height } => radius.hypot(0.5 * height),
+            Shape::Capsule { radius, height } => radius + 0.5 * height,
+            Shape::Plane => f32::INFINITY,
+            Shape::ConvexMesh { points, .. } => points.iter().map(|p| p.norm()).fold(0.0, f32::max),
+            Shape::Compound { parts } => parts
+                .iter()
+                .map(|(t, part)| t.translation().norm() + part.bounding_radius())
+                .fold(0.0, f32::max),
+        }
+    }
+    /// Inertia tensor of the shape as a solid of the given mass, about its
+    /// centre and in its own frame.
+    pub fn inertia(&self, mass: f32) -> glm::Mat3 {
+        let diagonal = match self {
+            Shape::Sphere { radius } => glm::Vec3::repeat(0.4 * mass * radius * radius),
+            Shape::Cuboid { half_extents } => {
+                let sq = half_extents.component_mul(half_extents);
+                glm::vec3(sq.y + sq.z, sq.x + sq.z, sq.x + sq.y) * mass / 3.0
+            }
+            Shape::Cylinder { radius, height } => {
+                let side = mass * (3.0 * radius * radius + height * height) / 12.0;
+                glm::vec3(side, side, 0.5 * mass * radius * radius)
+            }
+            Shape::Capsule { radius, height } => {
+                // mass shared by volume between the cylinder and the two
+                // hemispheres, which sit 3r/8 from the cylinder ends
+                let (r2, h) = (radius * radius, *height);
+                let cylinder_volume = h;
+                let sphere_volume = 4.0 / 3.0 * radius;
+                let mc = mass * cylinder_volume / (cylinder_volume + sphere_volume);
+                let ms = mass - mc;
+                let axial = 0.5 * mc * r2 + 0.4 * ms * r2;
+                let side = mc * (3.0 * r2 + h * h) / 12.0
+                    + ms * (0.4 * r2 + 0.25 * h * h + 0.375 * h * radius);
+                glm::vec3(side, side, axial)
+            }
+            Shape::Plane => glm::Vec3::zeros(),
+            // approximated by the box around the points
+            Shape::ConvexMesh { .. } | Shape::Compound { .. } => {
+                let Some(aabb) = self.aabb(&Transform::default()) else {
+                    return glm::Mat3::zeros();
+                };
+                return Shape::Cuboid {
+                    half_extents: aabb.half_extents(),
+                }
+                .inertia(mass);
+            }
+        };
+        glm::Mat3::from_diagonal(&diagonal)
+    }
+    /// Bounding box of the shape placed at `transform`, `None` for planes.
+    pub fn aabb(&self, transform: &Transform) -> Option<Aabb> {
+        match self {
+            Shape::Sphere { radius } => {
+                let c = transform.translation();
+                let r = glm::Vec3::repeat(*radius);
+                Some(Aabb::new(c - r, c + r))
+            }
+            Shape::Cuboid { half_extents } => Some(Aabb::from_box(half_extents, transform)),
+            Shape::Cylinder { radius, height } => Some(Aabb::from_box(
+                &glm::vec3(*radius, *radius, 0.5 * height),
+                transform,
+            )),
+            Shape::Capsule { radius, height } => Some(Aabb::from_box(
+                &glm::vec3(*radius, *radius, 0.5 * height + radius),
+                transform,
+            )),
+            Shape::Plane => None,
+            Shape::ConvexMesh { mesh, .. } => Aabb::from_polyhedron(mesh, transform),
+            // a compound without parts is a point that touches nothing
+            Shape::Compound { parts } => Some(
+                parts
+                    .iter()
+                    .filter_map(|(t, part)| part.aabb(&(*transform * *t)))
+                    .reduce(|a, b| a.union(&b))
+                    .unwrap_or_else(|| Aabb::new(transform.translation(), transform.translation())),
+            ),
+        }
+    }
+    /// Mesh of the shape for rendering.
+    pub fn polyhedron(&self) -> Polyhedron {
+        let mesh = match self {
+            Shape::Sphere { radius } => TriMesh::create_sphere(*radius, 20, 20),
+            Shape::Cuboid { half_extents } => TriMesh::create_box(2.0 * half_extents),
+            Shape::Cylinder { radius, height } => TriMesh::create_cylinder(*radius, *height, 20),
+            Shape::Capsule { radius, height } => TriMesh::create_capsule(*radius, *height, 20),
+            Shape::Plane => TriMesh::create_plane(),
+            Shape::ConvexMesh { mesh, .. } => return mesh.clone(),
+            Shape::Compound { parts } => {
+                let mut merged = Polyhedron::default();
+                for (t, part) in parts {
+                    let mesh = part.polyhedron();
+                    let offset = merged.verts.len() as u32;
+                    merged.verts.extend(mesh.verts.iter().map(|v| {
+                        let mut v = *t * *v;
+                        v.normal = t.rotation() * v.normal;
+                        v
+                    }));
+                    merged
+                        .indices
+                        .extend(mesh.indices.iter().map(|i| i + offset));
+                }
+                return merged;
+            }
+        };
+        Polyhedron::from(mesh)
+    }
+}
+
+/// Surface properties used when bodies touch. Two bodies in contact use
+/// the geometric mean of their friction and the larger restitution.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Material {
+    /// Coulomb friction coefficient.
+    pub friction: f32,
+    /// Fraction of the normal velocity kept after an impact, from 0 to 1.
+    pub restitution: f32,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            friction: 0.5,
+            restitution: 0.2,
+        }
+    }
+}
+
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BodyType {
+    /// Moved by forces and contacts.
+    #[default]
+    Dynamic,
+    /// Never moves, e.g. the ground.
+    Static,
+    /// Moved only by its velocity, unaffected by forces and contacts.
+    Kinematic,
 }
 
+#[derive(Debug, Clone)]
+pub struct Body {
+    pub dynamics: FreeBody,
+    pub shape: Shape,
+    pub material: Material,
+    pub body_type: BodyType,
+    pub filter: CollisionFilter,
+    /// World transform of the body after the last step.
+    pub transform: Transform,
+    // force and torque acting over the current substep, gravity included
+    applied: (glm::Vec3, glm::Vec3),
+    // pose at the start of the last step, for interpolation
+    previous: (glm::Vec3, UnitQuaternion<f32>),
+}
+
+impl Body {
+    /// A dynamic body whose inertia is that of a solid `shape`.
+    pub fn new(shape: Shape, dynamics: FreeBody) -> Self {
+        let dynamics = dynamics.with_inertia(shape.inertia(dynamics.mass));
+        Self {
+            transform: dynamics.transform(),
+            dynamics,
+            shape,
+            material: Material::default(),
+            body_type: BodyType::Dynamic,
+            filter: CollisionFilter::default(),
+            applied: (glm::Vec3::zeros(), glm::Vec3::zeros()),
+            previous: (dynamics.posn, dynamics.orientation),
+        }
+    }
+    pub fn fixed(shape: Shape, posn: glm::Vec3) -> Self {
+        Self {
+            body_type: BodyType::Static,
+            ..Self::new(shape, FreeBody::at(posn, 0.0))
+        }
+    }
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+        self
+    }
+    pub fn with_filter(mut self, filter: CollisionFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+    /// World space bounding box, `None` for unbounded shapes.
+    pub fn aabb(&self) -> Option<Aabb> {
+        self.shape.aabb(&self.transform)
+    }
+    /// Zero for bodies that contacts cannot move.
+    pub fn inverse_mass(&self) -> f32 {
+        match self.body_type {
+            BodyType::Dynamic => self.dynamics.inverse_mass(),
+            BodyType::Static | BodyType::Kinematic => 0.0,
+        }
+    }
+    pub fn inverse_inertia_world(&self) -> glm::Mat3 {
+        match self.body_type {
+            BodyType::Dynamic => self.dynamics.inverse_inertia_world(),
+            BodyType::Static | BodyType::Kinematic => glm::Mat3::zeros(),
+        }
+    }
+    /// Force and torque acting on the body over the current substep,
+    /// gravity included.
+    pub(crate) fn applied(&self) -> (glm::Vec3, glm::Vec3) {
+        self.applied
+    }
+    /// Transform between the pose before the last step (`alpha` = 0) and the
+    /// current one (`alpha` = 1).
+    pub fn interpolated_transform(&self, alpha: f32) -> Transform {
+        let (posn, orientation) = self.previous;
+        let posn = glm::lerp(&posn, &self.dynamics.posn, alpha);
+        let orientation = orientation.slerp(&self.dynamics.orientation, alpha);
+        Transform {
+            tmatrix: glm::translation(&posn) * orientation.to_homogeneous(),
+        }
+    }
+}
+
+/// Identifies a body of a `PhysicsWorld`. Handles of removed bodies are never
+/// reused, so they cannot refer to a body added later.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BodyHandle {
+    index: usize,
+    generation: u32,
+}
+
+#[derive(Debug, Clone)]
+struct Slot {
+    body: Option<Body>,
+    generation: u32,
+}
+
+/// Rigid bodies simulated together under gravity.
+#[derive(Debug, Clone)]
+pub struct PhysicsWorld {
+    slots: Vec<Slot>,
+    free: Vec<usize>,
+    contacts: Vec<ContactManifold>,
+    // sorted by handle
+    joints: Vec<JointConstraint>,
+    next_joint: usize,
+    broad_phase: BroadPhase<BodyHandle>,
+    accumulator: f32,
+    pub gravity: glm::Vec3,
+    /// Length of a step in seconds. Nothing moves while it is not positive.
+    pub dt: f32,
+    pub integrator: Integrator,
+    /// Number of times each step is subdivided; every phase runs once per
+    /// substep.
+    pub substeps: usize,
+    /// Most steps taken by one call to `advance`, so a slow frame cannot
+    /// make the next one slower.
+    pub max_steps: usize,
+    pub solver: ContactSolver,
+}
+
+impl Default for PhysicsWorld {
+    fn default() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            contacts: Vec::new(),
+            joints: Vec::new(),
+            next_joint: 0,
+            broad_phase: BroadPhase::new(),
+            accumulator: 0.0,
+            gravity: glm::vec3(0.0, 0.0, -9.81),
+            dt: 1.0 / 60.0,
+            integrator: Integrator::default(),
+            substeps: 1,
+            max_steps: 8,
+            solver: ContactSolver::default(),
+        }
+    }
+}
+
+impl PhysicsWorld {
+    pub fn add_body(&mut self, body: Body) -> BodyHandle {
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index];
+                slot.body = Some(body);
+                BodyHandle {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    body: Some(body),
+                    generation: 0,
+                });
+                BodyHandle {
+                    index: self.slots.len() - 1,
+                    generation: 0,
+                }
+            }
+        }
+    }
+    pub fn remove_body(&mut self, handle: BodyHandle) -> Option<Body> {
+        self.body(handle)?;
+        let slot = &mut self.slots[handle.index];
+        slot.generation += 1;
+        self.free.push(handle.index);
+        self.contacts.retain(|c| c.a != handle && c.b != handle);
+        self.joints.retain(|j| j.a != handle && j.b != handle);
+        self.broad_phase.remove(handle);
+        slot.body.take()
+    }
+    /// Adds a joint between two bodies of the world, which then no longer
+    /// collide with each other.
+    pub fn add_joint(&mut self, mut joint: JointConstraint) -> JointHandle {
+        let handle = JointHandle(self.next_joint);
+        self.next_joint += 1;
+        joint.handle = handle;
+        self.exclude_collision(joint.a, joint.b);
+        self.joints.push(joint);
+        handle
+    }
+    pub fn remove_joint(&mut self, handle: JointHandle) -> Option<JointConstraint> {
+        let index = self
+            .joints
+            .binary_search_by_key(&handle, |j| j.handle)
+            .ok()?;
+        Some(self.joints.remove(index))
+    }
+    pub fn joint(&self, handle: JointHandle) -> Option<&JointConstraint> {
+        let index = self
+            .joints
+            .binary_search_by_key(&handle, |j| j.handle)
+            .ok()?;
+        Some(&self.joints[index])
+    }
+    pub fn joint_mut(&mut self, handle: JointHandle) -> Option<&mut JointConstraint> {
+        let index = self
+            .joints
+            .binary_search_by_key(&handle, |j| j.handle)
+            .ok()?;
+        Some(&mut self.joints[index])
+    }
+    /// Joints in the world, in the order they were added.
+    pub fn joints(&self) -> impl Iterator<Item = &JointConstraint> {
+        self.joints.iter()
+    }
+    /// Never generate contacts between `a` and `b`, e.g. links sharing a
+    /// joint.
+    pub fn exclude_collision(&mut self, a: BodyHandle, b: BodyHandle) {
+        self.broad_phase.exclude_pair(a, b);
+    }
+    // pairs of bodies that may touch: overlapping boxes, then every plane
+    // against every other body
+    fn candidate_pairs(&mut self) -> Vec<(BodyHandle, BodyHandle)> {
+        let mut planes = Vec::new();
+        let boxes: Vec<_> = self
+            .bodies()
+            .map(|(h, b)| (h, b.aabb(), b.filter))
+            .collect();
+        for (handle, aabb, filter) in boxes {
+            match aabb {
+                Some(aabb) => self.broad_phase.insert(handle, aabb, filter),
+                None => planes.push((handle, filter)),
+            }
+        }
+        let mut pairs = self.broad_phase.pairs();
+        for (plane, filter) in planes {
+            pairs.extend(
+                self.bodies()
+                    .filter(|(h, b)| {
+                        *h != plane
+                            && filter.can_collide(&b.filter)
+                            && !self.broad_phase.is_excluded(plane, *h)
+                    })
+                    .map(|(h, _)| (plane, h)),
+            );
+        }
+        pairs
+    }
+    pub fn body(&self, handle: BodyHandle) -> Option<&Body> {
+        self.slots
+            .get(handle.index)
+            .filter(|s| s.generation == handle.generation)
+            .and_then(|s| s.body.as_ref())
+    }
+    pub fn body_mut(&mut self, handle: BodyHandle) -> Option<&mut Body> {
+        self.slots
+            .get_mut(handle.index)
+            .filter(|s| s.generation == handle.generation)
+            .and_then(|s| s.body.as_mut())
+    }
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Bodies in the world with their handles, in insertion order.
+    pub fn bodies(&self) -> impl Iterator<Item = (BodyHandle, &Body)> {
+        self.slots.iter().enumerate().filter_map(|(index, s)| {
+            let handle = BodyHandle {
+                index,
+                generation: s.generation,
+            };
+            s.body.as_ref().map(|b| (handle, b))
+        })
+    }
+    pub fn bodies_mut(&mut self) -> impl Iterator<Item = (BodyHandle, &mut Body)> {
+        self.slots.iter_mut().enumerate().filter_map(|(index, s)| {
+            let handle = BodyHandle {
+                index,
+                generation: s.generation,
+            };
+            s.body.as_mut().map(|b| (handle, b))
+        })
+    }
+    /// Contacts found by the last `detect_collisions`, one manifold per pair
+    /// of touching parts; the normal points from `a` to `b`.
+    pub fn contacts(&self) -> &[ContactManifold] {
+        &self.contacts
+    }
+    /// Runs as many fixed steps as fit in the time accumulated so far plus
+    /// `elapsed` seconds, e.g. the time since the last frame. Returns the
+    /// number of steps taken.
+    pub fn advance(&mut self, elapsed: f32) -> usize {
+        if self.dt <= 0.0 {
+            return 0;
+        }
+        self.accumulator += elapsed;
+        let mut steps = 0;
+        while self.accumulator >= self.dt && steps < self.max_steps {
+            self.step();
+            self.accumulator -= self.dt;
+            steps += 1;
+        }
+        // fall behind rather than trying to catch up forever
+        self.accumulator = self.accumulator.min(self.dt);
+        steps
+    }
+    /// Fraction of a step accumulated but not yet simulated, for
+    /// `Body::interpolated_transform`; 0 while `dt` is not positive.
+    pub fn alpha(&self) -> f32 {
+        if self.dt > 0.0 {
+            self.accumulator / self.dt
+        } else {
+            0.0
+        }
+    }
+    /// Transform of a body as it should be rendered after `advance`.
+    pub fn interpolated_transform(&self, handle: BodyHandle) -> Option<Transform> {
+        let alpha = self.alpha();
+        self.body(handle).map(|b| b.interpolated_transform(alpha))
+    }
+    /// Removes every body; their handles stay invalid.
+    pub fn clear(&mut self) {
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            if slot.body.take().is_some() {
+                slot.generation += 1;
+                self.free.push(index);
+            }
+        }
+        self.contacts.clear();
+        self.joints.clear();
+        self.broad_phase = BroadPhase::new();
+        self.accumulator = 0.0;
+    }
+}
+
+impl PhysicsProgram for PhysicsWorld {
+    fn new() -> Self {
+        Self::default()
+    }
+    /// Empties the world; bodies are then added with `add_body`.
+    fn setup(&mut self, _scene: &str) {
+        self.clear();
+    }
+    fn step(&mut self) {
+        if self.dt <= 0.0 {
+            return;
+        }
+        for (_, body) in self.bodies_mut() {
+            body.previous = (body.dynamics.posn, body.dynamics.orientation);
+        }
+        for _ in 0..self.substeps.max(1) {
+            // contacts are solved against the velocities the forces are about
+            // to produce, before anything moves, so resting bodies do not sink
+            self.apply_forces();
+            self.detect_collisions();
+            self.solve_constraints();
+            self.update_kinematics();
+        }
+        for (_, body) in self.bodies_mut() {
+            body.dynamics.force = glm::Vec3::zeros();
+            body.dynamics.torque = glm::Vec3::zeros();
+        }
+    }
+    /// Adds gravity, and the efforts of joint motors in `MotorMode::Effort`,
+    /// to the forces accumulated on each dynamic body since the last step.
+    fn apply_forces(&mut self) {
+        let gravity = self.gravity;
+        for (_, body) in self.bodies_mut() {
+            body.applied = match body.body_type {
+                BodyType::Dynamic => (
+                    body.dynamics.force + gravity * body.dynamics.mass,
+                    body.dynamics.torque,
+                ),
+                BodyType::Static | BodyType::Kinematic => (glm::Vec3::zeros(), glm::Vec3::zeros()),
+            };
+        }
+        let mut joints = std::mem::take(&mut self.joints);
+        for joint in &mut joints {
+            let Some(motor) = joint.motor else {
+                continue;
+            };
+            let (Some(n), Some(velocity), Some((fa, fb))) = (
+                joint.world_axis(self),
+                joint.velocity(self),
+                joint.world_frames(self),
+            ) else {
+                continue;
+            };
+            let effort = motor.effort(velocity);
+            joint.effort = effort;
+            if effort == 0.0 {
+                continue;
+            }
+            // equal and opposite on the two bodies, at the joint on each
+            let pairs = [(joint.a, fa, -effort), (joint.b, fb, effort)];
+            for (handle, frame, effort) in pairs {
+                let body = self.body_mut(handle).unwrap();
+                if body.body_type != BodyType::Dynamic {
+                    continue;
+                }
+                let (force, torque) = &mut body.applied;
+                match joint.kind {
+                    JointKind::Prismatic { .. } => {
+                        let r = frame.translation() - body.dynamics.posn;
+                        *force += n * effort;
+                        *torque += r.cross(&(n * effort));
+                    }
+                    _ => *torque += n * effort,
+                }
+            }
+        }
+        self.joints = joints;
+    }
+    /// Advances every body by one substep with the world's integrator.
+    fn update_kinematics(&mut self) {
+        let dt = self.dt / self.substeps.max(1) as f32;
+        let integrator = self.integrator;
+        for (_, body) in self.bodies_mut() {
+            let d = &mut body.dynamics;
+            match body.body_type {
+                BodyType::Dynamic => {
+                    let (force, torque) = body.applied;
+                    integrator.integrate(d, force, torque, dt);
+                }
+                BodyType::Kinematic => {
+                    d.posn += d.vel * dt;
+                    d.integrate_orientation(dt);
+                }
+                BodyType::Static => {
+                    d.vel = glm::Vec3::zeros();
+                    d.omega = glm::Vec3::zeros();
+                }
+            }
+            body.transform = d.transform();
+        }
+    }
+    /// Broad phase over the body bounding boxes, then the narrow phase on
+    /// each candidate pair that can move, one manifold per pair of touching
+    /// parts. Manifolds of parts still touching are updated rather than
+    /// rebuilt.
+    fn detect_collisions(&mut self) {
+        let mut previous: HashMap<_, _> = std::mem::take(&mut self.contacts)
+            .into_iter()
+            .map(|m| ((m.a, m.b, m.parts), m))
+            .collect();
+        let mut contacts = Vec::new();
+        for (ha, hb) in self.candidate_pairs() {
+            let (a, b) = (self.body(ha).unwrap(), self.body(hb).unwrap());
+            if a.inverse_mass() == 0.0 && b.inverse_mass() == 0.0 {
+                continue;
+            }
+            let manifolds =
+                narrow_phase::collide_parts(&a.shape, &a.transform, &b.shape, &b.transform);
+            for (parts, manifold) in manifolds {
+                let mut contact =
+                    previous
+                        .remove(&(ha, hb, parts))
+                        .unwrap_or_else(|| ContactManifold {
+                            parts,
+                            ..ContactManifold::new(ha, hb)
+                        });
+                contact.update(manifold, &a.transform, &b.transform);
+                contacts.push(contact);
+            }
+        }
+        self.contacts = contacts;
+    }
+    /// Resolves the contacts and joints with the world's `solver`.
+    fn solve_constraints(&mut self) {
+        let dt = self.dt / self.substeps.max(1) as f32;
+        let mut contacts = std::mem::take(&mut self.contacts);
+        let mut joints = std::mem::take(&mut self.joints);
+        let solver = self.solver;
+        solver.solve(self, &mut contacts, &mut joints, dt);
+        self.contacts = contacts;
+        self.joints = joints;
+    }
+}
@@ -0,0 +1,225 @@
+use crate::broad_phase::{Aabb, AabbTree};
+use crate::geometry::{Polyhedron, Transform};
+use crate::physics::{BodyHandle, PhysicsWorld, Shape};
+use crate::urdf::RobotDescriptor;
+use rayon::prelude::*;
+use std::cell::Cell;
+
+/// Closest surface met by a ray.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Hit {
+    /// Body the surface belongs to, when cast against a physics world.
+    pub body: Option<BodyHandle>,
+    /// Index in `RobotDescriptor::links` of the link the surface belongs to,
+    /// when cast against a robot.
+    pub link: Option<usize>,
+    pub point: glm::Vec3,
+    /// Unit normal of the surface, facing the ray.
+    pub normal: glm::Vec3,
+    /// Distance from the origin of the ray to `point`.
+    pub distance: f32,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Triangle {
+    vertices: [glm::Vec3; 3],
+    body: Option<BodyHandle>,
+    link: Option<usize>,
+}
+
+/// World space triangles, and infinite planes, in a bounding volume
+/// hierarchy for ray queries. Built once from a snapshot of a world or
+/// robot; rebuild it after things have moved.
+#[derive(Debug, Clone)]
+pub struct RaycastScene {
+    tree: AabbTree<usize>,
+    triangles: Vec<Triangle>,
+    // point, unit normal and owner of every plane
+    planes: Vec<(glm::Vec3, glm::Vec3, Option<BodyHandle>)>,
+}
+
+impl Default for RaycastScene {
+    fn default() -> Self {
+        Self {
+            tree: AabbTree::new(0.0),
+            triangles: Vec::new(),
+            planes: Vec::new(),
+        }
+    }
+}
+
+// distance along the ray where it enters `aabb`, if it does before `max`
+fn ray_aabb(origin: &glm::Vec3, inverse_dir: &glm::Vec3, aabb: &Aabb, max: f32) -> Option<f32> {
+    let (mut near, mut far) = (0.0f32, max);
+    for i in 0..3 {
+        let t0 = (aabb.min[i] - origin[i]) * inverse_dir[i];
+        let t1 = (aabb.max[i] - origin[i]) * inverse_dir[i];
+        // NaN, from a ray in the plane of a face, is ignored by min and max
+        near = near.max(t0.min(t1));
+        far = far.min(t0.max(t1));
+    }
+    (near <= far).then_some(near)
+}
+
+// Möller-Trumbore, from both sides
+fn ray_triangle(origin: &glm::Vec3, dir: &glm::Vec3, [a, b, c]: &[glm::Vec3; 3]) -> Option<f32> {
+    let (ab, ac) = (b - a, c - a);
+    let p = dir.cross(&ac);
+    let det = ab.dot(&p);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let s = origin - a;
+    let u = s.dot(&p) / det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(&ab);
+    let v = dir.dot(&q) / det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = ac.dot(&q) / det;
+    (t >= 0.0).then_some(t)
+}
+
+impl RaycastScene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Every body of `world` where it is now, with planes kept infinite.
+    pub fn from_world(world: &PhysicsWorld) -> Self {
+        let mut scene = Self::new();
+        for (handle, body) in world.bodies() {
+            match body.shape {
+                Shape::Plane => scene.add_plane(
+                    body.transform.translation(),
+                    body.transform.rotation() * glm::Vec3::z(),
+                    Some(handle),
+                ),
+                ref shape => {
+                    scene.add_polyhedron(&shape.polyhedron(), &body.transform, Some(handle), None)
+                }
+            }
+        }
+        scene
+    }
+    /// The visual geometry of every link of `robot`, as placed by the last
+    /// `build`.
+    pub fn from_robot(robot: &RobotDescriptor) -> Self {
+        let mut scene = Self::new();
+        for (i, link) in robot.links.iter().enumerate() {
+            scene.add_polyhedron(&link.visual.geometry, &link.visual.transform, None, Some(i));
+        }
+        scene
+    }
+    /// Adds the triangles of `mesh` placed at `transform`, reported in hits
+    /// as belonging to `body` and `link`.
+    pub fn add_polyhedron(
+        &mut self,
+        mesh: &Polyhedron,
+        transform: &Transform,
+        body: Option<BodyHandle>,
+        link: Option<usize>,
+    ) {
+        let verts: Vec<glm::Vec3> = mesh
+            .verts
+            .iter()
+            .map(|v| (*transform * *v).position)
+            .collect();
+        for face in mesh.indices.chunks_exact(3) {
+            let vertices = [0, 1, 2].map(|k| verts[face[k] as usize]);
+            let Some(aabb) = Aabb::from_points(vertices) else {
+                continue;
+            };
+            self.tree.insert(aabb, self.triangles.len());
+            self.triangles.push(Triangle {
+                vertices,
+                body,
+                link,
+            });
+        }
+    }
+    /// Adds the infinite plane through `point` with the given normal.
+    pub fn add_plane(&mut self, point: glm::Vec3, normal: glm::Vec3, body: Option<BodyHandle>) {
+        self.planes.push((point, normal.normalize(), body));
+    }
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+    /// Closest hit of the ray from `origin` along `dir` within `max_dist`.
+    pub fn raycast(&self, origin: glm::Vec3, dir: glm::Vec3, max_dist: f32) -> Option<Hit> {
+        let dir = dir.try_normalize(f32::EPSILON)?;
+        let inverse_dir = glm::Vec3::repeat(1.0).component_div(&dir);
+        // shrinks as hits are found, pruning everything further away
+        let best = Cell::new(max_dist);
+        let mut hit: Option<(f32, glm::Vec3, Option<BodyHandle>, Option<usize>)> = None;
+        for &(point, normal, body) in &self.planes {
+            let rate = normal.dot(&dir);
+            if rate.abs() < 1e-12 {
+                continue;
+            }
+            let t = (point - origin).dot(&normal) / rate;
+            if (0.0..=best.get()).contains(&t) {
+                best.set(t);
+                hit = Some((t, normal, body, None));
+            }
+        }
+        self.tree.traverse(
+            |aabb| ray_aabb(&origin, &inverse_dir, aabb, best.get()).is_some(),
+            |_, &i| {
+                let triangle = &self.triangles[i];
+                if let Some(t) = ray_triangle(&origin, &dir, &triangle.vertices) {
+                    if t <= best.get() {
+                        let [a, b, c] = triangle.vertices;
+                        best.set(t);
+                        hit = Some((t, (b - a).cross(&(c - a)), triangle.body, triangle.link));
+                    }
+                }
+            },
+        );
+        hit.map(|(distance, normal, body, link)| {
+            let normal = normal.normalize();
+            Hit {
+                body,
+                link,
+                point: origin + dir * distance,
+                normal: if normal.dot(&dir) > 0.0 {
+                    -normal
+                } else {
+                    normal
+                },
+                distance,
+            }
+        })
+    }
+    /// `raycast` for every `(origin, dir)` of `rays`, in parallel.
+    pub fn raycast_many(&self, rays: &[(glm::Vec3, glm::Vec3)], max_dist: f32) -> Vec<Option<Hit>> {
+        rays.par_iter()
+            .map(|&(origin, dir)| self.raycast(origin, dir, max_dist))
+            .collect()
+    }
+}
+
+impl PhysicsWorld {
+    /// Closest body hit by the ray from `origin` along `dir` within
+    /// `max_dist`. Builds a `RaycastScene` each call; keep one for many
+    /// queries between steps.
+    pub fn raycast(&self, origin: glm::Vec3, dir: glm::Vec3, max_dist: f32) -> Option<Hit> {
+        RaycastScene::from_world(self).raycast(origin, dir, max_dist)
+    }
+    pub fn raycast_many(&self, rays: &[(glm::Vec3, glm::Vec3)], max_dist: f32) -> Vec<Option<Hit>> {
+        RaycastScene::from_world(self).raycast_many(rays, max_dist)
+    }
+}
+
+impl RobotDescriptor {
+    /// Closest link hit by the ray from `origin` along `dir` within
+    /// `max_dist`, e.g. for picking.
+    pub fn raycast(&self, origin: glm::Vec3, dir: glm::Vec3, max_dist: f32) -> Option<Hit> {
+        RaycastScene::from_robot(self).raycast(origin, dir, max_dist)
+    }
+    pub fn raycast_many(&self, rays: &[(glm::Vec3, glm::Vec3)], max_dist: f32) -> Vec<Option<Hit>> {
+        RaycastScene::from_robot(self).raycast_many(rays, max_dist)
+    }
+}
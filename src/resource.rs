@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Resolves the resource URIs found in URDF files (`package://`, `file://`,
+/// absolute and relative paths) to files on disk.
+#[derive(Default, Debug, Clone)]
+pub struct ResourceResolver {
+    // explicitly registered packages, name -> package directory
+    packages: HashMap<String, PathBuf>,
+    // directories containing packages, searched in order (like ROS_PACKAGE_PATH)
+    search_paths: Vec<PathBuf>,
+}
+
+impl ResourceResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Creates a resolver searching the directories listed in `ROS_PACKAGE_PATH`.
+    pub fn from_env() -> Self {
+        match std::env::var_os("ROS_PACKAGE_PATH") {
+            Some(paths) => Self {
+                search_paths: std::env::split_paths(&paths).collect(),
+                ..Default::default()
+            },
+            None => Self::default(),
+        }
+    }
+    /// Creates a resolver from a `ROS_PACKAGE_PATH`-style list of directories.
+    pub fn from_package_path(paths: &str) -> Self {
+        Self {
+            search_paths: std::env::split_paths(paths).collect(),
+            ..Default::default()
+        }
+    }
+    /// Maps `package://name/...` to files under `dir`.
+    pub fn with_package(mut self, name: &str, dir: impl Into<PathBuf>) -> Self {
+        self.packages.insert(name.to_owned(), dir.into());
+        self
+    }
+    /// Adds a directory whose subdirectories are packages.
+    pub fn with_search_path(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.search_paths.push(dir.into());
+        self
+    }
+
+    fn find_package(&self, name: &str) -> Option<PathBuf> {
+        if let Some(dir) = self.packages.get(name) {
+            return Some(dir.clone());
+        }
+        self.search_paths.iter().find_map(|root| {
+            if root.file_name().is_some_and(|f| f == name) && root.is_dir() {
+                Some(root.clone())
+            } else {
+                Some(root.join(name)).filter(|dir| dir.is_dir())
+            }
+        })
+    }
+
+    /// Resolves `uri` to an existing file. Relative paths are looked up next to
+    /// `relative_to` (usually the directory of the URDF file) first, then
+    /// against the working directory.
+    pub fn resolve(&self, uri: &str, relative_to: Option<&Path>) -> Option<PathBuf> {
+        if let Some(rest) = uri.strip_prefix("package://") {
+            let (package, file) = rest.split_once('/')?;
+            return Some(self.find_package(package)?.join(file)).filter(|p| p.is_file());
+        }
+        let path = Path::new(uri.strip_prefix("file://").unwrap_or(uri));
+        if path.is_relative() {
+            if let Some(base) = relative_to {
+                let candidate = base.join(path);
+                if candidate.is_file() {
+                    return Some(candidate);
+                }
+            }
+        }
+        Some(path.to_path_buf()).filter(|p| p.is_file())
+    }
+}
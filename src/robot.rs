@@ -1,4 +1,4 @@
-use crate::{urdf::RobotDescriptor, wgpu_program::{MeshBuffer, WGPUGraphics}};
+use crate::{graphics::GraphicsContext, urdf::RobotDescriptor, wgpu_program::{MeshBuffer, WGPUState}};
 
 
 pub trait RobotGraphics {
@@ -14,7 +14,7 @@ pub trait RobotGraphics {
     // fn robot_create_bindings(&mut self, /* robot: &RobotDescriptor, */ light_buffer: &wgpu::Buffer, camera_buffer: &wgpu::Buffer, transform_buffer: &Vec<wgpu::Buffer>);
 }
 
-impl RobotGraphics for WGPUGraphics<'_> {
+impl<W> RobotGraphics for GraphicsContext<WGPUState<'_>, W, wgpu::Buffer> {
     fn robot_create_mesh_buffers(&mut self, robot: &RobotDescriptor) -> Vec<MeshBuffer> {
         self.create_mesh_buffers(robot.links.iter().map(|l| &l.visual.geometry))
     }
@@ -26,7 +26,7 @@ impl RobotGraphics for WGPUGraphics<'_> {
         self.draw_mesh_list(pipeline, &buffers);
     }
     fn robot_create_transform_buffers(&mut self, robot: &RobotDescriptor) -> Vec<wgpu::Buffer> {
-        self.create_transform_buffers(robot.links.iter().map(|l| l.inertial.transform))
+        self.create_transform_buffers(robot.links.iter().map(|l| l.visual.transform))
     }
     fn robot_assign_transform_buffers(
         &mut self,
@@ -34,6 +34,6 @@ impl RobotGraphics for WGPUGraphics<'_> {
         buffers: &Vec<wgpu::Buffer>,
     ) {
         // std::iter::zip(buffers, &robot.links).for_each(|(b,l)| self.assign_uniform(b, &[l.inertial.transform]))
-        self.update_transforms(buffers, robot.links.iter().map(|l| l.inertial.transform))
+        self.update_transforms(buffers, robot.links.iter().map(|l| l.visual.transform))
     }
 }
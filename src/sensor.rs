@@ -0,0 +1,600 @@
+use crate::bindings::create_uniform_bind_group;
+use crate::geometry::Transform;
+use crate::graphics::GraphicsContext;
+use crate::raycast::RaycastScene;
+use crate::texture::Texture;
+use crate::urdf::{RayScan, RobotDescriptor, SensorKind};
+use crate::wgpu_program::{DrawMeshBuffer, MeshBuffer, WGPUState};
+use anyhow::Context;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use std::f32::consts::TAU;
+
+/// Pinhole camera model: focal lengths and principal point in pixels, image
+/// size, and the range of depths it sees.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CameraIntrinsics {
+    pub fx: f32,
+    pub fy: f32,
+    pub cx: f32,
+    pub cy: f32,
+    pub width: u32,
+    pub height: u32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl CameraIntrinsics {
+    /// Centred camera with square pixels seeing `fov` radians across.
+    pub fn from_fov(width: u32, height: u32, fov: f32, near: f32, far: f32) -> Self {
+        let f = width as f32 / (2.0 * (fov / 2.0).tan());
+        Self {
+            fx: f,
+            fy: f,
+            cx: width as f32 / 2.0,
+            cy: height as f32 / 2.0,
+            width,
+            height,
+            near,
+            far,
+        }
+    }
+    /// Projection from the OpenGL eye frame (looking down -z, y up) to wgpu
+    /// clip space, matching the pixels of the intrinsics.
+    pub fn projection(&self) -> glm::Mat4 {
+        let (w, h) = (self.width as f32, self.height as f32);
+        let (n, f) = (self.near, self.far);
+        #[rustfmt::skip]
+        let projection = glm::Mat4::new(
+            2.0 * self.fx / w, 0.0, 1.0 - 2.0 * self.cx / w, 0.0,
+            0.0, 2.0 * self.fy / h, 2.0 * self.cy / h - 1.0, 0.0,
+            0.0, 0.0, -f / (f - n), -f * n / (f - n),
+            0.0, 0.0, -1.0, 0.0,
+        );
+        projection
+    }
+    /// Distance along the optical axis of a value of the depth buffer.
+    pub fn linear_depth(&self, depth: f32) -> f32 {
+        if depth >= 1.0 {
+            return f32::INFINITY;
+        }
+        let (n, f) = (self.near, self.far);
+        f * n / (f - depth * (f - n))
+    }
+    /// Point at `depth` along the optical axis through pixel `(u, v)`, in
+    /// the optical frame.
+    pub fn unproject(&self, u: f32, v: f32, depth: f32) -> glm::Vec3 {
+        glm::vec3(
+            (u - self.cx) / self.fx * depth,
+            (v - self.cy) / self.fy * depth,
+            depth,
+        )
+    }
+}
+
+// the optical frame (x right, y down, looking down z) seen from the OpenGL
+// eye frame (x right, y up, looking down -z)
+fn optical_to_eye() -> glm::Mat4 {
+    glm::diagonal4x4(&glm::vec4(1.0, -1.0, -1.0, 1.0))
+}
+
+/// Color and depth images captured by an `RgbdCamera`.
+#[derive(Debug, Clone)]
+pub struct RgbdFrame {
+    pub color: image::RgbaImage,
+    /// Distance along the optical axis of every pixel, row after row;
+    /// infinite where nothing was seen.
+    pub depth: Vec<f32>,
+    pub intrinsics: CameraIntrinsics,
+    /// The optical frame in the frame of the camera's link.
+    pub offset: Transform,
+}
+
+impl RgbdFrame {
+    pub fn depth_at(&self, u: u32, v: u32) -> f32 {
+        self.depth[(v * self.intrinsics.width + u) as usize]
+    }
+    /// A point for every pixel that saw something, in the frame of the
+    /// camera's link.
+    pub fn point_cloud(&self) -> Vec<glm::Vec3> {
+        let width = self.intrinsics.width as usize;
+        self.depth
+            .iter()
+            .enumerate()
+            .filter(|(_, d)| d.is_finite())
+            .map(|(i, &d)| {
+                let (u, v) = ((i % width) as f32 + 0.5, (i / width) as f32 + 0.5);
+                let p = self.intrinsics.unproject(u, v, d);
+                let p = self.offset.tmatrix * glm::vec4(p.x, p.y, p.z, 1.0);
+                p.xyz()
+            })
+            .collect()
+    }
+}
+
+// draws a depth texture into a color target that can be read back, for
+// adapters that cannot copy depth textures into buffers
+struct DepthCopy {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    target: wgpu::Texture,
+}
+
+impl DepthCopy {
+    fn new(device: &wgpu::Device, depth: &wgpu::Texture) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("depth_copy_bind_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    // sampled as a float texture, which GLSL can load from
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("depth_copy_bind_group"),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(
+                    &depth.create_view(&wgpu::TextureViewDescriptor::default()),
+                ),
+            }],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Depth Copy Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/depth_copy.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Depth Copy Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Depth Copy Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(format.into())],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("depth_copy_texture"),
+            size: depth.size(),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        Self {
+            pipeline,
+            bind_group,
+            target,
+        }
+    }
+    fn encode(&self, encoder: &mut wgpu::CommandEncoder) {
+        let view = self
+            .target
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Depth Copy Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+/// A virtual RGB-D camera fixed to a robot link. It looks down the z axis
+/// of its optical frame, with x to the right of the image and y down, and
+/// renders into its own targets, so it works with a window or offscreen.
+pub struct RgbdCamera {
+    pub intrinsics: CameraIntrinsics,
+    /// Index of the link in `RobotDescriptor::links`.
+    pub link: usize,
+    /// The optical frame in the link frame.
+    pub offset: Transform,
+    color: wgpu::Texture,
+    depth: wgpu::Texture,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+    depth_copy: Option<DepthCopy>,
+}
+
+impl RgbdCamera {
+    /// Attaches a camera at `offset` from the named link of `robot`; needs
+    /// the bindings of `program` to be created.
+    pub fn new<W>(
+        program: &mut GraphicsContext<WGPUState<'_>, W, wgpu::Buffer>,
+        robot: &RobotDescriptor,
+        link_name: &str,
+        offset: Transform,
+        intrinsics: CameraIntrinsics,
+    ) -> anyhow::Result<Self> {
+        let link = robot
+            .link_index(link_name)
+            .with_context(|| format!("no known link with name \"{}\"", link_name))?;
+        let size = wgpu::Extent3d {
+            width: intrinsics.width,
+            height: intrinsics.height,
+            depth_or_array_layers: 1,
+        };
+        let target = |label: &str, format: wgpu::TextureFormat| {
+            program.device().create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::COPY_SRC
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+        };
+        // the format the render pipelines are made for
+        let color = target("sensor_color_texture", program.config().format);
+        let depth = target("sensor_depth_texture", Texture::DEPTH_FORMAT);
+        let camera_buffer = program.create_buffer(
+            "Sensor Camera Buffer",
+            &[[[0.0f32; 4]; 4]],
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        );
+        let camera_bind_group = create_uniform_bind_group(
+            program.device(),
+            program.camera_bind_layout(),
+            &camera_buffer,
+            "sensor_camera_bind_group",
+        );
+        let copies_depth = program
+            .adapter()
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::DEPTH_TEXTURE_AND_BUFFER_COPIES);
+        let depth_copy = (!copies_depth).then(|| DepthCopy::new(program.device(), &depth));
+        Ok(Self {
+            intrinsics,
+            link,
+            offset,
+            color,
+            depth,
+            camera_buffer,
+            camera_bind_group,
+            depth_copy,
+        })
+    }
+    /// World transform of the optical frame, with `robot` posed.
+    pub fn pose(&self, robot: &RobotDescriptor) -> Transform {
+        robot.links[self.link].transform * self.offset
+    }
+    /// Renders `meshes`, placed by the transform buffers bound to `program`,
+    /// from where the camera is on `robot` and reads the images back.
+    pub fn capture<W>(
+        &self,
+        program: &GraphicsContext<WGPUState<'_>, W, wgpu::Buffer>,
+        robot: &RobotDescriptor,
+        pipeline: &wgpu::RenderPipeline,
+        meshes: &Vec<MeshBuffer>,
+    ) -> anyhow::Result<RgbdFrame> {
+        let view = optical_to_eye() * self.pose(robot).inverse().tmatrix;
+        let view_proj: [[f32; 4]; 4] = (self.intrinsics.projection() * view).into();
+        program.assign_buffer(&self.camera_buffer, &[view_proj]);
+
+        let bindings = program
+            .state
+            .bindings
+            .as_ref()
+            .context("no bindings created")?;
+        let color_view = self
+            .color
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let depth_view = self
+            .depth
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder =
+            program
+                .device()
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Sensor Encoder"),
+                });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Sensor Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &color_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(program.bg_color.into()),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.draw_mesh_list(
+                meshes,
+                &self.camera_bind_group,
+                &bindings.light_bind_group,
+                &bindings.transform_bind_groups,
+            );
+        }
+        if let Some(depth_copy) = &self.depth_copy {
+            depth_copy.encode(&mut encoder);
+        }
+        program.queue().submit(std::iter::once(encoder.finish()));
+
+        let mut pixels = program.read_texture(&self.color, wgpu::TextureAspect::All)?;
+        if matches!(
+            self.color.format(),
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+        ) {
+            pixels.chunks_mut(4).for_each(|p| p.swap(0, 2));
+        }
+        let (width, height) = (self.intrinsics.width, self.intrinsics.height);
+        let color = image::RgbaImage::from_raw(width, height, pixels)
+            .context("unexpected readback size")?;
+        let depth = match &self.depth_copy {
+            Some(depth_copy) => {
+                program.read_texture(&depth_copy.target, wgpu::TextureAspect::All)?
+            }
+            None => program.read_texture(&self.depth, wgpu::TextureAspect::DepthOnly)?,
+        };
+        let depth = depth
+            .chunks(4)
+            .map(|b| {
+                self.intrinsics
+                    .linear_depth(f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            })
+            .collect();
+        Ok(RgbdFrame {
+            color,
+            depth,
+            intrinsics: self.intrinsics,
+            offset: self.offset,
+        })
+    }
+}
+
+/// Scan pattern, range and noise of a `Lidar`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LidarConfig {
+    /// Angles about the z axis of the sensor frame, from x towards y.
+    pub horizontal: RayScan,
+    /// Elevations above the xy plane of the sensor frame.
+    pub vertical: RayScan,
+    /// Returns closer than this are not seen.
+    pub min_range: f32,
+    pub max_range: f32,
+    /// Standard deviation of the gaussian noise added to every range.
+    pub noise_stddev: f32,
+    /// Makes the noise reproducible: every ray draws from its own generator
+    /// seeded from this, so a sweep of the same scene always returns the
+    /// same ranges. `None` draws fresh noise on every sweep.
+    pub seed: Option<u64>,
+}
+
+impl LidarConfig {
+    /// A scanner sweeping `samples` rays in the xy plane, across `fov`
+    /// radians centred on the x axis.
+    pub fn planar(samples: u32, fov: f32, min_range: f32, max_range: f32) -> Self {
+        Self {
+            horizontal: RayScan {
+                samples,
+                resolution: 1.0,
+                min_angle: -fov / 2.0,
+                max_angle: fov / 2.0,
+            },
+            vertical: RayScan::default(),
+            min_range,
+            max_range,
+            noise_stddev: 0.0,
+            seed: None,
+        }
+    }
+}
+
+// evenly spaced from the first angle to the last, as many as the samples
+// scaled by the resolution
+fn scan_angles(scan: &RayScan) -> Vec<f32> {
+    let count = (scan.samples as f32 * scan.resolution).round().max(1.0) as usize;
+    let step = match count {
+        1 => 0.0,
+        _ => (scan.max_angle - scan.min_angle) / (count - 1) as f32,
+    };
+    (0..count)
+        .map(|i| scan.min_angle + step * i as f32)
+        .collect()
+}
+
+// standard normal sample by the Box-Muller transform
+fn gaussian<R: Rng>(rng: &mut R) -> f32 {
+    let u1: f32 = rng.gen_range(f32::EPSILON..1.0);
+    let u2: f32 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (TAU * u2).cos()
+}
+
+/// Ranges measured by one sweep of a `Lidar`.
+#[derive(Debug, Clone)]
+pub struct LidarScan {
+    /// A row of horizontal samples per vertical sample, lowest row first;
+    /// infinite for rays without a return.
+    pub ranges: Vec<f32>,
+    /// Point of every return, in the frame of the lidar's link.
+    pub points: Vec<glm::Vec3>,
+    /// Number of horizontal samples in a row of `ranges`.
+    pub width: usize,
+}
+
+impl LidarScan {
+    pub fn range_at(&self, horizontal: usize, vertical: usize) -> f32 {
+        self.ranges[vertical * self.width + horizontal]
+    }
+}
+
+/// A scanning range finder fixed to a robot link, casting a ray along every
+/// direction of its scan pattern. The sensor frame has x forward and z up.
+#[derive(Debug, Clone)]
+pub struct Lidar {
+    pub config: LidarConfig,
+    /// Index of the link in `RobotDescriptor::links`.
+    pub link: usize,
+    /// The sensor frame in the link frame.
+    pub offset: Transform,
+    // unit direction of every ray in the sensor frame, row after row
+    directions: Vec<glm::Vec3>,
+    width: usize,
+}
+
+impl Lidar {
+    /// Attaches a lidar at `offset` from the named link of `robot`.
+    pub fn new(
+        robot: &RobotDescriptor,
+        link_name: &str,
+        offset: Transform,
+        config: LidarConfig,
+    ) -> anyhow::Result<Self> {
+        let link = robot
+            .link_index(link_name)
+            .with_context(|| format!("no known link with name \"{}\"", link_name))?;
+        let horizontal = scan_angles(&config.horizontal);
+        let directions = scan_angles(&config.vertical)
+            .into_iter()
+            .flat_map(|v| {
+                horizontal
+                    .iter()
+                    .map(move |h| glm::vec3(v.cos() * h.cos(), v.cos() * h.sin(), v.sin()))
+            })
+            .collect();
+        Ok(Self {
+            config,
+            link,
+            offset,
+            directions,
+            width: horizontal.len(),
+        })
+    }
+    /// The lidar declared by a `<sensor>` with a `<ray>` in the URDF of
+    /// `robot`. URDF gives no range, so it sees everything in front of it
+    /// until `config.max_range` is set.
+    pub fn from_urdf(robot: &RobotDescriptor, sensor_name: &str) -> anyhow::Result<Self> {
+        let sensor = robot
+            .sensor(sensor_name)
+            .with_context(|| format!("no known sensor with name \"{}\"", sensor_name))?;
+        let Some(SensorKind::Ray {
+            horizontal,
+            vertical,
+        }) = sensor.kind
+        else {
+            anyhow::bail!("sensor \"{}\" is not a ray sensor", sensor_name);
+        };
+        let config = LidarConfig {
+            horizontal,
+            vertical,
+            min_range: 0.0,
+            max_range: f32::INFINITY,
+            noise_stddev: 0.0,
+            seed: None,
+        };
+        let link_name = &robot.links[sensor.link].link_name;
+        Self::new(robot, link_name, sensor.origin.into(), config)
+    }
+    /// Number of rays in a sweep.
+    pub fn ray_count(&self) -> usize {
+        self.directions.len()
+    }
+    /// World transform of the sensor frame, with `robot` posed.
+    pub fn pose(&self, robot: &RobotDescriptor) -> Transform {
+        robot.links[self.link].transform * self.offset
+    }
+    /// Sweeps `scene` from where the lidar is on `robot`.
+    pub fn scan(&self, robot: &RobotDescriptor, scene: &RaycastScene) -> LidarScan {
+        self.scan_at(&robot.links[self.link].transform, scene)
+    }
+    /// Sweeps `scene` with the lidar's link at `link_transform`, e.g. as
+    /// simulated by an `ArticulatedBody`. Rays are cast in parallel.
+    pub fn scan_at(&self, link_transform: &Transform, scene: &RaycastScene) -> LidarScan {
+        let pose = *link_transform * self.offset;
+        let (origin, rotation) = (pose.translation(), pose.rotation());
+        let LidarConfig {
+            min_range,
+            max_range,
+            noise_stddev,
+            seed,
+            ..
+        } = self.config;
+        let returns: Vec<Option<(f32, glm::Vec3)>> = self
+            .directions
+            .par_iter()
+            .enumerate()
+            .map_init(rand::thread_rng, |thread_rng, (i, d)| {
+                // starting at the minimum range also skips the lidar's own
+                // housing
+                let dir = rotation * d;
+                let hit = scene.raycast(origin + dir * min_range, dir, max_range - min_range)?;
+                let noise = match seed {
+                    // spread the ray index over the seed's bits
+                    Some(seed) => {
+                        let ray_seed = seed ^ (i as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+                        gaussian(&mut StdRng::seed_from_u64(ray_seed))
+                    }
+                    None => gaussian(thread_rng),
+                };
+                let range = hit.distance + min_range + noise_stddev * noise;
+                let range = range.clamp(min_range, max_range);
+                let p = self.offset.tmatrix * (d * range).push(1.0);
+                Some((range, p.xyz()))
+            })
+            .collect();
+        LidarScan {
+            ranges: returns
+                .iter()
+                .map(|r| r.map_or(f32::INFINITY, |(range, _)| range))
+                .collect(),
+            points: returns.into_iter().flatten().map(|(_, p)| p).collect(),
+            width: self.width,
+        }
+    }
+}
@@ -1,7 +1,8 @@
 use crate::bindings::*;
 use crate::graphics::Vertex;
 use crate::texture;
-use crate::wgpu_program::WGPUGraphics;
+use crate::graphics::GraphicsContext;
+use crate::wgpu_program::WGPUState;
 use anyhow::*;
 // use wgpu::hal::vulkan::ShaderModule;
 use itertools::Itertools;
@@ -17,7 +18,7 @@ pub trait CompileShaders {
     ) -> wgpu::ShaderModule;
 }
 
-impl CompileShaders for WGPUGraphics<'_> {
+impl<W> CompileShaders for GraphicsContext<WGPUState<'_>, W, wgpu::Buffer> {
     fn compile_wgsl(&mut self, name: &str, source: &str) -> wgpu::ShaderModule {
         let desc = wgpu::ShaderModuleDescriptor {
                 label: Some(name),
@@ -50,7 +51,7 @@ pub trait CreatePipeline {
     //Produces a ComputePipeline from shader source string
     fn create_compute_pipeline(&mut self, shader_source: &str, bind_group_layouts: &[&wgpu::BindGroupLayout]) -> Result<wgpu::ComputePipeline>;
 }
-impl CreatePipeline for WGPUGraphics<'_> {
+impl<W> CreatePipeline for GraphicsContext<WGPUState<'_>, W, wgpu::Buffer> {
     fn create_compute_pipeline(&mut self, shader_source: &str, bind_group_layouts: &[&wgpu::BindGroupLayout]) -> Result<wgpu::ComputePipeline> {
         let shader_module = self.compile_wgsl("compute shader", &shader_source);
         let pipeline_layout_desc = wgpu::PipelineLayoutDescriptor {
@@ -0,0 +1,859 @@
+use crate::constraint::{JointConstraint, JointKind, MotorMode};
+use crate::narrow_phase::ContactManifold;
+use crate::physics::{BodyHandle, BodyType, PhysicsWorld};
+use nalgebra::{DMatrix, DVector, Dyn, UnitQuaternion, LU};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::f32::consts::{PI, TAU};
+
+/// How the solver removes penetration.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PositionCorrection {
+    /// Feeds the penetration back into the contact velocities. Simple, but
+    /// the extra velocity shows up as bodies popping apart.
+    Baumgarte,
+    /// Pushes the bodies apart with separate pseudo velocities that move
+    /// them but are forgotten after the step, so no energy is added.
+    #[default]
+    SplitImpulse,
+}
+
+/// Sequential impulse solver for joints and for contacts with Coulomb
+/// friction and restitution, warm started from the impulses of the previous
+/// step. Joints linked through bodies that can move, such as the links of a
+/// robot, are solved together rather than one after another.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ContactSolver {
+    /// Passes over all joints and contacts solving the velocities.
+    pub velocity_iterations: usize,
+    /// Passes over all joints and contacts solving the penetration and the
+    /// joint drift, used by `PositionCorrection::SplitImpulse`.
+    pub position_iterations: usize,
+    pub correction: PositionCorrection,
+    /// Fraction of the penetration and joint drift removed per step.
+    pub bias_factor: f32,
+    /// Penetration allowed without correction, which keeps resting contacts
+    /// from jittering.
+    pub slop: f32,
+    /// Closing speed below which contacts do not bounce.
+    pub restitution_threshold: f32,
+    /// Start from the impulses found for the same points last step.
+    pub warm_starting: bool,
+}
+
+impl Default for ContactSolver {
+    fn default() -> Self {
+        Self {
+            velocity_iterations: 10,
+            position_iterations: 4,
+            correction: PositionCorrection::default(),
+            bias_factor: 0.2,
+            slop: 0.005,
+            restitution_threshold: 0.5,
+            warm_starting: true,
+        }
+    }
+}
+
+// velocities of a body as the solver changes them
+#[derive(Debug, Copy, Clone)]
+struct SolverBody {
+    posn: glm::Vec3,
+    vel: glm::Vec3,
+    omega: glm::Vec3,
+    // part of the velocity change from the applied forces that moves the
+    // body this step
+    forced_vel: glm::Vec3,
+    forced_omega: glm::Vec3,
+    // split impulse velocities, turned into a displacement after solving
+    push_vel: glm::Vec3,
+    push_omega: glm::Vec3,
+    inverse_mass: f32,
+    inverse_inertia: glm::Mat3,
+}
+
+impl SolverBody {
+    fn apply(&mut self, impulse: &glm::Vec3, r: &glm::Vec3) {
+        self.vel += impulse * self.inverse_mass;
+        self.omega += self.inverse_inertia * r.cross(impulse);
+    }
+    fn push(&mut self, impulse: &glm::Vec3, r: &glm::Vec3) {
+        self.push_vel += impulse * self.inverse_mass;
+        self.push_omega += self.inverse_inertia * r.cross(impulse);
+    }
+    fn apply_row(&mut self, linear: &glm::Vec3, angular: &glm::Vec3, impulse: f32) {
+        self.vel += linear * (impulse * self.inverse_mass);
+        self.omega += self.inverse_inertia * angular * impulse;
+    }
+    fn push_row(&mut self, linear: &glm::Vec3, angular: &glm::Vec3, impulse: f32) {
+        self.push_vel += linear * (impulse * self.inverse_mass);
+        self.push_omega += self.inverse_inertia * angular * impulse;
+    }
+}
+
+// one contact point of a manifold, ready to solve
+#[derive(Debug, Copy, Clone)]
+struct Constraint {
+    a: usize,
+    b: usize,
+    ra: glm::Vec3,
+    rb: glm::Vec3,
+    normal: glm::Vec3,
+    tangents: [glm::Vec3; 2],
+    normal_mass: f32,
+    tangent_mass: [f32; 2],
+    friction: f32,
+    // normal velocity the contact aims for: restitution, plus the
+    // penetration with Baumgarte correction
+    velocity_bias: f32,
+    // pseudo velocity removing the penetration with split impulses
+    push_bias: f32,
+    normal_impulse: f32,
+    tangent_impulse: [f32; 2],
+    push_impulse: f32,
+}
+
+// the rows of one joint: each drives the relative velocity
+// `linear . (vb - va) + angular_b . wb - angular_a . wa` to its bias. Six
+// two sided rows (three linear, three angular, unused ones left at zero),
+// the one sided limit row and the bounded motor row, solved along with the
+// other joints of their `JointIsland`
+#[derive(Debug, Copy, Clone)]
+struct JointBlock {
+    a: usize,
+    b: usize,
+    joint: usize,
+    // [linear, angular_a, angular_b] of each row, the limit and the motor
+    // last
+    jacobians: [[glm::Vec3; 3]; 8],
+    velocity_bias: [f32; 8],
+    push_bias: [f32; 8],
+    impulse: [f32; 8],
+    push_impulse: [f32; 8],
+    // which way the active limit pushes, when there is one
+    limit_side: Option<f32>,
+    // spring of position control, see `ContactSolver::motor_row`
+    softness: f32,
+    max_motor_impulse: f32,
+}
+
+fn row_velocity(
+    j: &[glm::Vec3; 3],
+    a: (&glm::Vec3, &glm::Vec3),
+    b: (&glm::Vec3, &glm::Vec3),
+) -> f32 {
+    j[0].dot(&(b.0 - a.0)) + j[2].dot(b.1) - j[1].dot(a.1)
+}
+
+impl JointBlock {
+    fn velocity(&self, row: usize, a: &SolverBody, b: &SolverBody, push: bool) -> f32 {
+        let j = &self.jacobians[row];
+        if push {
+            row_velocity(
+                j,
+                (&a.push_vel, &a.push_omega),
+                (&b.push_vel, &b.push_omega),
+            )
+        } else {
+            row_velocity(j, (&a.vel, &a.omega), (&b.vel, &b.omega))
+        }
+    }
+    fn apply(&self, bodies: &mut [SolverBody], row: usize, impulse: f32, push: bool) {
+        let [linear, angular_a, angular_b] = &self.jacobians[row];
+        if push {
+            bodies[self.a].push_row(linear, angular_a, -impulse);
+            bodies[self.b].push_row(linear, angular_b, impulse);
+        } else {
+            bodies[self.a].apply_row(linear, angular_a, -impulse);
+            bodies[self.b].apply_row(linear, angular_b, impulse);
+        }
+    }
+    fn used(&self, row: usize) -> bool {
+        self.jacobians[row] != [glm::Vec3::zeros(); 3]
+    }
+    // the part of a row acting on `body`: the linear and angular directions
+    // its impulse is applied along
+    fn on(&self, row: usize, body: usize) -> Option<(glm::Vec3, glm::Vec3)> {
+        let [linear, angular_a, angular_b] = self.jacobians[row];
+        if body == self.b {
+            Some((linear, angular_b))
+        } else if body == self.a {
+            Some((-linear, -angular_a))
+        } else {
+            None
+        }
+    }
+}
+
+// joints linked by bodies that can move, their rows solved as one system so
+// that an impulse reaches the whole chain in a single pass. One joint after
+// another, a motor on a light link such as a hip only ever sees that link,
+// and the limb below never settles. A limit or motor row pushed past its
+// bound is held there and the others solved again, and let go once the
+// others no longer push it there
+#[derive(Debug, Clone)]
+struct JointIsland {
+    blocks: Vec<JointBlock>,
+    // effective mass J M^-1 J^T between all rows, eight per block, with the
+    // identity for unused rows and softened along the motors
+    mass: DMatrix<f32>,
+    // total impulse of the rows held at a bound by the last velocity and
+    // push passes, where the next pass starts from
+    held: [Vec<Option<f32>>; 2],
+    // factors of `mass` over the free rows, by which rows are fixed
+    factors: Vec<(Vec<bool>, LU<f32, Dyn, Dyn>)>,
+}
+
+impl JointIsland {
+    fn new(blocks: Vec<JointBlock>, bodies: &[SolverBody]) -> Self {
+        let n = 8 * blocks.len();
+        let mut mass = DMatrix::zeros(n, n);
+        for (p, bp) in blocks.iter().enumerate() {
+            for (q, bq) in blocks.iter().enumerate() {
+                for body in [bp.a, bp.b] {
+                    let s = &bodies[body];
+                    for i in (0..8).filter(|&i| bp.used(i)) {
+                        let (li, ai) = bp.on(i, body).unwrap();
+                        for j in (0..8).filter(|&j| bq.used(j)) {
+                            if let Some((lj, aj)) = bq.on(j, body) {
+                                mass[(8 * p + i, 8 * q + j)] += s.inverse_mass * li.dot(&lj)
+                                    + ai.dot(&(s.inverse_inertia * aj));
+                            }
+                        }
+                    }
+                }
+            }
+            for i in 0..8 {
+                if !bp.used(i) {
+                    mass[(8 * p + i, 8 * p + i)] = 1.0;
+                }
+            }
+            mass[(8 * p + 7, 8 * p + 7)] += bp.softness;
+        }
+        Self {
+            blocks,
+            mass,
+            held: [vec![None; n], vec![None; n]],
+            factors: Vec::new(),
+        }
+    }
+    // whether the two sided rows can all hold at once; in a closed loop
+    // some of them are the same constraint twice
+    fn solvable(&self) -> bool {
+        let rigid: Vec<usize> = (0..self.mass.nrows()).filter(|i| i % 8 < 6).collect();
+        self.mass
+            .select_rows(&rigid)
+            .select_columns(&rigid)
+            .lu()
+            .is_invertible()
+    }
+    // the change of row `i` when it is not solved for: unused rows, the
+    // motors while pushing, rows held at a bound, and a rigid motor while
+    // the limit along the same axis is free, as the two would be one row
+    fn fixed(
+        &self,
+        i: usize,
+        held: &[Option<f32>],
+        impulse: &DVector<f32>,
+        push: bool,
+    ) -> Option<f32> {
+        let (block, row) = (&self.blocks[i / 8], i % 8);
+        if !block.used(row) || (push && row == 7) {
+            return Some(0.0);
+        }
+        if let Some(at) = held[i] {
+            return Some(at - impulse[i]);
+        }
+        let limit = i - row + 6;
+        if row == 7 && block.softness == 0.0 && block.used(6) && held[limit].is_none() {
+            return Some(0.0);
+        }
+        None
+    }
+    // the change of every row, the free ones solved for with the others
+    // fixed, reusing the factors while the same rows stay fixed
+    fn solve_free(
+        &mut self,
+        fixed: &[Option<f32>],
+        residual: &DVector<f32>,
+    ) -> Option<DVector<f32>> {
+        let mut delta = DVector::from_fn(fixed.len(), |i, _| fixed[i].unwrap_or(0.0));
+        let free: Vec<usize> = (0..fixed.len()).filter(|&i| fixed[i].is_none()).collect();
+        if free.is_empty() {
+            return Some(delta);
+        }
+        let rest = residual - &self.mass * &delta;
+        let pattern: Vec<bool> = fixed.iter().map(Option::is_some).collect();
+        let k = match self.factors.iter().position(|(p, _)| *p == pattern) {
+            Some(k) => k,
+            None => {
+                let lu = self.mass.select_rows(&free).select_columns(&free).lu();
+                self.factors.push((pattern, lu));
+                self.factors.len() - 1
+            }
+        };
+        let solved = self.factors[k]
+            .1
+            .solve(&DVector::from_fn(free.len(), |k, _| rest[free[k]]))?;
+        for (k, &i) in free.iter().enumerate() {
+            delta[i] = solved[k];
+        }
+        Some(delta)
+    }
+    // one pass over the rows, on the velocities or, with `push`, on the
+    // split impulse velocities
+    fn solve(&mut self, bodies: &mut [SolverBody], push: bool) {
+        let n = self.mass.nrows();
+        let (mut impulse, mut residual) = (DVector::zeros(n), DVector::zeros(n));
+        for (p, block) in self.blocks.iter().enumerate() {
+            let (ba, bb) = (&bodies[block.a], &bodies[block.b]);
+            let (bias, current) = if push {
+                (&block.push_bias, &block.push_impulse)
+            } else {
+                (&block.velocity_bias, &block.impulse)
+            };
+            for row in (0..8).filter(|&row| block.used(row)) {
+                impulse[8 * p + row] = current[row];
+                residual[8 * p + row] = bias[row] - block.velocity(row, ba, bb, push);
+            }
+            if !push {
+                residual[8 * p + 7] -= block.softness * block.impulse[7];
+            }
+        }
+        let pass = usize::from(push);
+        let mut held = std::mem::take(&mut self.held[pass]);
+        let mut delta = DVector::zeros(n);
+        // rows can keep trading places at their bounds; give up after as
+        // many tries as there are rows
+        for _ in 0..n {
+            let fixed: Vec<Option<f32>> = (0..n)
+                .map(|i| self.fixed(i, &held, &impulse, push))
+                .collect();
+            match self.solve_free(&fixed, &residual) {
+                Some(solved) => delta = solved,
+                None => break,
+            }
+            // what each row still asks for with the others solved
+            let rest = &residual - &self.mass * &delta;
+            let mut changed = false;
+            for i in 0..n {
+                let (block, row) = (&self.blocks[i / 8], i % 8);
+                if row < 6 || !block.used(row) || (push && row == 7) {
+                    continue;
+                }
+                match held[i] {
+                    Some(at) => {
+                        let pushed = match row {
+                            6 => rest[i] <= 0.0,
+                            _ if at > 0.0 => rest[i] >= 0.0,
+                            _ if at < 0.0 => rest[i] <= 0.0,
+                            _ => true,
+                        };
+                        if !pushed {
+                            held[i] = None;
+                            changed = true;
+                        }
+                    }
+                    None if fixed[i].is_some() => {}
+                    None => {
+                        let total = impulse[i] + delta[i];
+                        let max = block.max_motor_impulse;
+                        let bound = match row {
+                            6 => (total < 0.0).then_some(0.0),
+                            _ => (total.abs() > max).then(|| total.clamp(-max, max)),
+                        };
+                        if bound.is_some() {
+                            held[i] = bound;
+                            changed = true;
+                        }
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        for (p, block) in self.blocks.iter_mut().enumerate() {
+            for row in 0..8 {
+                let d = delta[8 * p + row];
+                if push {
+                    block.push_impulse[row] += d;
+                } else {
+                    block.impulse[row] += d;
+                }
+                block.apply(bodies, row, d, push);
+            }
+        }
+        self.held[pass] = held;
+    }
+}
+
+// groups the blocks joined through bodies that can move into islands; one
+// whose rows cannot all hold at once is solved a joint at a time instead
+fn joint_islands(blocks: Vec<JointBlock>, bodies: &[SolverBody]) -> Vec<JointIsland> {
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    let mut parent: Vec<usize> = (0..blocks.len()).collect();
+    // the first block reaching each body
+    let mut first: HashMap<usize, usize> = HashMap::new();
+    for (k, block) in blocks.iter().enumerate() {
+        for body in [block.a, block.b] {
+            if bodies[body].inverse_mass == 0.0 {
+                continue;
+            }
+            match first.entry(body) {
+                Entry::Vacant(e) => {
+                    e.insert(k);
+                }
+                Entry::Occupied(e) => {
+                    let (r, s) = (root(&mut parent, k), root(&mut parent, *e.get()));
+                    parent[r] = s;
+                }
+            }
+        }
+    }
+    let mut groups: Vec<Vec<JointBlock>> = Vec::new();
+    let mut group_of: HashMap<usize, usize> = HashMap::new();
+    for (k, block) in blocks.into_iter().enumerate() {
+        let r = root(&mut parent, k);
+        let g = *group_of.entry(r).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[g].push(block);
+    }
+    let mut islands = Vec::new();
+    for group in groups {
+        let island = JointIsland::new(group, bodies);
+        if island.blocks.len() == 1 || island.solvable() {
+            islands.push(island);
+        } else {
+            for block in island.blocks {
+                islands.push(JointIsland::new(vec![block], bodies));
+            }
+        }
+    }
+    islands
+}
+
+// an orthonormal basis of the plane perpendicular to `n`, always the same
+// for the same normal so tangent impulses can be warm started
+fn tangent_basis(n: &glm::Vec3) -> [glm::Vec3; 2] {
+    let helper = if n.x.abs() < 0.57 {
+        glm::vec3(1.0, 0.0, 0.0)
+    } else {
+        glm::vec3(0.0, 1.0, 0.0)
+    };
+    let t1 = n.cross(&helper).normalize();
+    [t1, n.cross(&t1)]
+}
+
+fn relative_velocity(a: &SolverBody, b: &SolverBody, c: &Constraint) -> glm::Vec3 {
+    (b.vel + b.omega.cross(&c.rb)) - (a.vel + a.omega.cross(&c.ra))
+}
+
+impl ContactSolver {
+    /// Solves the contacts and joints of `world` over a step of `dt`
+    /// seconds, before the bodies are integrated, storing the impulses found
+    /// in `manifolds` and `joints` for warm starting. The constraints see
+    /// the velocities the world's integrator will move the bodies with under
+    /// the applied forces, but only the constraint impulses are written back.
+    pub fn solve(
+        &self,
+        world: &mut PhysicsWorld,
+        manifolds: &mut [ContactManifold],
+        joints: &mut [JointConstraint],
+        dt: f32,
+    ) {
+        let mut indices: HashMap<BodyHandle, usize> = HashMap::new();
+        let mut handles = Vec::new();
+        let mut bodies: Vec<SolverBody> = Vec::new();
+        let joint_bodies = joints.iter().flat_map(|j| [j.a, j.b]);
+        for handle in manifolds
+            .iter()
+            .flat_map(|m| [m.a, m.b])
+            .chain(joint_bodies)
+        {
+            if indices.contains_key(&handle) {
+                continue;
+            }
+            let body = world.body(handle).unwrap();
+            let (force, torque) = body.applied();
+            let h = dt * world.integrator.position_weight();
+            let forced_vel = force * body.inverse_mass() * h;
+            let forced_omega = body.inverse_inertia_world() * torque * h;
+            indices.insert(handle, bodies.len());
+            handles.push(handle);
+            bodies.push(SolverBody {
+                posn: body.dynamics.posn,
+                vel: body.dynamics.vel + forced_vel,
+                omega: body.dynamics.omega + forced_omega,
+                forced_vel,
+                forced_omega,
+                push_vel: glm::Vec3::zeros(),
+                push_omega: glm::Vec3::zeros(),
+                inverse_mass: body.inverse_mass(),
+                inverse_inertia: body.inverse_inertia_world(),
+            });
+        }
+
+        let mut constraints = Vec::new();
+        for m in manifolds.iter() {
+            let (a, b) = (indices[&m.a], indices[&m.b]);
+            let (ma, mb) = (
+                world.body(m.a).unwrap().material,
+                world.body(m.b).unwrap().material,
+            );
+            let friction = (ma.friction * mb.friction).sqrt();
+            let restitution = ma.restitution.max(mb.restitution);
+            let normal = m.normal;
+            let tangents = tangent_basis(&normal);
+            for p in &m.points {
+                let point = p.contact.point();
+                let (ra, rb) = (point - bodies[a].posn, point - bodies[b].posn);
+                let mass = |dir: &glm::Vec3| {
+                    let (ba, bb) = (&bodies[a], &bodies[b]);
+                    let k = ba.inverse_mass
+                        + bb.inverse_mass
+                        + (ba.inverse_inertia * ra.cross(dir)).cross(&ra).dot(dir)
+                        + (bb.inverse_inertia * rb.cross(dir)).cross(&rb).dot(dir);
+                    if k > 0.0 {
+                        1.0 / k
+                    } else {
+                        0.0
+                    }
+                };
+                let mut c = Constraint {
+                    a,
+                    b,
+                    ra,
+                    rb,
+                    normal,
+                    tangents,
+                    normal_mass: mass(&normal),
+                    tangent_mass: [mass(&tangents[0]), mass(&tangents[1])],
+                    friction,
+                    velocity_bias: 0.0,
+                    push_bias: 0.0,
+                    normal_impulse: 0.0,
+                    tangent_impulse: [0.0; 2],
+                    push_impulse: 0.0,
+                };
+                let closing = relative_velocity(&bodies[a], &bodies[b], &c).dot(&normal);
+                if closing < -self.restitution_threshold {
+                    c.velocity_bias = -restitution * closing;
+                }
+                let penetration = self.bias_factor / dt * (p.contact.depth - self.slop).max(0.0);
+                match self.correction {
+                    PositionCorrection::Baumgarte => {
+                        c.velocity_bias = c.velocity_bias.max(penetration)
+                    }
+                    PositionCorrection::SplitImpulse => c.push_bias = penetration,
+                }
+                if self.warm_starting {
+                    c.normal_impulse = p.normal_impulse;
+                    c.tangent_impulse = p.tangent_impulse;
+                }
+                constraints.push(c);
+            }
+        }
+        let blocks: Vec<JointBlock> = (0..joints.len())
+            .filter_map(|j| self.joint_block(world, joints, j, &indices, &bodies, dt))
+            .collect();
+        let mut islands = joint_islands(blocks, &bodies);
+
+        // only once every closing velocity has been measured
+        for c in &constraints {
+            let impulse = c.normal * c.normal_impulse
+                + c.tangents[0] * c.tangent_impulse[0]
+                + c.tangents[1] * c.tangent_impulse[1];
+            bodies[c.a].apply(&-impulse, &c.ra);
+            bodies[c.b].apply(&impulse, &c.rb);
+        }
+        for block in islands.iter().flat_map(|island| &island.blocks) {
+            for (row, impulse) in block.impulse.iter().enumerate() {
+                block.apply(&mut bodies, row, *impulse, false);
+            }
+        }
+
+        for _ in 0..self.velocity_iterations {
+            // joints first, so that contacts get the last word
+            for island in &mut islands {
+                island.solve(&mut bodies, false);
+            }
+            for c in &mut constraints {
+                // friction first, limited by the normal impulse of the
+                // last pass to the cone |t| <= friction * n
+                let (ba, bb) = (bodies[c.a], bodies[c.b]);
+                let v = relative_velocity(&ba, &bb, c);
+                let old = c.tangent_impulse;
+                let mut new = [
+                    old[0] - c.tangent_mass[0] * v.dot(&c.tangents[0]),
+                    old[1] - c.tangent_mass[1] * v.dot(&c.tangents[1]),
+                ];
+                let limit = c.friction * c.normal_impulse;
+                let length = new[0].hypot(new[1]);
+                if length > limit {
+                    let scale = if length > 0.0 { limit / length } else { 0.0 };
+                    new = [new[0] * scale, new[1] * scale];
+                }
+                c.tangent_impulse = new;
+                let impulse = c.tangents[0] * (new[0] - old[0]) + c.tangents[1] * (new[1] - old[1]);
+                bodies[c.a].apply(&-impulse, &c.ra);
+                bodies[c.b].apply(&impulse, &c.rb);
+
+                let (ba, bb) = (bodies[c.a], bodies[c.b]);
+                let vn = relative_velocity(&ba, &bb, c).dot(&c.normal);
+                let old = c.normal_impulse;
+                c.normal_impulse = (old + c.normal_mass * (c.velocity_bias - vn)).max(0.0);
+                let impulse = c.normal * (c.normal_impulse - old);
+                bodies[c.a].apply(&-impulse, &c.ra);
+                bodies[c.b].apply(&impulse, &c.rb);
+            }
+        }
+
+        if self.correction == PositionCorrection::SplitImpulse {
+            for _ in 0..self.position_iterations {
+                for island in &mut islands {
+                    island.solve(&mut bodies, true);
+                }
+                for c in &mut constraints {
+                    let (ba, bb) = (&bodies[c.a], &bodies[c.b]);
+                    let vn = ((bb.push_vel + bb.push_omega.cross(&c.rb))
+                        - (ba.push_vel + ba.push_omega.cross(&c.ra)))
+                    .dot(&c.normal);
+                    let old = c.push_impulse;
+                    c.push_impulse = (old + c.normal_mass * (c.push_bias - vn)).max(0.0);
+                    let impulse = c.normal * (c.push_impulse - old);
+                    bodies[c.a].push(&-impulse, &c.ra);
+                    bodies[c.b].push(&impulse, &c.rb);
+                }
+            }
+        }
+
+        let mut solved = constraints.iter();
+        for m in manifolds.iter_mut() {
+            for (p, c) in m.points.iter_mut().zip(&mut solved) {
+                p.normal_impulse = c.normal_impulse;
+                p.tangent_impulse = c.tangent_impulse;
+            }
+        }
+        for joint in joints.iter_mut() {
+            joint.impulses = [0.0; 8];
+        }
+        for block in islands.iter().flat_map(|island| &island.blocks) {
+            let joint = &mut joints[block.joint];
+            joint.impulses = block.impulse;
+            joint.impulses[6] *= block.limit_side.unwrap_or(0.0);
+            if block.jacobians[7] != [glm::Vec3::zeros(); 3] {
+                joint.effort = block.impulse[7] / dt;
+            }
+        }
+        for (handle, s) in handles.into_iter().zip(bodies) {
+            let body = world.body_mut(handle).unwrap();
+            if body.body_type != BodyType::Dynamic {
+                continue;
+            }
+            let d = &mut body.dynamics;
+            // the integrator applies the forces itself
+            d.vel = s.vel - s.forced_vel;
+            d.omega = s.omega - s.forced_omega;
+            if s.push_vel != glm::Vec3::zeros() || s.push_omega != glm::Vec3::zeros() {
+                d.posn += s.push_vel * dt;
+                d.orientation = UnitQuaternion::from_scaled_axis(s.push_omega * dt) * d.orientation;
+                d.update_inertia();
+            }
+            body.transform = d.transform();
+        }
+    }
+
+    // rows of one joint, with the free axis taken in the frame of body `a`;
+    // `None` when neither body can move
+    #[allow(clippy::too_many_arguments)]
+    fn joint_block(
+        &self,
+        world: &PhysicsWorld,
+        joints: &[JointConstraint],
+        index: usize,
+        indices: &HashMap<BodyHandle, usize>,
+        bodies: &[SolverBody],
+        dt: f32,
+    ) -> Option<JointBlock> {
+        let joint = &joints[index];
+        let (fa, fb) = joint.world_frames(world)?;
+        let (a, b) = (indices[&joint.a], indices[&joint.b]);
+        let (ba, bb) = (&bodies[a], &bodies[b]);
+        if ba.inverse_mass == 0.0 && bb.inverse_mass == 0.0 {
+            return None;
+        }
+        let (pa, pb) = (fa.translation(), fb.translation());
+        let (ra, rb) = (pa - ba.posn, pb - bb.posn);
+        let zero = glm::Vec3::zeros();
+        let world_axis = |axis: &glm::Vec3| fa.rotation() * axis.normalize();
+        let mut block = JointBlock {
+            a,
+            b,
+            joint: index,
+            jacobians: [[zero; 3]; 8],
+            velocity_bias: [0.0; 8],
+            push_bias: [0.0; 8],
+            impulse: [0.0; 8],
+            push_impulse: [0.0; 8],
+            limit_side: None,
+            softness: 0.0,
+            max_motor_impulse: 0.0,
+        };
+        // each row with how far the joint is off along it
+        let mut errors = [0.0; 7];
+        // row along the free axis `n` of a revolute or prismatic joint
+        let free_row = |n: glm::Vec3| match joint.kind {
+            JointKind::Prismatic { .. } => [n, ra.cross(&n), rb.cross(&n)],
+            _ => [zero, n, n],
+        };
+
+        let linear: Vec<glm::Vec3> = match joint.kind {
+            JointKind::Prismatic { axis } => tangent_basis(&world_axis(&axis)).to_vec(),
+            _ => vec![
+                glm::vec3(1.0, 0.0, 0.0),
+                glm::vec3(0.0, 1.0, 0.0),
+                glm::vec3(0.0, 0.0, 1.0),
+            ],
+        };
+        for (row, n) in linear.iter().enumerate() {
+            block.jacobians[row] = [*n, ra.cross(n), rb.cross(n)];
+            errors[row] = (pb - pa).dot(n);
+        }
+        match joint.kind {
+            JointKind::Revolute { axis } => {
+                let (ua, ub) = (world_axis(&axis), fb.rotation() * axis.normalize());
+                let tilt = ua.cross(&ub);
+                for (i, t) in tangent_basis(&ua).iter().enumerate() {
+                    block.jacobians[3 + i] = [zero, *t, *t];
+                    errors[3 + i] = tilt.dot(t);
+                }
+            }
+            JointKind::Fixed | JointKind::Prismatic { .. } => {
+                let qa = UnitQuaternion::from_matrix(&fa.rotation());
+                let qb = UnitQuaternion::from_matrix(&fb.rotation());
+                let tilt = (qb * qa.inverse()).scaled_axis();
+                for i in 0..3 {
+                    let mut n = zero;
+                    n[i] = 1.0;
+                    block.jacobians[3 + i] = [zero, n, n];
+                    errors[3 + i] = tilt[i];
+                }
+            }
+            JointKind::Spherical => {}
+        }
+        if let (Some((lower, upper)), Some(q)) = (joint.limits, joint.position(world)) {
+            // the nearer limit, as a distance that must stay positive
+            // moving along `side` times the axis
+            let (distance, side) = if q - lower < upper - q {
+                (q - lower, 1.0)
+            } else {
+                (upper - q, -1.0)
+            };
+            let axis = match joint.kind {
+                JointKind::Revolute { axis } | JointKind::Prismatic { axis } => axis,
+                JointKind::Fixed | JointKind::Spherical => unreachable!(),
+            };
+            block.jacobians[6] = free_row(world_axis(&axis) * side);
+            errors[6] = distance;
+            block.limit_side = Some(side);
+        }
+        let motor = joint
+            .world_axis(world)
+            .zip(self.motor_row(world, joints, joint, dt));
+        if let Some((n, (target, softness, max_impulse))) = motor {
+            block.jacobians[7] = free_row(n);
+            block.velocity_bias[7] = target;
+            block.softness = softness;
+            block.max_motor_impulse = max_impulse;
+        }
+
+        for (row, error) in errors.iter().enumerate() {
+            let correction = -self.bias_factor * error / dt;
+            match self.correction {
+                PositionCorrection::Baumgarte => block.velocity_bias[row] = correction,
+                PositionCorrection::SplitImpulse => block.push_bias[row] = correction,
+            }
+        }
+        if errors[6] > 0.0 {
+            // not at the limit yet: only stop it being passed this step
+            block.velocity_bias[6] = -errors[6] / dt;
+            block.push_bias[6] = 0.0;
+        }
+        if self.warm_starting {
+            block.impulse = joint.impulses;
+            block.impulse[6] = (block.impulse[6] * block.limit_side.unwrap_or(0.0)).max(0.0);
+            block.impulse[7] = if block.jacobians[7] == [zero; 3] {
+                0.0
+            } else {
+                block.impulse[7].clamp(-block.max_motor_impulse, block.max_motor_impulse)
+            };
+        }
+        Some(block)
+    }
+
+    // target velocity, softness and largest impulse of the motor row of a
+    // joint driven by velocity or position. Position control is the PD law
+    // `kp (q* - q) + kd (v* - v)` taken implicitly over the step, as a soft
+    // row: with `softness = 1 / (dt (kd + dt kp))` and
+    // `beta = dt kp / (kd + dt kp)` it aims for `v* - beta / dt (q - q*)`,
+    // reaching it only as far as the gains allow. A mimic joint is a rigid
+    // row, correcting its error like the other joint rows, with the joint it
+    // follows looked up in `joints` as they are out of `world` while solved.
+    // Past the velocity limit the row holds the limit instead.
+    fn motor_row(
+        &self,
+        world: &PhysicsWorld,
+        joints: &[JointConstraint],
+        joint: &JointConstraint,
+        dt: f32,
+    ) -> Option<(f32, f32, f32)> {
+        let motor = joint.motor?;
+        let error = |position: f32| {
+            let error = joint.position(world)? - position;
+            Some(match joint.kind {
+                // the short way round
+                JointKind::Revolute { .. } => (error + PI).rem_euclid(TAU) - PI,
+                _ => error,
+            })
+        };
+        let (target, softness) = match motor.mode {
+            MotorMode::Effort(_) => return None,
+            MotorMode::Velocity(velocity) => (velocity, 0.0),
+            MotorMode::Position { position, velocity } => {
+                let gain = motor.damping + dt * motor.stiffness;
+                if gain <= 0.0 {
+                    return None;
+                }
+                let beta = dt * motor.stiffness / gain;
+                (velocity - beta / dt * error(position)?, 1.0 / (dt * gain))
+            }
+            MotorMode::Mimic {
+                joint: source,
+                multiplier,
+                offset,
+            } => {
+                let k = joints.binary_search_by_key(&source, |j| j.handle).ok()?;
+                let source = &joints[k];
+                let position = multiplier * source.position(world)? + offset;
+                let velocity = multiplier * source.velocity(world)?;
+                (velocity - self.bias_factor / dt * error(position)?, 0.0)
+            }
+        };
+        let max_impulse = motor.max_effort * dt;
+        if target.abs() > motor.max_velocity {
+            // saturated: hold the top speed as firmly as the effort allows
+            let target = target.clamp(-motor.max_velocity, motor.max_velocity);
+            return Some((target, 0.0, max_impulse));
+        }
+        Some((target, softness, max_impulse))
+    }
+}
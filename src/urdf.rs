@@ -1,17 +1,22 @@
-use crate::bindings::create_uniform_bind_group;
-use crate::geometry::{BoxMesh, CylinderMesh, Polyhedron, SphereMesh, Transform, TriMesh};
-use crate::texture::Texture;
-use crate::wgpu_program::{MeshBuffer, WGPUGraphics};
+use crate::geometry::{
+    BoxMesh, CapsuleMesh, CylinderMesh, Polyhedron, SphereMesh, Transform, TriMesh,
+};
+use crate::joint_state::{JointError, LimitPolicy, LimitViolation};
+use crate::kinematics::{KinematicTree, TreeError};
+use crate::resource::ResourceResolver;
 use glm;
-use itertools::Itertools;
+use std::fmt;
+use std::path::Path;
 use std::str::FromStr;
+use xml::attribute::OwnedAttribute;
+use xml::common::Position;
 use xml::reader::{XmlEvent, XmlEvent::*};
 use xml::EventReader;
 
 #[derive(Default, Debug, Copy, Clone)]
 pub struct Origin {
-    xyz: glm::Vec3,
-    rpy: Option<glm::Vec3>,
+    pub xyz: glm::Vec3,
+    pub rpy: Option<glm::Vec3>,
 }
 
 impl From<Origin> for Transform {
@@ -41,22 +46,117 @@ pub struct VisualBody {
     pub material: Option<String>,
 }
 
+/// Primitive a `<geometry>` element describes, kept next to its mesh so that
+/// collision shapes can use the exact surface.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Geometry {
+    Box {
+        size: glm::Vec3,
+    },
+    /// Along z, centred on the origin.
+    Cylinder {
+        radius: f32,
+        length: f32,
+    },
+    Sphere {
+        radius: f32,
+    },
+    /// Cylinder of `length` along z capped by hemispheres, as used by drake.
+    Capsule {
+        radius: f32,
+        length: f32,
+    },
+}
+
 #[derive(Default, Debug, Clone)]
 pub struct CollisionBody {
     pub origin: Origin,
     pub transform: Transform,
     pub geometry: Polyhedron,
+    /// `None` when the geometry is a mesh.
+    pub shape: Option<Geometry>,
 }
 
 #[derive(Default, Debug, Clone)]
 pub struct Link {
     pub link_name: String,
+    // world transform of the link frame, visual/inertial/collision
+    // transforms are offset from it by their origin
+    pub transform: Transform,
     pub visual: VisualBody,
     pub inertial: InertialBody,
-    pub collision: CollisionBody,
+    /// A link may be covered by several collision elements, or none.
+    pub collisions: Vec<CollisionBody>,
+}
+
+impl Link {
+    /// The first collision element, which used to be the only one kept.
+    pub fn collision(&self) -> Option<&CollisionBody> {
+        self.collisions.first()
+    }
+}
+
+/// Named frame fixed to a link, as declared by drake's `<frame>` element.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub name: String,
+    /// Index of the link in `RobotDescriptor::links`.
+    pub link: usize,
+    pub origin: Origin,
+}
+
+/// Directions swept by a ray sensor about one axis, as given by the
+/// `<horizontal>` and `<vertical>` elements of `<ray>`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RayScan {
+    pub samples: u32,
+    pub resolution: f32,
+    pub min_angle: f32,
+    pub max_angle: f32,
+}
+
+impl Default for RayScan {
+    fn default() -> Self {
+        Self {
+            samples: 1,
+            resolution: 1.0,
+            min_angle: 0.0,
+            max_angle: 0.0,
+        }
+    }
+}
+
+/// What a `<sensor>` measures.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SensorKind {
+    Camera {
+        width: u32,
+        height: u32,
+        /// Horizontal field of view in radians.
+        hfov: f32,
+        near: f32,
+        far: f32,
+    },
+    Ray {
+        horizontal: RayScan,
+        vertical: RayScan,
+    },
+}
+
+/// Sensor fixed to a link, as declared by the `<sensor>` element.
+#[derive(Debug, Clone)]
+pub struct Sensor {
+    pub name: String,
+    /// Index of the link in `RobotDescriptor::links`.
+    pub link: usize,
+    pub origin: Origin,
+    /// In Hz, when given.
+    pub update_rate: Option<f32>,
+    /// `None` for kinds of sensor that are not simulated.
+    pub kind: Option<SensorKind>,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum JointType {
     Revolute,
     Fixed,
@@ -67,16 +167,41 @@ pub enum JointType {
 
 #[derive(Default, Debug, Copy, Clone)]
 pub struct JointLimits {
-    effort: f32,
-    velocity: f32,
-    lower: f32,
-    upper: f32,
+    pub effort: f32,
+    pub velocity: f32,
+    pub lower: f32,
+    pub upper: f32,
 }
 
 #[derive(Default, Debug, Copy, Clone)]
 pub struct JointDynamics {
-    damping: f32,
-    friction: f32,
+    pub damping: f32,
+    pub friction: f32,
+}
+
+/// Makes a joint follow another: `position = multiplier * other + offset`.
+#[derive(Debug, Clone)]
+pub struct JointMimic {
+    pub joint: String,
+    pub multiplier: f32,
+    pub offset: f32,
+}
+
+/// Soft limits and gains used by a safety controller to keep the joint away
+/// from its hard limits.
+#[derive(Default, Debug, Copy, Clone)]
+pub struct SafetyController {
+    pub soft_lower_limit: f32,
+    pub soft_upper_limit: f32,
+    pub k_position: f32,
+    pub k_velocity: f32,
+}
+
+/// Reference positions at which the joint's calibration switch triggers.
+#[derive(Default, Debug, Copy, Clone)]
+pub struct JointCalibration {
+    pub rising: Option<f32>,
+    pub falling: Option<f32>,
 }
 
 #[derive(Debug, Clone)]
@@ -90,130 +215,424 @@ pub struct Joint {
     axis: Option<glm::Vec3>, // axis in joint frame
     limits: Option<JointLimits>,
     dynamics: Option<JointDynamics>,
+    mimic: Option<JointMimic>,
+    safety_controller: Option<SafetyController>,
+    calibration: Option<JointCalibration>,
+}
+
+impl Joint {
+    pub fn name(&self) -> &str {
+        &self.joint_name
+    }
+    pub fn joint_type(&self) -> JointType {
+        self.joint_type
+    }
+    /// Index of the parent link in `RobotDescriptor::links`.
+    pub fn parent(&self) -> usize {
+        self.parent
+    }
+    /// Index of the child link in `RobotDescriptor::links`.
+    pub fn child(&self) -> usize {
+        self.child
+    }
+    pub fn origin(&self) -> Origin {
+        self.origin
+    }
+    /// Axis in the joint frame, URDF defaults to x when none is given.
+    pub fn axis(&self) -> glm::Vec3 {
+        self.axis.unwrap_or(glm::vec3(1.0, 0.0, 0.0))
+    }
+    pub fn limits(&self) -> Option<&JointLimits> {
+        self.limits.as_ref()
+    }
+    pub fn dynamics(&self) -> Option<&JointDynamics> {
+        self.dynamics.as_ref()
+    }
+    /// Transform from the parent link frame to the child link frame with the
+    /// joint at position `q`.
+    pub fn transform_at(&self, q: f32) -> Transform {
+        let mut transform: Transform = self.origin.into();
+        match self.joint_type {
+            JointType::Revolute | JointType::Continuous => transform.rotate(self.axis(), q),
+            JointType::Prismatic => transform.translate(q * self.axis()),
+            JointType::Floating => { /* do nothing */ }
+            JointType::Fixed => { /* do nothing */ }
+        }
+        transform
+    }
+    /// Whether the joint has a degree of freedom of its own.
+    pub fn is_actuated(&self) -> bool {
+        self.mimic.is_none()
+            && matches!(
+                self.joint_type,
+                JointType::Revolute | JointType::Continuous | JointType::Prismatic
+            )
+    }
+    pub fn mimic(&self) -> Option<&JointMimic> {
+        self.mimic.as_ref()
+    }
+    pub fn safety_controller(&self) -> Option<&SafetyController> {
+        self.safety_controller.as_ref()
+    }
+    pub fn calibration(&self) -> Option<&JointCalibration> {
+        self.calibration.as_ref()
+    }
+}
+/// Relates actuators to the joints they drive. For a simple transmission the
+/// joint effort is the actuator effort multiplied by `mechanical_reduction`.
+#[derive(Debug, Clone)]
+pub struct Transmission {
+    pub name: Option<String>,
+    pub transmission_type: Option<String>,
+    pub joints: Vec<String>,
+    pub actuators: Vec<String>,
+    pub mechanical_reduction: f32,
+    pub hardware_interface: Option<String>,
+}
+
+impl Default for Transmission {
+    fn default() -> Self {
+        Self {
+            name: None,
+            transmission_type: None,
+            joints: Vec::new(),
+            actuators: Vec::new(),
+            mechanical_reduction: 1.0,
+            hardware_interface: None,
+        }
+    }
+}
+
+impl Transmission {
+    pub fn actuator_to_joint_effort(&self, actuator_effort: f32) -> f32 {
+        actuator_effort * self.mechanical_reduction
+    }
+    pub fn joint_to_actuator_effort(&self, joint_effort: f32) -> f32 {
+        joint_effort / self.mechanical_reduction
+    }
 }
+
 #[derive(Default, Debug, Clone)]
 pub struct RobotDescriptor {
     pub name: Option<String>,
     pub links: Vec<Link>,
     pub joints: Vec<Joint>,
+    pub transmissions: Vec<Transmission>,
+    pub frames: Vec<Frame>,
+    pub sensors: Vec<Sensor>,
+    // last position set on every joint, in joint order
+    joint_positions: Vec<f32>,
+    /// What to do when a joint is moved past its limits. Defaults to
+    /// `LimitPolicy::Allow`: violations are reported but nothing is clamped
+    /// or rejected unless this is set.
+    pub limit_policy: LimitPolicy,
+    tree: KinematicTree,
 }
 
-type ParseRobotError = Box<dyn std::error::Error>;
-
-//gets position, rotation from origin element
-fn parse_3f(s: &str) -> Result<glm::Vec3, ParseRobotError> {
-    let v: [f32; 3] = s
-        .split_whitespace()
-        .map(|ns| ns.parse::<f32>())
-        .collect::<Result<Vec<f32>, _>>()
-        .unwrap()
-        .try_into()
-        .unwrap();
-    Ok(v.into())
-}
-fn parse_4f(s: &str) -> Result<glm::Vec4, ParseRobotError> {
-    let v: [f32; 4] = s
-        .split_whitespace()
-        .map(|ns| ns.parse::<f32>())
-        .collect::<Result<Vec<f32>, _>>()
-        .unwrap()
-        .try_into()
-        .unwrap();
-    Ok(v.into())
-}
-fn parse_origin(origin_event: XmlEvent) -> Result<Origin, ParseRobotError> {
-    if let XmlEvent::StartElement { attributes, .. } = origin_event {
-        let xyz_attr = attributes
-            .iter()
-            .find(|&a| a.name.local_name == "xyz")
-            .ok_or("expected attribute xyz")
-            .unwrap();
-        let xyz = parse_3f(&xyz_attr.value).unwrap();
-        let rpy_attr = attributes.iter().find(|&a| a.name.local_name == "rpy");
-        let rpy = if let Some(attr) = rpy_attr {
-            let value = parse_3f(&attr.value).ok();
-            value
-        } else {
-            None
-        };
-        Ok(Origin { xyz, rpy })
-    } else {
-        unreachable!();
+/// Reason a URDF document was rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UrdfErrorKind {
+    /// A required attribute was not present on the element.
+    MissingAttribute(String),
+    /// A required child element was not present.
+    MissingElement(String),
+    /// An attribute could not be parsed as a number (or list of numbers).
+    InvalidNumber(String),
+    /// An attribute held a value outside of the set URDF allows.
+    InvalidValue { attribute: String, value: String },
+    /// A joint referenced a link which has not been declared.
+    UnknownLink(String),
+    /// A transmission referenced a joint which has not been declared.
+    UnknownJoint(String),
+    /// A link is the child of more than one joint.
+    MultipleParents(String),
+    /// More than one link has no parent joint.
+    MultipleRoots(Vec<String>),
+    /// A link is not connected to the rest of the robot by any joint.
+    DisconnectedLink(String),
+    /// The joints between these links form a loop.
+    KinematicCycle(Vec<String>),
+    /// The element is valid XML but not something this parser handles.
+    UnsupportedElement(String),
+    /// A mesh file could not be found or has an unknown extension.
+    InvalidMesh(String),
+    /// The underlying XML was malformed or ended early.
+    Xml(String),
+    /// The URDF file itself could not be read.
+    Io(String),
+}
+
+/// Error produced while parsing a URDF document, with the path of the element
+/// being parsed and its (1-based) line and column in the source. Errors with
+/// no position in the source, such as a file which could not be read, have
+/// `line` and `column` set to 0 and the file name as `path`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UrdfError {
+    pub path: String,
+    /// 1-based line, 0 when there is no position.
+    pub line: u64,
+    /// 1-based column, 0 when there is no position.
+    pub column: u64,
+    pub kind: UrdfErrorKind,
+}
+
+impl fmt::Display for UrdfErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UrdfErrorKind::MissingAttribute(a) => write!(f, "missing attribute \"{}\"", a),
+            UrdfErrorKind::MissingElement(e) => write!(f, "missing element <{}>", e),
+            UrdfErrorKind::InvalidNumber(v) => write!(f, "could not parse \"{}\" as a number", v),
+            UrdfErrorKind::InvalidValue { attribute, value } => {
+                write!(
+                    f,
+                    "invalid value \"{}\" for attribute \"{}\"",
+                    value, attribute
+                )
+            }
+            UrdfErrorKind::UnknownLink(l) => write!(f, "no known link with name \"{}\"", l),
+            UrdfErrorKind::UnknownJoint(j) => write!(f, "no known joint with name \"{}\"", j),
+            UrdfErrorKind::MultipleParents(l) => {
+                write!(f, "link \"{}\" is the child of more than one joint", l)
+            }
+            UrdfErrorKind::MultipleRoots(l) => {
+                write!(f, "expected a single root link, found {}", l.join(", "))
+            }
+            UrdfErrorKind::DisconnectedLink(l) => {
+                write!(f, "link \"{}\" is not connected to any joint", l)
+            }
+            UrdfErrorKind::KinematicCycle(l) => {
+                write!(f, "joints form a cycle through {}", l.join(", "))
+            }
+            UrdfErrorKind::UnsupportedElement(e) => write!(f, "unsupported element <{}>", e),
+            UrdfErrorKind::InvalidMesh(m) => write!(f, "unable to load mesh \"{}\"", m),
+            UrdfErrorKind::Xml(msg) => write!(f, "malformed xml: {}", msg),
+            UrdfErrorKind::Io(msg) => write!(f, "unable to read file: {}", msg),
+        }
     }
 }
 
-fn parse_link_geometry(xml_parser: &mut EventReader<&[u8]>) -> Result<Polyhedron, ParseRobotError> {
-    let mut shape: Option<Polyhedron> = None;
+impl fmt::Display for UrdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line > 0 {
+            write!(f, "{}:{}: ", self.line, self.column)?;
+        }
+        write!(f, "{}", self.kind)?;
+        if !self.path.is_empty() {
+            write!(f, " (at {})", self.path)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for UrdfError {}
+
+// Wraps the xml event stream, keeping track of the element path so that
+// errors can report where in the document they happened.
+struct UrdfReader<'a> {
+    events: EventReader<&'a [u8]>,
+    path: Vec<String>,
+    resolver: &'a ResourceResolver,
+    base_dir: Option<&'a Path>,
+}
+
+impl<'a> UrdfReader<'a> {
+    fn new(s: &'a str, resolver: &'a ResourceResolver, base_dir: Option<&'a Path>) -> Self {
+        Self {
+            events: EventReader::from_str(s),
+            path: Vec::new(),
+            resolver,
+            base_dir,
+        }
+    }
+    fn next(&mut self) -> Result<XmlEvent, UrdfError> {
+        let event = self
+            .events
+            .next()
+            .map_err(|e| self.error(UrdfErrorKind::Xml(e.msg().to_owned())))?;
+        match &event {
+            StartElement {
+                name, attributes, ..
+            } => {
+                // name the element after its "name" attribute when it has one,
+                // eg. robot/link[base_link]/visual
+                let segment = match find_attr(attributes, "name") {
+                    Some(n) => format!("{}[{}]", name.local_name, n),
+                    None => name.local_name.clone(),
+                };
+                self.path.push(segment);
+            }
+            EndElement { .. } => {
+                self.path.pop();
+            }
+            EndDocument => {
+                return Err(self.error(UrdfErrorKind::Xml("unexpected end of document".into())))
+            }
+            _ => {}
+        }
+        Ok(event)
+    }
+    // reads the text content of the current element, consuming its end tag
+    fn text(&mut self) -> Result<String, UrdfError> {
+        let mut text = String::new();
+        loop {
+            match self.next()? {
+                Characters(s) | CData(s) => text.push_str(&s),
+                EndElement { .. } => return Ok(text.trim().to_owned()),
+                StartElement { name, .. } => {
+                    return Err(self.error(UrdfErrorKind::UnsupportedElement(name.local_name)))
+                }
+                _ => {}
+            }
+        }
+    }
+    fn error(&self, kind: UrdfErrorKind) -> UrdfError {
+        let pos = self.events.position();
+        UrdfError {
+            path: self.path.join("/"),
+            line: pos.row + 1,
+            column: pos.column + 1,
+            kind,
+        }
+    }
+    fn attr<'b>(&self, attributes: &'b [OwnedAttribute], name: &str) -> Result<&'b str, UrdfError> {
+        find_attr(attributes, name)
+            .ok_or_else(|| self.error(UrdfErrorKind::MissingAttribute(name.to_owned())))
+    }
+    fn parse_f32(&self, s: &str) -> Result<f32, UrdfError> {
+        s.trim()
+            .parse::<f32>()
+            .map_err(|_| self.error(UrdfErrorKind::InvalidNumber(s.to_owned())))
+    }
+    fn parse_nf<const N: usize>(&self, s: &str) -> Result<[f32; N], UrdfError> {
+        s.split_whitespace()
+            .map(|ns| self.parse_f32(ns))
+            .collect::<Result<Vec<f32>, _>>()?
+            .try_into()
+            .map_err(|_| self.error(UrdfErrorKind::InvalidNumber(s.to_owned())))
+    }
+    //gets position, rotation from origin element
+    fn parse_3f(&self, s: &str) -> Result<glm::Vec3, UrdfError> {
+        Ok(self.parse_nf::<3>(s)?.into())
+    }
+    fn parse_4f(&self, s: &str) -> Result<glm::Vec4, UrdfError> {
+        Ok(self.parse_nf::<4>(s)?.into())
+    }
+    fn attr_f32(&self, attributes: &[OwnedAttribute], name: &str) -> Result<f32, UrdfError> {
+        self.parse_f32(self.attr(attributes, name)?)
+    }
+    fn attr_f32_or(
+        &self,
+        attributes: &[OwnedAttribute],
+        name: &str,
+        default: f32,
+    ) -> Result<f32, UrdfError> {
+        find_attr(attributes, name).map_or(Ok(default), |v| self.parse_f32(v))
+    }
+    fn attr_u32_or(
+        &self,
+        attributes: &[OwnedAttribute],
+        name: &str,
+        default: u32,
+    ) -> Result<u32, UrdfError> {
+        find_attr(attributes, name).map_or(Ok(default), |v| {
+            v.trim()
+                .parse::<u32>()
+                .map_err(|_| self.error(UrdfErrorKind::InvalidNumber(v.to_owned())))
+        })
+    }
+}
+
+fn find_attr<'a>(attributes: &'a [OwnedAttribute], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|a| a.name.local_name == name)
+        .map(|a| a.value.as_str())
+}
+
+fn parse_origin(
+    xml_parser: &UrdfReader,
+    attributes: &[OwnedAttribute],
+) -> Result<Origin, UrdfError> {
+    let xyz = match find_attr(attributes, "xyz") {
+        Some(v) => xml_parser.parse_3f(v)?,
+        None => glm::Vec3::zeros(),
+    };
+    let rpy = match find_attr(attributes, "rpy") {
+        Some(v) => Some(xml_parser.parse_3f(v)?),
+        None => None,
+    };
+    Ok(Origin { xyz, rpy })
+}
+
+fn load_mesh(xml_parser: &UrdfReader, uri: &str) -> Result<Polyhedron, UrdfError> {
+    let path = xml_parser
+        .resolver
+        .resolve(uri, xml_parser.base_dir)
+        .ok_or_else(|| xml_parser.error(UrdfErrorKind::InvalidMesh(uri.to_owned())))?;
+    let supported = matches!(
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase())
+            .as_deref(),
+        Some("stl") | Some("obj")
+    );
+    if !supported {
+        return Err(xml_parser.error(UrdfErrorKind::InvalidMesh(uri.to_owned())));
+    }
+    Ok(Polyhedron::from(path.to_string_lossy().into_owned()))
+}
+
+fn parse_link_geometry(
+    xml_parser: &mut UrdfReader,
+) -> Result<(Polyhedron, Option<Geometry>), UrdfError> {
+    let mut shape: Option<(Polyhedron, Option<Geometry>)> = None;
     loop {
-        let event = xml_parser.next();
-        match event.unwrap() {
+        match xml_parser.next()? {
             StartElement {
                 name, attributes, ..
             } => match name.local_name.as_str() {
                 "mesh" => {
-                    let fname = attributes
-                        .iter()
-                        .find(|&a| a.name.local_name == "filename")
-                        .ok_or("expected file name attribute")
-                        .unwrap()
-                        .value
-                        .to_owned();
-                    // if fname.starts_with("package://") {
-                    // }
-                    let mut poly = Polyhedron::from(fname.to_owned());
-                    if let Some(scale) = attributes.iter().find(|&a| a.name.local_name == "scale") {
-                        poly.scale_xyz(parse_3f(&scale.value).unwrap());
-                    }
-                    shape = Some(poly);
-                }
-                "box" | "cylinder" | "sphere" => match name.local_name.as_str() {
-                    "box" => {
-                        let size_attr = attributes.get(0).ok_or("expected sized").unwrap();
-                        if size_attr.name.local_name == "size" {
-                            let size = parse_3f(&size_attr.value).unwrap();
-                            shape = Polyhedron::from(TriMesh::create_box(size)).into();
-                        } else {
-                            return Err("box requires size attribute".into());
-                        }
+                    let fname = xml_parser.attr(&attributes, "filename")?;
+                    let mut poly = load_mesh(xml_parser, fname)?;
+                    if let Some(scale) = find_attr(&attributes, "scale") {
+                        poly.scale_xyz(xml_parser.parse_3f(scale)?);
                     }
-                    "cylinder" => {
-                        let l = attributes
-                            .iter()
-                            .find(|&a| a.name.local_name == "length")
-                            .ok_or("cylinder requires length")
-                            .unwrap()
-                            .value
-                            .parse::<f32>()
-                            .unwrap();
-                        let r = attributes
-                            .iter()
-                            .find(|&a| a.name.local_name == "radius")
-                            .ok_or("cylinder requires radius")
-                            .unwrap()
-                            .value
-                            .parse::<f32>()
-                            .unwrap();
-                        shape = Polyhedron::from(TriMesh::create_cylinder(r, l, 30)).into();
-                    }
-                    "sphere" => {
-                        let r = attributes
-                            .iter()
-                            .find(|&a| a.name.local_name == "radius")
-                            .ok_or("sphere requires radius")
-                            .unwrap()
-                            .value
-                            .parse::<f32>()
-                            .unwrap();
-                        shape = Polyhedron::from(TriMesh::create_sphere(r, 20, 20)).into();
-                    }
-                    _ => return Err("unknown element".into()),
-                },
-                _ => return Err("unknown element".into()),
-            },
-            EndElement { name } => {
-                if name.local_name == "geometry" {
-                    return shape.ok_or("no shape provided?".into());
+                    shape = Some((poly, None));
+                }
+                "box" => {
+                    let size = xml_parser.parse_3f(xml_parser.attr(&attributes, "size")?)?;
+                    let poly = Polyhedron::from(TriMesh::create_box(size));
+                    shape = Some((poly, Some(Geometry::Box { size })));
                 }
+                "cylinder" => {
+                    let length = xml_parser.attr_f32(&attributes, "length")?;
+                    let radius = xml_parser.attr_f32(&attributes, "radius")?;
+                    let poly = Polyhedron::from(TriMesh::create_cylinder(radius, length, 30));
+                    shape = Some((poly, Some(Geometry::Cylinder { radius, length })));
+                }
+                "sphere" => {
+                    let radius = xml_parser.attr_f32(&attributes, "radius")?;
+                    let poly = Polyhedron::from(TriMesh::create_sphere(radius, 20, 20));
+                    shape = Some((poly, Some(Geometry::Sphere { radius })));
+                }
+                "capsule" => {
+                    let length = xml_parser.attr_f32(&attributes, "length")?;
+                    let radius = xml_parser.attr_f32(&attributes, "radius")?;
+                    let poly = Polyhedron::from(TriMesh::create_capsule(radius, length, 20));
+                    shape = Some((poly, Some(Geometry::Capsule { radius, length })));
+                }
+                other => {
+                    return Err(
+                        xml_parser.error(UrdfErrorKind::UnsupportedElement(other.to_owned()))
+                    )
+                }
+            },
+            EndElement { name } if name.local_name == "geometry" => {
+                return shape.ok_or_else(|| {
+                    xml_parser.error(UrdfErrorKind::MissingElement(
+                        "box|cylinder|sphere|capsule|mesh".into(),
+                    ))
+                });
             }
             _ => {}
         }
@@ -221,45 +640,34 @@ fn parse_link_geometry(xml_parser: &mut EventReader<&[u8]>) -> Result<Polyhedron
 }
 
 fn parse_link_visual(
-    xml_parser: &mut EventReader<&[u8]>,
+    xml_parser: &mut UrdfReader,
     mut link: Link,
     materials: &mut Vec<Material>,
-) -> Result<Link, ParseRobotError> {
-    let mut transform: Option<Transform> = None;
+) -> Result<Link, UrdfError> {
+    let mut origin: Option<Origin> = None;
     loop {
-        let event = xml_parser.next();
-        match event.clone().unwrap() {
+        match xml_parser.next()? {
             StartElement {
                 name, attributes, ..
             } => match name.local_name.as_str() {
-                "origin" => {
-                    let Origin { xyz, rpy } = parse_origin(event.unwrap()).unwrap();
-                    transform = Some(Transform::new(xyz, rpy.unwrap_or_default()));
-                }
-                "geometry" => link.visual.geometry = parse_link_geometry(xml_parser).unwrap(),
-                "material" => {
-                    if link.visual.material.is_none() {
-                        let mat_name = &attributes
-                            .iter()
-                            .find(|&a| a.name.local_name == "name")
-                            .ok_or("material requires name")
-                            .unwrap()
-                            .value
-                            .to_owned();
-                        link.visual.material = Some(mat_name.to_owned());
-                        // if let Some(mat) = materials.iter().find(|m| m.name == *mat_name) {
-                        //     link.geometry.set_color(mat.color);
-                        // } else {
-                        if let Ok(mat) = parse_material(xml_parser, mat_name.to_owned()) {
-                            materials.push(mat);
-                        }
-                        // }
+                "origin" => origin = Some(parse_origin(xml_parser, &attributes)?),
+                "geometry" => link.visual.geometry = parse_link_geometry(xml_parser)?.0,
+                "material" if link.visual.material.is_none() => {
+                    let mat_name = xml_parser.attr(&attributes, "name")?.to_owned();
+                    link.visual.material = Some(mat_name.to_owned());
+                    // if let Some(mat) = materials.iter().find(|m| m.name == *mat_name) {
+                    //     link.geometry.set_color(mat.color);
+                    // } else {
+                    if let Some(mat) = parse_material(xml_parser, mat_name)? {
+                        materials.push(mat);
                     }
+                    // }
                 }
                 _ => {}
             },
             EndElement { name } => {
-                link.visual.transform = transform.unwrap_or_default();
+                link.visual.origin = origin.unwrap_or_default();
+                link.visual.transform = link.visual.origin.into();
                 if name.local_name == "visual" {
                     return Ok(link);
                 }
@@ -269,105 +677,72 @@ fn parse_link_visual(
     }
 }
 
-fn parse_link_collision(
-    xml_parser: &mut EventReader<&[u8]>,
-    mut link: Link,
-) -> Result<Link, ParseRobotError> {
+fn parse_link_collision(xml_parser: &mut UrdfReader, mut link: Link) -> Result<Link, UrdfError> {
+    let mut collision = CollisionBody::default();
     loop {
-        let event = xml_parser.next();
-        match event.clone().unwrap() {
+        match xml_parser.next()? {
             StartElement {
                 name, attributes, ..
             } => match name.local_name.as_str() {
-                "origin" => link.collision.transform = parse_origin(event.unwrap()).unwrap().into(),
+                "origin" => {
+                    collision.origin = parse_origin(xml_parser, &attributes)?;
+                    collision.transform = collision.origin.into();
+                }
                 "geometry" => {
-                    link.collision.geometry = parse_link_geometry(xml_parser).unwrap();
+                    (collision.geometry, collision.shape) = parse_link_geometry(xml_parser)?;
                 }
                 _ => {}
             },
-            EndElement { name } => {
-                if name.local_name == "collision" {
-                    return Ok(link);
-                }
+            EndElement { name } if name.local_name == "collision" => {
+                link.collisions.push(collision);
+                return Ok(link);
             }
             _ => {}
         }
     }
 }
-fn parse_link_inertial(
-    xml_parser: &mut EventReader<&[u8]>,
-    mut link: Link,
-) -> Result<Link, ParseRobotError> {
+fn parse_link_inertial(xml_parser: &mut UrdfReader, mut link: Link) -> Result<Link, UrdfError> {
     let mut origin: Option<Origin> = None;
     let mut mass: Option<f32> = None;
     let mut inertia: Option<[f32; 6]> = None;
     loop {
-        let event = xml_parser.next();
-        match event.clone().unwrap() {
+        match xml_parser.next()? {
             StartElement {
                 name, attributes, ..
             } => match name.local_name.as_str() {
-                "origin" => origin = parse_origin(event.unwrap()).ok(),
-                "mass" => mass = attributes.get(0).unwrap().value.parse::<f32>().ok(),
+                "origin" => origin = Some(parse_origin(xml_parser, &attributes)?),
+                "mass" => mass = Some(xml_parser.attr_f32(&attributes, "value")?),
                 "inertia" => {
                     inertia = Some([
-                        if let Some(a) = attributes.iter().find(|a| a.name.local_name == "ixx") {
-                            a.value.parse::<f32>().unwrap()
-                        } else {
-                            0.0
-                        },
-                        if let Some(a) = attributes.iter().find(|a| a.name.local_name == "iyy") {
-                            a.value.parse::<f32>().unwrap()
-                        } else {
-                            0.0
-                        },
-                        if let Some(a) = attributes.iter().find(|a| a.name.local_name == "izz") {
-                            a.value.parse::<f32>().unwrap()
-                        } else {
-                            0.0
-                        },
-                        if let Some(a) = attributes.iter().find(|a| a.name.local_name == "ixy") {
-                            a.value.parse::<f32>().unwrap()
-                        } else {
-                            0.0
-                        },
-                        if let Some(a) = attributes.iter().find(|a| a.name.local_name == "ixz") {
-                            a.value.parse::<f32>().unwrap()
-                        } else {
-                            0.0
-                        },
-                        if let Some(a) = attributes.iter().find(|a| a.name.local_name == "iyz") {
-                            a.value.parse::<f32>().unwrap()
-                        } else {
-                            0.0
-                        },
+                        xml_parser.attr_f32_or(&attributes, "ixx", 0.0)?,
+                        xml_parser.attr_f32_or(&attributes, "iyy", 0.0)?,
+                        xml_parser.attr_f32_or(&attributes, "izz", 0.0)?,
+                        xml_parser.attr_f32_or(&attributes, "ixy", 0.0)?,
+                        xml_parser.attr_f32_or(&attributes, "ixz", 0.0)?,
+                        xml_parser.attr_f32_or(&attributes, "iyz", 0.0)?,
                     ]);
                 }
                 _ => {}
             },
-            EndElement { name } => {
-                if name.local_name == "inertial" {
-                    if let Some(mass) = mass {
-                        if let Some([ixx, iyy, izz, ixy, ixz, iyz]) = inertia {
-                            link.inertial = InertialBody {
-                                origin: origin.unwrap_or_default(),
-                                transform: origin.unwrap_or_default().into(),
-                                mass,
-                                ixx,
-                                iyy,
-                                izz,
-                                ixy,
-                                ixz,
-                                iyz,
-                            };
-                            return Ok(link);
-                        } else {
-                            panic!("inertial body requires moments of inertia!")
-                        }
-                    } else {
-                        panic!("inertial body requires mass!")
-                    }
-                }
+            EndElement { name } if name.local_name == "inertial" => {
+                let mass = mass.ok_or_else(|| {
+                    xml_parser.error(UrdfErrorKind::MissingElement("mass".into()))
+                })?;
+                let [ixx, iyy, izz, ixy, ixz, iyz] = inertia.ok_or_else(|| {
+                    xml_parser.error(UrdfErrorKind::MissingElement("inertia".into()))
+                })?;
+                link.inertial = InertialBody {
+                    origin: origin.unwrap_or_default(),
+                    transform: origin.unwrap_or_default().into(),
+                    mass,
+                    ixx,
+                    iyy,
+                    izz,
+                    ixy,
+                    ixz,
+                    iyz,
+                };
+                return Ok(link);
             }
             _ => {}
         }
@@ -375,135 +750,134 @@ fn parse_link_inertial(
 }
 
 fn parse_link(
-    xml_parser: &mut EventReader<&[u8]>,
+    xml_parser: &mut UrdfReader,
     link_name: String,
     materials: &mut Vec<Material>,
-) -> Result<Link, ParseRobotError> {
-    let mut link = Link::default();
-    link.link_name = link_name;
+) -> Result<Link, UrdfError> {
+    let mut link = Link {
+        link_name,
+        ..Default::default()
+    };
     loop {
-        let event = xml_parser.next();
-        match event.clone().unwrap() {
+        match xml_parser.next()? {
             StartElement { name, .. } => match name.local_name.as_str() {
-                "visual" => {
-                    link = parse_link_visual(xml_parser, link, materials).unwrap();
-                }
-                "inertial" => link = parse_link_inertial(xml_parser, link).unwrap(),
-                "collision" => link = parse_link_collision(xml_parser, link).unwrap(),
+                "visual" => link = parse_link_visual(xml_parser, link, materials)?,
+                "inertial" => link = parse_link_inertial(xml_parser, link)?,
+                "collision" => link = parse_link_collision(xml_parser, link)?,
                 _ => {}
             },
-            EndElement { name } => {
-                if name.local_name == "link" {
-                    return Ok(link);
-                }
+            EndElement { name } if name.local_name == "link" => {
+                return Ok(link);
             }
             _ => {}
         }
     }
 }
 
-pub fn parse_joint(
-    xml_parser: &mut EventReader<&[u8]>,
+// no longer public: it reads from the private UrdfReader, and joints can only
+// be resolved against the links of a whole document
+fn parse_joint(
+    xml_parser: &mut UrdfReader,
     joint_name: String,
     joint_type: JointType,
-    links: &Vec<Link>,
-) -> Result<Joint, ParseRobotError> {
-    let mut parent_name: Option<String> = None;
-    let mut child_name: Option<String> = None;
+    link_refs: &mut Vec<[(String, UrdfError); 2]>,
+    joint_refs: &mut Vec<(String, UrdfError)>,
+) -> Result<Joint, UrdfError> {
+    let mut parent: Option<(String, UrdfError)> = None;
+    let mut child: Option<(String, UrdfError)> = None;
     let mut origin: Option<Origin> = None;
     let mut axis: Option<glm::Vec3> = None;
     let mut limits: Option<JointLimits> = None;
     let mut dynamics: Option<JointDynamics> = None;
+    let mut mimic: Option<JointMimic> = None;
+    let mut safety_controller: Option<SafetyController> = None;
+    let mut calibration: Option<JointCalibration> = None;
+
+    // links may be declared after the joints using them, so the names are
+    // resolved once the whole document has been read
+    let link_ref = |xml_parser: &UrdfReader, attributes: &[OwnedAttribute]| {
+        let link_name = xml_parser.attr(attributes, "link")?.to_owned();
+        let err = xml_parser.error(UrdfErrorKind::UnknownLink(link_name.clone()));
+        Ok::<_, UrdfError>((link_name, err))
+    };
 
     loop {
-        let event = xml_parser.next();
-        match event.clone().unwrap() {
+        match xml_parser.next()? {
             StartElement {
                 name, attributes, ..
             } => match name.local_name.as_str() {
-                "parent" => {
-                    parent_name = Some(
-                        attributes
-                            .get(0)
-                            .expect("parent requires name")
-                            .value
-                            .clone(),
-                    )
-                }
-                "child" => {
-                    child_name = Some(
-                        attributes
-                            .get(0)
-                            .expect("child requires name")
-                            .value
-                            .clone(),
-                    )
-                }
-                "origin" => origin = Some(parse_origin(event.clone().unwrap()).unwrap()),
-                "axis" => {
-                    let attr = attributes.get(0).ok_or("expected attribute xyz").unwrap();
-                    axis = Some(parse_3f(&attr.value).unwrap());
-                }
+                "parent" => parent = Some(link_ref(xml_parser, &attributes)?),
+                "child" => child = Some(link_ref(xml_parser, &attributes)?),
+                "origin" => origin = Some(parse_origin(xml_parser, &attributes)?),
+                "axis" => axis = Some(xml_parser.parse_3f(xml_parser.attr(&attributes, "xyz")?)?),
                 "limit" => {
-                    let (mut effort, mut lower, mut upper, mut velocity) = (0., 0., 0., 0.);
-                    for attr in attributes {
-                        match attr.name.local_name.as_str() {
-                            "effort" => effort = attr.value.parse::<f32>().unwrap(),
-                            "lower" => lower = attr.value.parse::<f32>().unwrap(),
-                            "upper" => upper = attr.value.parse::<f32>().unwrap(),
-                            "velocity" => velocity = attr.value.parse::<f32>().unwrap(),
-                            _ => {
-                                panic!("unkown attribute in limit")
-                            }
-                        }
-                    }
                     limits = Some(JointLimits {
-                        effort,
-                        velocity,
-                        lower,
-                        upper,
+                        effort: xml_parser.attr_f32_or(&attributes, "effort", 0.0)?,
+                        velocity: xml_parser.attr_f32_or(&attributes, "velocity", 0.0)?,
+                        lower: xml_parser.attr_f32_or(&attributes, "lower", 0.0)?,
+                        upper: xml_parser.attr_f32_or(&attributes, "upper", 0.0)?,
                     });
                 }
                 "dynamics" => {
-                    let (mut damping, mut friction) = (0.0, 0.0);
-                    for attr in attributes {
-                        match attr.name.local_name.as_str() {
-                            "damping" => damping = attr.value.parse::<f32>().unwrap(),
-                            "friction" => friction = attr.value.parse::<f32>().unwrap(),
-                            _ => {
-                                panic!("unknown attribute in dynamics")
-                            }
-                        }
-                    }
-                    dynamics = Some(JointDynamics { damping, friction });
+                    dynamics = Some(JointDynamics {
+                        damping: xml_parser.attr_f32_or(&attributes, "damping", 0.0)?,
+                        friction: xml_parser.attr_f32_or(&attributes, "friction", 0.0)?,
+                    });
                 }
-                _ => {
-                    eprintln!("{}", name.local_name.as_str());
-                    return Err("possibly unsupported feature".into());
+                "mimic" => {
+                    let joint = xml_parser.attr(&attributes, "joint")?.to_owned();
+                    let err = xml_parser.error(UrdfErrorKind::UnknownJoint(joint.clone()));
+                    joint_refs.push((joint.clone(), err));
+                    mimic = Some(JointMimic {
+                        joint,
+                        multiplier: xml_parser.attr_f32_or(&attributes, "multiplier", 1.0)?,
+                        offset: xml_parser.attr_f32_or(&attributes, "offset", 0.0)?,
+                    });
                 }
-            },
-            EndElement { name } => {
-                if name.local_name == "joint" {
-                    break;
+                "safety_controller" => {
+                    safety_controller = Some(SafetyController {
+                        soft_lower_limit: xml_parser.attr_f32_or(
+                            &attributes,
+                            "soft_lower_limit",
+                            0.0,
+                        )?,
+                        soft_upper_limit: xml_parser.attr_f32_or(
+                            &attributes,
+                            "soft_upper_limit",
+                            0.0,
+                        )?,
+                        k_position: xml_parser.attr_f32_or(&attributes, "k_position", 0.0)?,
+                        k_velocity: xml_parser.attr_f32(&attributes, "k_velocity")?,
+                    });
+                }
+                "calibration" => {
+                    let optional = |name| {
+                        find_attr(&attributes, name)
+                            .map(|v| xml_parser.parse_f32(v))
+                            .transpose()
+                    };
+                    calibration = Some(JointCalibration {
+                        rising: optional("rising")?,
+                        falling: optional("falling")?,
+                    });
+                }
+                other => {
+                    return Err(
+                        xml_parser.error(UrdfErrorKind::UnsupportedElement(other.to_owned()))
+                    )
                 }
+            },
+            EndElement { name } if name.local_name == "joint" => {
+                break;
             }
-            Whitespace(..) => {}
             _ => {}
         }
     }
-    let (parent, child);
-    let p_name = parent_name.ok_or("parent element is required!").unwrap();
-    parent = links
-        .iter()
-        .position(|l| l.link_name == p_name)
-        .ok_or(format!("no known link with name {}", p_name))
-        .unwrap();
-    let c_name = child_name.ok_or("child element is required!").unwrap();
-    child = links
-        .iter()
-        .position(|l| l.link_name == c_name)
-        .ok_or(format!("no known link with name {}", c_name))
-        .unwrap();
+    let parent =
+        parent.ok_or_else(|| xml_parser.error(UrdfErrorKind::MissingElement("parent".into())))?;
+    let child =
+        child.ok_or_else(|| xml_parser.error(UrdfErrorKind::MissingElement("child".into())))?;
+    link_refs.push([parent, child]);
     let transform = if let Some(Origin { xyz, rpy }) = origin {
         Transform::new(xyz, rpy.unwrap_or_default())
     } else {
@@ -512,13 +886,16 @@ pub fn parse_joint(
     Ok(Joint {
         joint_name,
         joint_type,
-        parent,
-        child,
+        parent: 0,
+        child: 0,
         origin: origin.unwrap_or_default(),
         transform,
         axis,
         limits,
         dynamics,
+        mimic,
+        safety_controller,
+        calibration,
     })
 }
 
@@ -527,260 +904,568 @@ struct Material {
     name: String,
     color: glm::Vec3,
 }
+// a material element may only reference a previously declared material by
+// name, in which case there is nothing to return
 fn parse_material(
-    xml_parser: &mut EventReader<&[u8]>,
+    xml_parser: &mut UrdfReader,
     material_name: String,
-) -> Result<Material, ParseRobotError> {
-    let color: glm::Vec3;
+) -> Result<Option<Material>, UrdfError> {
+    let mut material = None;
     loop {
-        let event = xml_parser.next().unwrap();
-        match event {
+        match xml_parser.next()? {
             StartElement {
                 name, attributes, ..
             } => match name.local_name.as_str() {
                 "color" => {
-                    let attr = attributes
-                        .iter()
-                        .find(|&a| a.name.local_name == "rgba")
-                        .ok_or("color must have rgba value")
-                        .unwrap();
-                    color = parse_4f(&attr.value).unwrap().xyz();
-                    return Ok(Material {
-                        name: material_name,
+                    let color = xml_parser
+                        .parse_4f(xml_parser.attr(&attributes, "rgba")?)?
+                        .xyz();
+                    material = Some(Material {
+                        name: material_name.clone(),
                         color,
                     });
                 }
-                "texture" => {
-                    unimplemented!()
+                other => {
+                    return Err(
+                        xml_parser.error(UrdfErrorKind::UnsupportedElement(other.to_owned()))
+                    )
                 }
-                _ => return Err("unknown element in material".into()),
             },
-            EndElement { name } => {
-                if name.local_name == "material" {
-                    return Err("could not parse material".into());
-                }
-            }
-            Whitespace(..) => {}
-            _ => {
-                return Err("could not parse material".into());
+            EndElement { name } if name.local_name == "material" => {
+                return Ok(material);
             }
+            _ => {}
         }
     }
 }
+fn parse_joint_type(xml_parser: &UrdfReader, value: &str) -> Result<JointType, UrdfError> {
+    match value {
+        "fixed" => Ok(JointType::Fixed),
+        "revolute" => Ok(JointType::Revolute),
+        "continuous" => Ok(JointType::Continuous),
+        "prismatic" => Ok(JointType::Prismatic),
+        "floating" => Ok(JointType::Floating),
+        _ => Err(xml_parser.error(UrdfErrorKind::InvalidValue {
+            attribute: "type".into(),
+            value: value.to_owned(),
+        })),
+    }
+}
+// joint names are checked once the whole document has been read, as the
+// transmission may come before the joint it refers to
 fn parse_transmission(
-    mut xml_parser: EventReader<&[u8]>,
-    name: Option<String>
-    ) -> Result<(), ParseRobotError> {
-    let joints: Vec<String> = Vec::new();
+    xml_parser: &mut UrdfReader,
+    mut transmission: Transmission,
+    joint_refs: &mut Vec<(String, UrdfError)>,
+) -> Result<Transmission, UrdfError> {
     loop {
-        let event = xml_parser.next();
-        match event.unwrap() {
-            StartElement { name, attributes, .. } =>
-                match name.local_name.as_str() {
-                    "type" => {},
-                    "joint" => {},
-                    "actuator" => {},
-                    _=> {}
-                },
-            EndElement { name } => if name.local_name.as_str() == "transmission" { return Ok(()) }
+        match xml_parser.next()? {
+            StartElement {
+                name, attributes, ..
+            } => match name.local_name.as_str() {
+                "type" => transmission.transmission_type = Some(xml_parser.text()?),
+                "joint" => {
+                    let joint_name = xml_parser.attr(&attributes, "name")?.to_owned();
+                    let err = xml_parser.error(UrdfErrorKind::UnknownJoint(joint_name.clone()));
+                    joint_refs.push((joint_name.clone(), err));
+                    transmission.joints.push(joint_name);
+                }
+                "actuator" => {
+                    let actuator_name = xml_parser.attr(&attributes, "name")?.to_owned();
+                    transmission.actuators.push(actuator_name);
+                }
+                // may appear directly in the transmission or in its joint/actuator
+                "mechanicalReduction" => {
+                    let text = xml_parser.text()?;
+                    transmission.mechanical_reduction = xml_parser.parse_f32(&text)?;
+                }
+                "hardwareInterface" => {
+                    let text = xml_parser.text()?;
+                    transmission.hardware_interface.get_or_insert(text);
+                }
+                _ => {}
+            },
+            EndElement { name } if name.local_name == "transmission" => {
+                return Ok(transmission);
+            }
             _ => {}
         }
     }
 }
+fn parse_ray_scan(
+    xml_parser: &UrdfReader,
+    attributes: &[OwnedAttribute],
+) -> Result<RayScan, UrdfError> {
+    let default = RayScan::default();
+    Ok(RayScan {
+        samples: xml_parser.attr_u32_or(attributes, "samples", default.samples)?,
+        resolution: xml_parser.attr_f32_or(attributes, "resolution", default.resolution)?,
+        min_angle: xml_parser.attr_f32_or(attributes, "min_angle", default.min_angle)?,
+        max_angle: xml_parser.attr_f32_or(attributes, "max_angle", default.max_angle)?,
+    })
+}
+// the sensor with the name of its parent link, resolved by the caller
+fn parse_sensor(
+    xml_parser: &mut UrdfReader,
+    sensor_name: String,
+    update_rate: Option<f32>,
+) -> Result<(Sensor, (String, UrdfError)), UrdfError> {
+    let mut parent: Option<(String, UrdfError)> = None;
+    let mut origin = Origin::default();
+    let mut kind: Option<SensorKind> = None;
+    loop {
+        match xml_parser.next()? {
+            StartElement {
+                name, attributes, ..
+            } => match name.local_name.as_str() {
+                "parent" => {
+                    let link_name = xml_parser.attr(&attributes, "link")?.to_owned();
+                    let err = xml_parser.error(UrdfErrorKind::UnknownLink(link_name.clone()));
+                    parent = Some((link_name, err));
+                }
+                "origin" => origin = parse_origin(xml_parser, &attributes)?,
+                "image" => {
+                    kind = Some(SensorKind::Camera {
+                        width: xml_parser.attr_u32_or(&attributes, "width", 640)?,
+                        height: xml_parser.attr_u32_or(&attributes, "height", 480)?,
+                        hfov: xml_parser.attr_f32_or(
+                            &attributes,
+                            "hfov",
+                            std::f32::consts::FRAC_PI_2,
+                        )?,
+                        near: xml_parser.attr_f32_or(&attributes, "near", 0.01)?,
+                        far: xml_parser.attr_f32_or(&attributes, "far", 100.0)?,
+                    })
+                }
+                "ray" => {
+                    kind = Some(SensorKind::Ray {
+                        horizontal: RayScan::default(),
+                        vertical: RayScan::default(),
+                    })
+                }
+                "horizontal" | "vertical" => {
+                    let scan = parse_ray_scan(xml_parser, &attributes)?;
+                    if let Some(SensorKind::Ray {
+                        horizontal,
+                        vertical,
+                    }) = &mut kind
+                    {
+                        match name.local_name.as_str() {
+                            "horizontal" => *horizontal = scan,
+                            _ => *vertical = scan,
+                        }
+                    }
+                }
+                _ => {}
+            },
+            EndElement { name } if name.local_name == "sensor" => {
+                break;
+            }
+            _ => {}
+        }
+    }
+    let parent =
+        parent.ok_or_else(|| xml_parser.error(UrdfErrorKind::MissingElement("parent".into())))?;
+    let sensor = Sensor {
+        name: sensor_name,
+        link: 0,
+        origin,
+        update_rate,
+        kind,
+    };
+    Ok((sensor, parent))
+}
 fn parse_robot(
-    mut xml_parser: EventReader<&[u8]>,
+    mut xml_parser: UrdfReader,
     robot_name: Option<String>,
-) -> Result<RobotDescriptor, ParseRobotError> {
+) -> Result<RobotDescriptor, UrdfError> {
     let mut links = Vec::new();
     let mut joints = Vec::new();
+    let mut transmissions = Vec::new();
+    let mut frames = Vec::new();
+    let mut frame_refs = Vec::new();
+    let mut sensors = Vec::new();
+    let mut sensor_refs = Vec::new();
     let mut materials = Vec::<Material>::new();
-    // let mut attr_name: String = "".into();
+    let mut joint_refs = Vec::new();
+    let mut link_refs = Vec::new();
+    // where each link was declared, to point tree errors at it
+    let mut link_locations = Vec::new();
     loop {
-        let event = xml_parser.next();
-        match event.unwrap() {
+        match xml_parser.next()? {
             StartElement {
                 name, attributes, ..
-            } => {
-                match name.local_name.as_str() {
-                    "link" => {
-                        let attr = attributes
-                            .iter()
-                            .find(|a| a.name.local_name == "name")
-                            .ok_or("link requires name")
-                            .unwrap();
-                        // attr_name = attr.value.to_owned();
-                        links.push(parse_link(&mut xml_parser, attr.value.to_owned(), &mut materials).unwrap())
-                    }
-                    "joint" => {
-                        let attr = attributes
-                            .iter()
-                            .find(|a| a.name.local_name == "name")
-                            .ok_or("joint requires name")
-                            .unwrap();
-                        // attr_name = attr.value.to_owned();
-                        let joint_type: JointType;
-                        if let Some(attr) = attributes.get(1) {
-                            assert!(attr.name.local_name == "type");
-                            match attr.value.as_str() {
-                                "fixed" => joint_type = JointType::Fixed,
-                                "revolute" => joint_type = JointType::Revolute,
-                                "continuous" => joint_type = JointType::Continuous,
-                                "prismatic" => joint_type = JointType::Prismatic,
-                                "floating" => joint_type = JointType::Floating,
-                                _ => return Err("unrecognized joint type".into()),
-                            }
-                        } else {
-                            return Err("joint requires type attribute".into());
-                        }
-                        joints.push(
-                            parse_joint(&mut xml_parser, attr.value.to_owned(), joint_type, &links).unwrap(),
-                        )
-                    }
-                    "transmission" => {panic!("unsupported feature: transmission")},
-                    "sensor" => {panic!("unsupported feature: sensor")},
-                    "material" => {
-                        let attr = attributes
-                            .iter()
-                            .find(|a| a.name.local_name == "name")
-                            .ok_or("material requires name")
-                            .unwrap();
-                        materials.push(parse_material(&mut xml_parser, attr.value.to_owned()).unwrap())
-                    }
-                    _ => {
-                        return Err(
-                            format!("unexpected element name! \"{}\"", name.local_name).into()
-                        )
+            } => match name.local_name.as_str() {
+                "link" => {
+                    let link_name = xml_parser.attr(&attributes, "name")?.to_owned();
+                    link_locations
+                        .push(xml_parser.error(UrdfErrorKind::DisconnectedLink(link_name.clone())));
+                    links.push(parse_link(&mut xml_parser, link_name, &mut materials)?)
+                }
+                "joint" => {
+                    let joint_name = xml_parser.attr(&attributes, "name")?.to_owned();
+                    let joint_type =
+                        parse_joint_type(&xml_parser, xml_parser.attr(&attributes, "type")?)?;
+                    joints.push(parse_joint(
+                        &mut xml_parser,
+                        joint_name,
+                        joint_type,
+                        &mut link_refs,
+                        &mut joint_refs,
+                    )?)
+                }
+                "material" => {
+                    let mat_name = xml_parser.attr(&attributes, "name")?.to_owned();
+                    if let Some(mat) = parse_material(&mut xml_parser, mat_name)? {
+                        materials.push(mat)
                     }
                 }
-            }
-            EndElement { name } => {
-                if name.local_name == "robot" {
-                    break;
+                "transmission" => {
+                    // older files give the type as an attribute rather than an element
+                    let transmission = Transmission {
+                        name: find_attr(&attributes, "name").map(str::to_owned),
+                        transmission_type: find_attr(&attributes, "type").map(str::to_owned),
+                        ..Default::default()
+                    };
+                    transmissions.push(parse_transmission(
+                        &mut xml_parser,
+                        transmission,
+                        &mut joint_refs,
+                    )?)
+                }
+                "frame" => {
+                    let link_name = xml_parser.attr(&attributes, "link")?.to_owned();
+                    frame_refs.push((
+                        link_name.clone(),
+                        xml_parser.error(UrdfErrorKind::UnknownLink(link_name)),
+                    ));
+                    frames.push(Frame {
+                        name: xml_parser.attr(&attributes, "name")?.to_owned(),
+                        link: 0,
+                        origin: parse_origin(&xml_parser, &attributes)?,
+                    });
                 }
+                "sensor" => {
+                    let sensor_name = xml_parser.attr(&attributes, "name")?.to_owned();
+                    let update_rate = find_attr(&attributes, "update_rate")
+                        .map(|v| xml_parser.parse_f32(v))
+                        .transpose()?;
+                    let (sensor, parent) = parse_sensor(&mut xml_parser, sensor_name, update_rate)?;
+                    sensors.push(sensor);
+                    sensor_refs.push(parent);
+                }
+                other => {
+                    return Err(
+                        xml_parser.error(UrdfErrorKind::UnsupportedElement(other.to_owned()))
+                    )
+                }
+            },
+            EndElement { name } if name.local_name == "robot" => {
+                break;
             }
             _ => {}
         }
     }
 
+    for (joint, [(parent, parent_err), (child, child_err)]) in joints.iter_mut().zip(&link_refs) {
+        let find_link = |name: &str| links.iter().position(|l: &Link| l.link_name == name);
+        joint.parent = find_link(parent).ok_or_else(|| parent_err.clone())?;
+        joint.child = find_link(child).ok_or_else(|| child_err.clone())?;
+    }
+    for (frame, (link, err)) in frames.iter_mut().zip(frame_refs) {
+        frame.link = links.iter().position(|l| l.link_name == link).ok_or(err)?;
+    }
+    for (sensor, (link, err)) in sensors.iter_mut().zip(sensor_refs) {
+        sensor.link = links.iter().position(|l| l.link_name == link).ok_or(err)?;
+    }
+    let tree = KinematicTree::new(links.len(), &joints).map_err(|e| {
+        let link_names =
+            |ids: Vec<usize>| ids.iter().map(|&l| links[l].link_name.clone()).collect();
+        let at = |location: &UrdfError, kind| UrdfError {
+            kind,
+            ..location.clone()
+        };
+        match e {
+            TreeError::NoLinks => xml_parser.error(UrdfErrorKind::MissingElement("link".into())),
+            TreeError::MultipleParents { link, joint } => at(
+                &link_refs[joint][1].1,
+                UrdfErrorKind::MultipleParents(links[link].link_name.clone()),
+            ),
+            TreeError::Disconnected(link) => link_locations[link].clone(),
+            TreeError::MultipleRoots(roots) => at(
+                &link_locations[roots[1]],
+                UrdfErrorKind::MultipleRoots(link_names(roots)),
+            ),
+            TreeError::Cycle(cycle) => at(
+                &link_locations[cycle[0]],
+                UrdfErrorKind::KinematicCycle(link_names(cycle)),
+            ),
+        }
+    })?;
+
+    if let Some((_, err)) = joint_refs
+        .into_iter()
+        .find(|(name, _)| !joints.iter().any(|j: &Joint| j.joint_name == *name))
+    {
+        return Err(err);
+    }
+
     //setup colors
     for mat in materials {
         for link in links.iter_mut() {
-            // println!("{:?}", link.visual.material);
             if link
                 .visual
                 .material
-                .clone()
-                .is_some_and(|mn| mn == mat.name)
+                .as_ref()
+                .is_some_and(|mn| *mn == mat.name)
             {
-                // println!("{:?}", mat);
                 link.visual.geometry.set_color(mat.color);
             }
         }
     }
 
-    return Ok(RobotDescriptor {
+    let mut robot = RobotDescriptor {
         name: robot_name,
         links,
+        joint_positions: vec![0.0; joints.len()],
         joints,
-    });
+        transmissions,
+        frames,
+        sensors,
+        limit_policy: LimitPolicy::default(),
+        tree,
+    };
+    robot.build();
+    Ok(robot)
 }
 
-impl FromStr for RobotDescriptor {
-    type Err = ParseRobotError;
-    fn from_str(s: &str) -> Result<RobotDescriptor, ParseRobotError> {
-        let mut xml_parser = EventReader::from_str(s);
-        let mut robot_name: Option<String> = None;
-        let event = xml_parser.next();
-        match event.unwrap() {
-            StartDocument { .. } => {}
-            _ => return Err("Is this a valid XML URDF file.unwrap()".into()),
-        }
-        let event = xml_parser.next();
-        match event.unwrap() {
+fn parse_document(
+    s: &str,
+    resolver: &ResourceResolver,
+    base_dir: Option<&Path>,
+) -> Result<RobotDescriptor, UrdfError> {
+    let mut xml_parser = UrdfReader::new(s, resolver, base_dir);
+    loop {
+        match xml_parser.next()? {
             StartElement {
                 name, attributes, ..
             } => {
-                assert!(name.local_name == "robot");
-                if let Some(attr) = attributes.iter().find(|a| a.name.local_name == "name") {
-                    robot_name = Some(attr.value.clone());
+                if name.local_name != "robot" {
+                    return Err(xml_parser.error(UrdfErrorKind::MissingElement("robot".into())));
                 }
+                let robot_name = find_attr(&attributes, "name").map(str::to_owned);
                 return parse_robot(xml_parser, robot_name);
             }
-            _ => Err("expected robot element as first element".into()),
+            StartDocument { .. } | Comment(..) | ProcessingInstruction { .. } | Whitespace(..) => {}
+            _ => return Err(xml_parser.error(UrdfErrorKind::MissingElement("robot".into()))),
         }
     }
 }
 
+// places a link frame and, offset from it, its visual, collision and
+// inertial frames
+fn place_link(link: &mut Link, frame: Transform) {
+    link.transform = frame;
+    link.visual.transform = frame * Transform::from(link.visual.origin);
+    for c in &mut link.collisions {
+        c.transform = frame * Transform::from(c.origin);
+    }
+    link.inertial.transform = frame * Transform::from(link.inertial.origin);
+}
+
+impl FromStr for RobotDescriptor {
+    type Err = UrdfError;
+    // mesh paths are resolved relative to the working directory
+    fn from_str(s: &str) -> Result<RobotDescriptor, UrdfError> {
+        parse_document(s, &ResourceResolver::default(), None)
+    }
+}
+
 impl RobotDescriptor {
-    pub fn set_joint_position(&mut self, theta: &[f32], relative: bool) {
-        if theta.len() != self.joints.len() {
-            panic!("expected {} got {}", self.joints.len(), theta.len())
-        }
-        for (&th, j) in std::iter::zip(theta.into_iter(), &mut self.joints) {
-            if !relative {
-                j.transform = j.origin.into();
+    pub fn transmission_for_joint(&self, joint_name: &str) -> Option<&Transmission> {
+        self.transmissions
+            .iter()
+            .find(|t| t.joints.iter().any(|j| j == joint_name))
+    }
+    /// Loads a URDF file, resolving `package://` and relative mesh paths
+    /// through `resolver` and the directory containing the file.
+    pub fn from_file(
+        path: impl AsRef<Path>,
+        resolver: &ResourceResolver,
+    ) -> Result<RobotDescriptor, UrdfError> {
+        let path = path.as_ref();
+        let s = std::fs::read_to_string(path).map_err(|e| UrdfError {
+            path: path.display().to_string(),
+            line: 0,
+            column: 0,
+            kind: UrdfErrorKind::Io(e.to_string()),
+        })?;
+        parse_document(&s, resolver, path.parent())
+    }
+    pub fn joint_index(&self, joint_name: &str) -> Option<usize> {
+        self.joints.iter().position(|j| j.joint_name == joint_name)
+    }
+    // Joints ordered so that every mimic joint comes after the joint it
+    // follows, with the index of that joint. Mimic joints whose source is
+    // unknown, or which follow each other in a loop, have nothing to follow
+    // and keep their own entry.
+    pub(crate) fn mimic_order(&self) -> Vec<(usize, Option<usize>)> {
+        let source: Vec<Option<usize>> = self
+            .joints
+            .iter()
+            .map(|j| j.mimic.as_ref().and_then(|m| self.joint_index(&m.joint)))
+            .collect();
+        let mut placed = vec![false; self.joints.len()];
+        let mut order = Vec::with_capacity(self.joints.len());
+        loop {
+            let before = order.len();
+            for (i, s) in source.iter().enumerate() {
+                if !placed[i] && s.is_none_or(|k| placed[k]) {
+                    placed[i] = true;
+                    order.push((i, *s));
+                }
             }
-            match j.joint_type {
-                JointType::Revolute => {
-                    j.transform
-                        .rotate(j.axis.expect("revolute joint requires axis!"), th);
-                    /* check for limits */
-                }
-                JointType::Prismatic => {
-                    j.transform
-                        .translate(th * j.axis.expect("prismatic joint requires axis"));
-                }
-                JointType::Continuous => {
-                    j.transform
-                        .rotate(j.axis.expect("revolute joint requires axis!"), th);
-                }
-                JointType::Floating => { /* do nothing */ }
-                JointType::Fixed => { /* do nothing */ }
+            if order.len() == before {
+                break;
             }
         }
+        order.extend(
+            (0..self.joints.len())
+                .filter(|&i| !placed[i])
+                .map(|i| (i, None)),
+        );
+        order
+    }
+    // position of joint `i`, following `theta[source]` for mimic joints
+    pub(crate) fn mimic_position(&self, i: usize, source: Option<usize>, theta: &[f32]) -> f32 {
+        match (&self.joints[i].mimic, source) {
+            (Some(m), Some(k)) => m.multiplier * theta[k] + m.offset,
+            _ => theta[i],
+        }
+    }
+    // mimic joints ignore their own entry and follow the joint they mimic
+    pub(crate) fn apply_mimic(&self, theta: &[f32]) -> Vec<f32> {
+        let mut theta = theta.to_vec();
+        for (i, source) in self.mimic_order() {
+            theta[i] = self.mimic_position(i, source, &theta);
+        }
+        theta
+    }
+    /// Sets the position of every joint, in joint order. With `relative` the
+    /// values are added to the current positions. Limits are enforced
+    /// according to `limit_policy`; the violations found are returned.
+    pub fn set_joint_position(
+        &mut self,
+        theta: &[f32],
+        relative: bool,
+    ) -> Result<Vec<LimitViolation>, JointError> {
+        let target = self.target_positions(theta, relative)?;
+        self.move_joints(target, None)
+    }
+    /// Like `set_joint_position`, but the move is taken to happen over `dt`
+    /// seconds so that joint velocity limits are checked as well.
+    pub fn set_joint_position_timed(
+        &mut self,
+        theta: &[f32],
+        relative: bool,
+        dt: f32,
+    ) -> Result<Vec<LimitViolation>, JointError> {
+        let target = self.target_positions(theta, relative)?;
+        self.move_joints(target, Some(dt))
+    }
+    fn target_positions(&self, theta: &[f32], relative: bool) -> Result<Vec<f32>, JointError> {
+        if theta.len() != self.joints.len() {
+            return Err(JointError::WrongLength {
+                expected: self.joints.len(),
+                got: theta.len(),
+            });
+        }
+        Ok(if relative {
+            std::iter::zip(&self.joint_positions, theta)
+                .map(|(q, th)| q + th)
+                .collect()
+        } else {
+            theta.to_vec()
+        })
+    }
+    fn move_joints(
+        &mut self,
+        mut target: Vec<f32>,
+        dt: Option<f32>,
+    ) -> Result<Vec<LimitViolation>, JointError> {
+        let violations = self.enforce_limits(&mut target, dt)?;
+        for ((&th, j), q) in target
+            .iter()
+            .zip(&mut self.joints)
+            .zip(&mut self.joint_positions)
+        {
+            j.transform = j.transform_at(th);
+            *q = th;
+        }
+        Ok(violations)
+    }
+    /// Last position set on every joint (including fixed ones), in joint order.
+    pub fn joint_positions(&self) -> &[f32] {
+        &self.joint_positions
     }
     pub fn reset_joint_transforms(&mut self) {
         self.links.iter_mut().for_each(|l| {
+            l.transform = Transform::default();
             l.inertial.transform = l.inertial.origin.into();
             l.visual.transform = l.visual.origin.into();
-            l.collision.transform = l.collision.origin.into();
+            for c in &mut l.collisions {
+                c.transform = c.origin.into();
+            }
         })
     }
-    fn walk_children(&self, cur_link: &Link) -> Vec<(usize, Transform)> {
-        self.joints
-            .iter()
-            .filter(|j| self.links[j.parent].link_name == cur_link.link_name)
-            .map(|j| {
-                let tf = cur_link.inertial.transform
-                    * j.transform
-                    * self.links[j.child].inertial.transform;
-                (j.child, tf)
-            })
-            .collect()
-    }
-    // Walk the DAG
+    pub fn tree(&self) -> &KinematicTree {
+        &self.tree
+    }
+    pub fn root_link(&self) -> &Link {
+        &self.links[self.tree.root()]
+    }
+    pub fn link_index(&self, link_name: &str) -> Option<usize> {
+        self.links.iter().position(|l| l.link_name == link_name)
+    }
+    pub fn frame(&self, frame_name: &str) -> Option<&Frame> {
+        self.frames.iter().find(|f| f.name == frame_name)
+    }
+    pub fn sensor(&self, sensor_name: &str) -> Option<&Sensor> {
+        self.sensors.iter().find(|s| s.name == sensor_name)
+    }
+    /// World transform of a named frame, as placed by the last `build`.
+    pub fn frame_transform(&self, frame_name: &str) -> Option<Transform> {
+        let frame = self.frame(frame_name)?;
+        Some(self.links[frame.link].transform * Transform::from(frame.origin))
+    }
+    // Walk the tree from the root, placing every link frame and then its
+    // visual, collision and inertial origins relative to it
     pub fn build(&mut self) {
-        //next, setup transforms
-        self.reset_joint_transforms();
-        let base_link = self.links.get(0).expect("No links found.unwrap()");
-        let mut child_transforms = self.walk_children(base_link);
-        loop {
-            let mut queue: Option<Vec<(usize, Transform)>> = None;
-            for (c_id, c_tf) in &child_transforms {
-                // update link with new transform
-                self.links[*c_id].inertial.transform = *c_tf;
-                self.links[*c_id].visual.transform = *c_tf;
-                // query for correct transforms of children links
-                let mut v = self.walk_children(&self.links[*c_id]);
-                v.extend(queue.unwrap_or_default());
-                queue = Some(v);
-            }
-            if let Some(qlist) = queue {
-                child_transforms = qlist;
-            } else {
-                break;
-            }
+        for i in 0..self.tree.order().len() {
+            let l = self.tree.order()[i];
+            let frame = match self.tree.parent_joint(l) {
+                Some(j) => {
+                    let joint = &self.joints[j];
+                    self.links[joint.parent].transform * joint.transform
+                }
+                None => Transform::default(),
+            };
+            place_link(&mut self.links[l], frame);
+        }
+    }
+    // links at `frames` and joints at `theta` as read back from a
+    // simulation, which has already dealt with limits and mimic joints
+    pub(crate) fn place_links(&mut self, frames: &[Transform], theta: &[f32]) {
+        for (link, &frame) in self.links.iter_mut().zip(frames) {
+            place_link(link, frame);
+        }
+        for ((&th, j), q) in theta
+            .iter()
+            .zip(&mut self.joints)
+            .zip(&mut self.joint_positions)
+        {
+            j.transform = j.transform_at(th);
+            *q = th;
         }
     }
 }
-
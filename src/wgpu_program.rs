@@ -6,10 +6,12 @@ use crate::{
     light::{Light, LightUniform},
     texture::Texture,
 };
+use anyhow::Context;
 use bytemuck::{cast_slice, Pod, Zeroable};
 use itertools::Itertools;
 use std::borrow::Borrow;
 use std::collections::HashMap;
+use std::path::Path;
 // use rayon::prelude::*;
 use winit::{
     dpi::PhysicalSize, event::WindowEvent, event_loop::EventLoop, keyboard::KeyCode, window::{Window, WindowBuilder}
@@ -21,10 +23,14 @@ pub struct WGPUState<'a> {
     pub instance: wgpu::Instance,
     pub adapter: wgpu::Adapter,
     pub device: wgpu::Device,
-    pub surface: wgpu::Surface<'a>,
+    // `None` when rendering offscreen
+    pub surface: Option<wgpu::Surface<'a>>,
     pub queue: wgpu::Queue,
+    // size and format of the frames, also configuring the surface if any
     pub config: wgpu::SurfaceConfiguration,
     pub depth_texture: Texture,
+    // frames are rendered into this texture when there is no surface
+    pub color_texture: Option<wgpu::Texture>,
 
     // Runtime state
     pub camera: Camera,
@@ -35,31 +41,35 @@ pub struct WGPUState<'a> {
     pub bindings: Option<Bindings>,
 }
 
+// without a surface, any adapter will do, down to a software one such as
+// lavapipe or llvmpipe with `force_fallback_adapter`
 fn retrieve_adapter_device(
     instance: &wgpu::Instance,
-    surface: &wgpu::Surface,
-) -> (wgpu::Adapter, wgpu::Device, wgpu::Queue) {
+    surface: Option<&wgpu::Surface>,
+    force_fallback_adapter: bool,
+) -> anyhow::Result<(wgpu::Adapter, wgpu::Device, wgpu::Queue)> {
     let device_fut = async {
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
                 power_preference: wgpu::PowerPreference::default(),
-                force_fallback_adapter: false,
-                compatible_surface: Some(
-                    surface
-                ),
+                force_fallback_adapter,
+                compatible_surface: surface,
             })
             .await
-            .expect("unable to find appropriate adapter");
+            .context("unable to find appropriate adapter")?;
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
                     label: None,
-                    required_features: wgpu::Features::TEXTURE_BINDING_ARRAY,
+                    // software adapters may not have it
+                    required_features: wgpu::Features::TEXTURE_BINDING_ARRAY & adapter.features(),
                     // Need to do the spatial transforms on
                     // shader!
                     // Make sure we use the texture resolution limits from the adapter, so we can support images the size of the swapchain.
                     required_limits: if cfg!(target_arch = "wasm32") {
                         wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits())
+                    } else if surface.is_none() {
+                        wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits())
                     } else {
                         wgpu::Limits::default()
                     },
@@ -68,8 +78,8 @@ fn retrieve_adapter_device(
                 None,
             )
             .await
-            .expect("Failed to get device");
-        (adapter, device, queue)
+            .context("Failed to get device")?;
+        Ok((adapter, device, queue))
     };
     futures::executor::block_on(device_fut)
 }
@@ -92,7 +102,10 @@ impl Vertex {
 
 #[allow(dead_code)]
 pub type WGPUGraphics<'a> = GraphicsContext<WGPUState<'a>, &'a Window, wgpu::Buffer>;
-impl<'a> WGPUGraphics<'a> {
+/// Renders into a texture instead of a window, e.g. for screenshots in CI.
+pub type OffscreenGraphics = GraphicsContext<WGPUState<'static>, (), wgpu::Buffer>;
+
+impl<'a, W> GraphicsContext<WGPUState<'a>, W, wgpu::Buffer> {
     // convenience accessors for state
     pub fn size(&self) -> &winit::dpi::PhysicalSize<u32> {
         return &self.state.size;
@@ -110,7 +123,7 @@ impl<'a> WGPUGraphics<'a> {
         &mut self.state.device
     }
     pub fn surface(&self) -> &wgpu::Surface {
-        &self.state.surface
+        self.state.surface.as_ref().expect("no surface when rendering offscreen")
     }
     pub fn queue(&self) -> &wgpu::Queue {
         &self.state.queue
@@ -317,7 +330,9 @@ impl<'a> WGPUGraphics<'a> {
         self.assign_buffer(&buffer.index_buffer, poly.indices());
         self.assign_buffer(&buffer.vertex_buffer, poly.verts());
     }
+}
 
+impl<'a> WGPUGraphics<'a> {
     //constructor
     pub fn new(width: u32, height: u32, window: &'a Window) -> Self {
         // let window = Window::new(event).expect("unable to create winit window");
@@ -356,7 +371,8 @@ impl<'a> WGPUGraphics<'a> {
         });
         let surface = instance.create_surface(wgpu::SurfaceTarget::from(window)).expect("unable to create surface");
 
-        let (adapter, device, queue) = retrieve_adapter_device(&instance, &surface);
+        let (adapter, device, queue) = retrieve_adapter_device(&instance, Some(&surface), false)
+            .expect("unable to create device");
 
         let swapchain_capabilities = surface.get_capabilities(&adapter);
         let swapchain_format = swapchain_capabilities
@@ -377,8 +393,26 @@ impl<'a> WGPUGraphics<'a> {
             desired_maximum_frame_latency: 2,
         };
 
+        Self::with_target(window, instance, adapter, device, queue, config, Some(surface), None)
+    }
+}
+
+impl<'a, W> GraphicsContext<WGPUState<'a>, W, wgpu::Buffer> {
+    // everything but the device and where frames go
+    #[allow(clippy::too_many_arguments)]
+    fn with_target(
+        window: W,
+        instance: wgpu::Instance,
+        adapter: wgpu::Adapter,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        config: wgpu::SurfaceConfiguration,
+        surface: Option<wgpu::Surface<'a>>,
+        color_texture: Option<wgpu::Texture>,
+    ) -> Self {
         let depth_texture = Texture::create_depth_texture(&device, &config, "depth_texture");
 
+        let (width, height) = (config.width, config.height);
         let camera = Camera::new(width, height);
         let camera_controller = CameraController::default();
         let camera_uniform = CameraUniform::new();
@@ -393,10 +427,11 @@ impl<'a> WGPUGraphics<'a> {
             state: WGPUState {
                 instance,
                 surface,
+                color_texture,
                 adapter,
                 device,
                 queue,
-                size,
+                size: PhysicalSize::new(config.width, config.height),
                 config,
                 camera,
                 camera_controller,
@@ -428,14 +463,16 @@ impl<'a> WGPUGraphics<'a> {
         buffer_list: &Vec<MeshBuffer>,
     ) {
         // self.set_clear_color((1.0, 1.0, 1.0, 1.0));
-        let output = self
-            .state
-            .surface
-            .get_current_texture()
-            .expect("failed to get current texture");
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        let output = self.state.surface.as_ref().map(|surface| {
+            surface
+                .get_current_texture()
+                .expect("failed to get current texture")
+        });
+        let target = match &output {
+            Some(output) => &output.texture,
+            None => self.state.color_texture.as_ref().expect("no render target"),
+        };
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
         let mut encoder =
             self.state
                 .device
@@ -474,7 +511,89 @@ impl<'a> WGPUGraphics<'a> {
             )
         }
         self.queue().submit(std::iter::once(encoder.finish()));
-        output.present();
+        if let Some(output) = output {
+            output.present();
+        }
+    }
+    // Offscreen
+    /// Texture the frames are rendered into, when rendering offscreen.
+    pub fn color_texture(&self) -> Option<&wgpu::Texture> {
+        self.state.color_texture.as_ref()
+    }
+    /// Reads back the last frame rendered offscreen.
+    pub fn read_image(&self) -> anyhow::Result<image::RgbaImage> {
+        let texture = self
+            .color_texture()
+            .context("only frames rendered offscreen can be read back")?;
+        let pixels = self.read_texture(texture, wgpu::TextureAspect::All)?;
+        image::RgbaImage::from_raw(texture.width(), texture.height(), pixels)
+            .context("unexpected readback size")
+    }
+    /// Copies `aspect` of a texture made with `COPY_SRC` back to the CPU,
+    /// row after row without padding.
+    pub fn read_texture(
+        &self,
+        texture: &wgpu::Texture,
+        aspect: wgpu::TextureAspect,
+    ) -> anyhow::Result<Vec<u8>> {
+        let (width, height) = (texture.width(), texture.height());
+        let texel = texture
+            .format()
+            .block_copy_size(Some(aspect))
+            .context("texture aspect cannot be copied")?;
+        // rows of a copy to a buffer are padded to a multiple of 256 bytes
+        let row = texel * width;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_row = row.div_ceil(align) * align;
+        let buffer = self.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback Buffer"),
+            size: (padded_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Readback Encoder"),
+            });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                aspect,
+                ..texture.as_image_copy()
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+        self.queue().submit(std::iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device().poll(wgpu::Maintain::Wait);
+        receiver.recv()??;
+        let bytes = slice
+            .get_mapped_range()
+            .chunks(padded_row as usize)
+            .flat_map(|r| &r[..row as usize])
+            .copied()
+            .collect();
+        buffer.unmap();
+        Ok(bytes)
+    }
+    /// Saves the last frame rendered offscreen as a PNG file.
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        self.read_image()?
+            .save_with_format(path, image::ImageFormat::Png)?;
+        Ok(())
     }
     pub fn create_bindings(
         &mut self,
@@ -533,7 +652,46 @@ impl<'a> WGPUGraphics<'a> {
     }
 }
 
-impl GraphicsProgram for WGPUGraphics<'_> {
+impl OffscreenGraphics {
+    /// Renders `width` by `height` frames into a texture, without a window,
+    /// on a software adapter with `force_fallback_adapter`.
+    pub fn new_offscreen(width: u32, height: u32, force_fallback_adapter: bool) -> anyhow::Result<Self> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            flags: wgpu::InstanceFlags::default(),
+            dx12_shader_compiler: Default::default(),
+            gles_minor_version: wgpu::Gles3MinorVersion::Automatic,
+        });
+        let (adapter, device, queue) = retrieve_adapter_device(&instance, None, force_fallback_adapter)?;
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        let color_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("color_texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: config.usage | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        Ok(Self::with_target((), instance, adapter, device, queue, config, None, Some(color_texture)))
+    }
+}
+
+impl<W> GraphicsProgram for GraphicsContext<WGPUState<'_>, W, wgpu::Buffer> {
     fn swap_window(&self) {}
     fn get_backend_info(&self) {
         println!("Device features:\n{:#?}", self.device().features());
@@ -541,9 +699,9 @@ impl GraphicsProgram for WGPUGraphics<'_> {
         // println!("Adapter features:\n{:#?}", self.adapter().features());
     }
     fn default_state(&mut self) {
-        self.state
-            .surface
-            .configure(&self.state.device, self.config());
+        if let Some(surface) = &self.state.surface {
+            surface.configure(&self.state.device, &self.state.config);
+        }
     }
 }
 impl Into<wgpu::Color> for Color {
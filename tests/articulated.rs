@@ -0,0 +1,89 @@
+extern crate nalgebra_glm as glm;
+use std::str::FromStr;
+use wgpu_robotic_simulator::articulated::ArticulatedBody;
+use wgpu_robotic_simulator::geometry::Transform;
+use wgpu_robotic_simulator::physics::{Body, PhysicsProgram, PhysicsWorld, Shape};
+use wgpu_robotic_simulator::urdf::RobotDescriptor;
+
+// a slab with a flat arm hinged to its side, both 0.1 m thick
+const HINGED: &str = r#"<?xml version="1.0"?>
+<robot name="hinged">
+  <link name="slab">
+    <inertial>
+      <origin xyz="0 0 0" rpy="0 0 0"/>
+      <mass value="2"/>
+      <inertia ixx="0.03" ixy="0" ixz="0" iyy="0.03" iyz="0" izz="0.05"/>
+    </inertial>
+    <collision>
+      <geometry><box size="0.4 0.4 0.1"/></geometry>
+    </collision>
+  </link>
+  <link name="arm">
+    <inertial>
+      <origin xyz="0.15 0 0" rpy="0 0 0"/>
+      <mass value="0.5"/>
+      <inertia ixx="0.001" ixy="0" ixz="0" iyy="0.004" iyz="0" izz="0.004"/>
+    </inertial>
+    <collision>
+      <origin xyz="0.15 0 0" rpy="0 0 0"/>
+      <geometry><box size="0.28 0.1 0.1"/></geometry>
+    </collision>
+  </link>
+  <joint name="hinge" type="revolute">
+    <parent link="slab"/>
+    <child link="arm"/>
+    <origin xyz="0.21 0 0" rpy="0 0 0"/>
+    <axis xyz="0 1 0"/>
+    <limit lower="-1" upper="1" effort="10" velocity="5"/>
+  </joint>
+</robot>"#;
+
+#[test]
+fn dropped_robot_comes_to_rest() {
+    let mut robot = RobotDescriptor::from_str(HINGED).unwrap();
+    robot.build();
+    let mut world = PhysicsWorld::new();
+    world.add_body(Body::fixed(Shape::Plane, glm::Vec3::zeros()));
+    let base = Transform::new(glm::vec3(0.0, 0.0, 0.5), glm::Vec3::zeros());
+    let body = ArticulatedBody::spawn(&mut world, &robot, base);
+    for _ in 0..300 {
+        world.step();
+    }
+
+    let (slab, arm) = (
+        robot.link_index("slab").unwrap(),
+        robot.link_index("arm").unwrap(),
+    );
+    for link in [slab, arm] {
+        let dynamics = &world.body(body.link_body(link)).unwrap().dynamics;
+        assert!(dynamics.vel.norm() < 0.02, "{:?}", dynamics.vel);
+        assert!(dynamics.omega.norm() < 0.05, "{:?}", dynamics.omega);
+        // lying flat on the plane
+        assert!((dynamics.posn.z - 0.05).abs() < 0.01, "{:?}", dynamics.posn);
+    }
+
+    // the hinge still joins the arm to the side of the slab
+    let frames = body.link_transforms(&world);
+    let offset = (frames[slab].inverse() * frames[arm]).translation();
+    assert!(
+        (offset - glm::vec3(0.21, 0.0, 0.0)).norm() < 1e-3,
+        "{}",
+        offset
+    );
+    let hinge = body.joint_positions(&world)[0];
+    assert!(hinge.abs() < 0.05, "{}", hinge);
+
+    // the descriptor shows the links where the simulation put them
+    body.sync(&world, &mut robot);
+    for (link, frame) in robot.links.iter().zip(&frames) {
+        assert!((link.transform.tmatrix - frame.tmatrix).abs().max() < 1e-6);
+        let collision = frame.tmatrix * Transform::from(link.collision().unwrap().origin).tmatrix;
+        assert!(
+            (link.collision().unwrap().transform.tmatrix - collision)
+                .abs()
+                .max()
+                < 1e-6
+        );
+    }
+    assert_eq!(robot.joint_positions(), &[hinge]);
+}
@@ -0,0 +1,133 @@
+extern crate nalgebra_glm as glm;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{HashMap, HashSet};
+use wgpu_robotic_simulator::broad_phase::{Aabb, AabbTree, BroadPhase, CollisionFilter, ProxyId};
+
+fn random_box(rng: &mut StdRng) -> Aabb {
+    let center = glm::vec3(
+        rng.gen_range(-5.0..5.0),
+        rng.gen_range(-5.0..5.0),
+        rng.gen_range(-5.0..5.0),
+    );
+    let half = glm::vec3(
+        rng.gen_range(0.1..1.0),
+        rng.gen_range(0.1..1.0),
+        rng.gen_range(0.1..1.0),
+    );
+    Aabb::new(center - half, center + half)
+}
+
+fn sorted(pair: (ProxyId, ProxyId), tree: &AabbTree<usize>) -> (usize, usize) {
+    let (a, b) = (*tree.get(pair.0).unwrap(), *tree.get(pair.1).unwrap());
+    (a.min(b), a.max(b))
+}
+
+#[test]
+fn pairs_match_brute_force() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut tree = AabbTree::new(0.1);
+    // exact box and proxy of every live object
+    let mut live: HashMap<usize, (Aabb, ProxyId)> = HashMap::new();
+    for key in 0..200 {
+        let aabb = random_box(&mut rng);
+        live.insert(key, (aabb, tree.insert(aabb, key)));
+    }
+    for round in 0..5 {
+        // nudge some, teleport others, and replace a few
+        let keys: Vec<usize> = live.keys().copied().collect();
+        for key in keys {
+            match rng.gen_range(0..10) {
+                0..=3 => {
+                    let (aabb, id) = live[&key];
+                    let step = glm::vec3(
+                        rng.gen_range(-0.05..0.05),
+                        rng.gen_range(-0.05..0.05),
+                        rng.gen_range(-0.05..0.05),
+                    );
+                    let moved = Aabb::new(aabb.min + step, aabb.max + step);
+                    tree.update(id, moved);
+                    live.insert(key, (moved, id));
+                }
+                4..=5 => {
+                    let aabb = random_box(&mut rng);
+                    let id = live[&key].1;
+                    tree.update(id, aabb);
+                    live.insert(key, (aabb, id));
+                }
+                6 => {
+                    let (_, id) = live.remove(&key).unwrap();
+                    assert_eq!(tree.remove(id), Some(key));
+                    let key = 1000 * (round + 1) + key;
+                    let aabb = random_box(&mut rng);
+                    live.insert(key, (aabb, tree.insert(aabb, key)));
+                }
+                _ => {}
+            }
+        }
+
+        let found: Vec<(usize, usize)> = tree
+            .overlapping_pairs()
+            .into_iter()
+            .map(|p| sorted(p, &tree))
+            .collect();
+        let unique: HashSet<(usize, usize)> = found.iter().copied().collect();
+        assert_eq!(unique.len(), found.len(), "pairs reported twice");
+
+        // the tree works on its fattened boxes
+        let mut expected = HashSet::new();
+        let entries: Vec<_> = live.iter().collect();
+        for (i, (&a, &(box_a, id_a))) in entries.iter().enumerate() {
+            for (&b, &(box_b, id_b)) in &entries[i + 1..] {
+                if tree.fat_aabb(id_a).overlaps(&tree.fat_aabb(id_b)) {
+                    expected.insert((a.min(b), a.max(b)));
+                }
+                // which always hold the exact ones
+                if box_a.overlaps(&box_b) {
+                    assert!(unique.contains(&(a.min(b), a.max(b))));
+                }
+            }
+        }
+        assert_eq!(unique, expected, "round {}", round);
+    }
+}
+
+#[test]
+fn filters_and_exclusions_suppress_pairs() {
+    let unit = Aabb::new(glm::vec3(-1.0, -1.0, -1.0), glm::vec3(1.0, 1.0, 1.0));
+    let mut broad_phase = BroadPhase::new();
+    broad_phase.insert("a", unit, CollisionFilter::default());
+    broad_phase.insert("b", unit, CollisionFilter::default());
+    // in group 2, ignoring group 1
+    broad_phase.insert("c", unit, CollisionFilter::new(2, !1));
+    broad_phase.insert("d", unit, CollisionFilter::new(2, u32::MAX));
+    let pairs = |broad_phase: &BroadPhase<&'static str>| {
+        let mut pairs: Vec<_> = broad_phase
+            .pairs()
+            .into_iter()
+            .map(|(a, b)| if a < b { (a, b) } else { (b, a) })
+            .collect();
+        pairs.sort();
+        pairs
+    };
+    assert_eq!(
+        pairs(&broad_phase),
+        vec![("a", "b"), ("a", "d"), ("b", "d"), ("c", "d")]
+    );
+
+    broad_phase.exclude_pair("d", "a");
+    assert!(broad_phase.is_excluded("a", "d"));
+    assert_eq!(
+        pairs(&broad_phase),
+        vec![("a", "b"), ("b", "d"), ("c", "d")]
+    );
+
+    // a key put back in is no longer excluded
+    broad_phase.remove("a");
+    assert_eq!(pairs(&broad_phase), vec![("b", "d"), ("c", "d")]);
+    broad_phase.insert("a", unit, CollisionFilter::default());
+    assert_eq!(
+        pairs(&broad_phase),
+        vec![("a", "b"), ("a", "d"), ("b", "d"), ("c", "d")]
+    );
+}
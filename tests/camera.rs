@@ -0,0 +1,82 @@
+extern crate nalgebra_glm as glm;
+use wgpu_robotic_simulator::geometry::Transform;
+use wgpu_robotic_simulator::sensor::{CameraIntrinsics, RgbdFrame};
+
+// off centre, with rectangular pixels
+fn intrinsics() -> CameraIntrinsics {
+    CameraIntrinsics {
+        fx: 500.0,
+        fy: 450.0,
+        cx: 310.0,
+        cy: 250.0,
+        width: 640,
+        height: 480,
+        near: 0.1,
+        far: 20.0,
+    }
+}
+
+#[test]
+fn projection_round_trip() {
+    let intrinsics = intrinsics();
+    let projection = intrinsics.projection();
+    for p in [
+        glm::vec3(0.0, 0.0, 1.0),
+        glm::vec3(0.3, -0.2, 2.5),
+        glm::vec3(-1.5, 1.1, 7.0),
+        glm::vec3(0.01, 0.02, 0.15),
+    ] {
+        // optical frame (y down, looking down z) to the OpenGL eye frame
+        let clip = projection * glm::vec4(p.x, -p.y, -p.z, 1.0);
+        let ndc = clip.xyz() / clip.w;
+        assert!((0.0..=1.0).contains(&ndc.z), "{:?} out of depth range", p);
+        // viewport transform, with pixel rows going down
+        let u = (ndc.x + 1.0) / 2.0 * intrinsics.width as f32;
+        let v = (1.0 - ndc.y) / 2.0 * intrinsics.height as f32;
+        assert!((u - (intrinsics.fx * p.x / p.z + intrinsics.cx)).abs() < 1e-3);
+        assert!((v - (intrinsics.fy * p.y / p.z + intrinsics.cy)).abs() < 1e-3);
+
+        let depth = intrinsics.linear_depth(ndc.z);
+        assert!((depth - p.z).abs() < 1e-3 * p.z, "{} != {}", depth, p.z);
+        let back = intrinsics.unproject(u, v, depth);
+        assert!((back - p).norm() < 1e-3 * p.z, "{} != {}", back, p);
+    }
+    assert_eq!(intrinsics.linear_depth(1.0), f32::INFINITY);
+    assert!((intrinsics.linear_depth(0.0) - intrinsics.near).abs() < 1e-6);
+}
+
+#[test]
+fn point_cloud_uses_pixel_centres() {
+    // the principal point sits on the centre of pixel (1, 0)
+    let intrinsics = CameraIntrinsics {
+        fx: 2.0,
+        fy: 4.0,
+        cx: 1.5,
+        cy: 0.5,
+        width: 3,
+        height: 2,
+        near: 0.1,
+        far: 10.0,
+    };
+    let frame = RgbdFrame {
+        color: image::RgbaImage::new(3, 2),
+        depth: vec![2.0, 2.0, f32::INFINITY, 2.0, 2.0, 4.0],
+        intrinsics,
+        offset: Transform::new(glm::vec3(0.0, 0.0, 1.0), glm::Vec3::zeros()),
+    };
+    assert_eq!(frame.depth_at(2, 1), 4.0);
+    let up = glm::vec3(0.0, 0.0, 1.0);
+    let expected = [
+        glm::vec3(-1.0, 0.0, 2.0),
+        glm::vec3(0.0, 0.0, 2.0),
+        // pixel (2, 0) saw nothing
+        glm::vec3(-1.0, 0.5, 2.0),
+        glm::vec3(0.0, 0.5, 2.0),
+        glm::vec3(2.0, 1.0, 4.0),
+    ];
+    let cloud = frame.point_cloud();
+    assert_eq!(cloud.len(), expected.len());
+    for (p, e) in cloud.iter().zip(expected) {
+        assert!((p - (e + up)).norm() < 1e-6, "{} != {}", p, e + up);
+    }
+}
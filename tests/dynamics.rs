@@ -0,0 +1,195 @@
+use std::str::FromStr;
+extern crate nalgebra_glm as glm;
+use nalgebra::DVector;
+use wgpu_robotic_simulator::joint_state::{JointError, JointState};
+use wgpu_robotic_simulator::urdf::RobotDescriptor;
+
+// a 2 kg bob 0.5 m out along x, swinging about y
+const PENDULUM: &str = r#"<?xml version="1.0"?>
+<robot name="pendulum">
+  <link name="base"/>
+  <link name="arm">
+    <inertial>
+      <origin xyz="0.5 0 0" rpy="0 0 0"/>
+      <mass value="2"/>
+      <inertia ixx="0.01" ixy="0" ixz="0" iyy="0.02" iyz="0" izz="0.01"/>
+    </inertial>
+  </link>
+  <joint name="swing" type="revolute">
+    <parent link="base"/>
+    <child link="arm"/>
+    <origin xyz="0 0 1" rpy="0 0 0"/>
+    <axis xyz="0 1 0"/>
+    <limit lower="-3" upper="3" effort="100" velocity="10"/>
+  </joint>
+</robot>"#;
+
+// the arm of tests/jacobian.rs, with masses
+const ARM: &str = r#"<?xml version="1.0"?>
+<robot name="arm">
+  <link name="base"/>
+  <link name="upper">
+    <inertial>
+      <origin xyz="0 0 0.15" rpy="0 0 0"/>
+      <mass value="1.5"/>
+      <inertia ixx="0.02" ixy="0.001" ixz="0" iyy="0.02" iyz="0" izz="0.005"/>
+    </inertial>
+  </link>
+  <link name="lower">
+    <inertial>
+      <origin xyz="0 0.01 0.12" rpy="0 0 0"/>
+      <mass value="1.0"/>
+      <inertia ixx="0.01" ixy="0" ixz="0.002" iyy="0.012" iyz="0" izz="0.003"/>
+    </inertial>
+  </link>
+  <link name="slider">
+    <inertial>
+      <origin xyz="0.1 0 0" rpy="0 0 0"/>
+      <mass value="0.5"/>
+      <inertia ixx="0.002" ixy="0" ixz="0" iyy="0.004" iyz="0.0005" izz="0.004"/>
+    </inertial>
+  </link>
+  <link name="hand">
+    <inertial>
+      <origin xyz="0.05 0.02 0" rpy="0 0 0"/>
+      <mass value="0.3"/>
+      <inertia ixx="0.001" ixy="0.0002" ixz="0" iyy="0.001" iyz="0" izz="0.0015"/>
+    </inertial>
+  </link>
+  <joint name="yaw" type="revolute">
+    <parent link="base"/>
+    <child link="upper"/>
+    <origin xyz="0 0 0.2" rpy="0 0 0"/>
+    <axis xyz="0 0 1"/>
+    <limit lower="-3" upper="3" effort="10" velocity="1"/>
+  </joint>
+  <joint name="pitch" type="revolute">
+    <parent link="upper"/>
+    <child link="lower"/>
+    <origin xyz="0.1 0 0.3" rpy="0.3 0 0.2"/>
+    <axis xyz="0 1 0"/>
+    <limit lower="-3" upper="3" effort="10" velocity="1"/>
+  </joint>
+  <joint name="extend" type="prismatic">
+    <parent link="lower"/>
+    <child link="slider"/>
+    <origin xyz="0 0 0.25" rpy="0 0.4 0"/>
+    <axis xyz="1 0 0"/>
+    <limit lower="-1" upper="1" effort="10" velocity="1"/>
+  </joint>
+  <joint name="roll" type="continuous">
+    <parent link="slider"/>
+    <child link="hand"/>
+    <origin xyz="0.2 0.05 0" rpy="0 0 0"/>
+    <axis xyz="1 0 0"/>
+  </joint>
+</robot>"#;
+
+const GRAVITY: glm::Vec3 = glm::Vec3::new(0.0, 0.0, -9.81);
+
+#[test]
+fn pendulum_inverse_dynamics() {
+    let robot = RobotDescriptor::from_str(PENDULUM).unwrap();
+    let mut state = JointState::new(&robot);
+    state.positions[0] = 0.3;
+    // a single joint feels no velocity product terms
+    state.velocities[0] = 1.5;
+    let tau = robot.inverse_dynamics(&state, &[2.0], GRAVITY).unwrap();
+    // rotating about +y lowers the bob, so gravity helps
+    let inertia = 0.02 + 2.0 * 0.5 * 0.5;
+    let expected = inertia * 2.0 - 2.0 * 9.81 * 0.5 * 0.3f32.cos();
+    assert!(
+        (tau[0] - expected).abs() < 1e-4,
+        "{} != {}",
+        tau[0],
+        expected
+    );
+
+    assert_eq!(
+        robot.inverse_dynamics(&state, &[], GRAVITY),
+        Err(JointError::WrongLength {
+            expected: 1,
+            got: 0
+        })
+    );
+}
+
+#[test]
+fn pendulum_gravity_compensation() {
+    let robot = RobotDescriptor::from_str(PENDULUM).unwrap();
+    let mut state = JointState::new(&robot);
+    for q in [-1.0, 0.0, 0.7] {
+        state.positions[0] = q;
+        let tau = robot.gravity_compensation(&state, GRAVITY);
+        let expected = -2.0 * 9.81 * 0.5 * q.cos();
+        assert!(
+            (tau[0] - expected).abs() < 1e-4,
+            "{} != {}",
+            tau[0],
+            expected
+        );
+    }
+    // nothing to hold up without gravity
+    let tau = robot.gravity_compensation(&state, glm::Vec3::zeros());
+    assert_eq!(tau, vec![0.0]);
+}
+
+// a configuration away from any symmetry, moving
+fn arm_state(robot: &RobotDescriptor) -> JointState {
+    let mut state = JointState::new(robot);
+    state.positions.copy_from_slice(&[0.4, -0.7, 0.15, 1.1]);
+    state.velocities.copy_from_slice(&[0.8, -1.2, 0.5, 2.0]);
+    state
+}
+
+fn assert_close(a: &[f32], b: &[f32], tolerance: f32) {
+    assert_eq!(a.len(), b.len());
+    for (i, (a, b)) in a.iter().zip(b).enumerate() {
+        assert!((a - b).abs() < tolerance, "dof {}: {} != {}", i, a, b);
+    }
+}
+
+#[test]
+fn mass_matrix_and_bias_match_inverse_dynamics() {
+    let robot = RobotDescriptor::from_str(ARM).unwrap();
+    let state = arm_state(&robot);
+    let qdd = [1.5, -0.5, 2.0, -3.0];
+    let tau = robot.inverse_dynamics(&state, &qdd, GRAVITY).unwrap();
+    let mass = robot.mass_matrix(&state);
+    let bias = robot.bias_forces(&state, GRAVITY);
+    let expected = mass * DVector::from_column_slice(&qdd) + DVector::from_vec(bias);
+    assert_close(&tau, expected.as_slice(), 1e-4);
+}
+
+#[test]
+fn forward_dynamics_inverts_inverse_dynamics() {
+    let robot = RobotDescriptor::from_str(ARM).unwrap();
+    let mut state = arm_state(&robot);
+    let qdd = [1.5, -0.5, 2.0, -3.0];
+    state.efforts = robot.inverse_dynamics(&state, &qdd, GRAVITY).unwrap();
+    assert_close(&robot.forward_dynamics(&state, GRAVITY), &qdd, 1e-3);
+}
+
+#[test]
+fn gravity_compensation_holds_still() {
+    let robot = RobotDescriptor::from_str(ARM).unwrap();
+    let mut state = arm_state(&robot);
+    state.velocities.fill(0.0);
+    state.efforts = robot.gravity_compensation(&state, GRAVITY);
+    assert!(state.efforts.iter().any(|e| e.abs() > 1.0));
+    assert_close(&robot.forward_dynamics(&state, GRAVITY), &[0.0; 4], 1e-3);
+}
+
+#[test]
+fn mass_matrix_is_symmetric_positive_definite() {
+    let robot = RobotDescriptor::from_str(ARM).unwrap();
+    for positions in [[0.0; 4], [0.4, -0.7, 0.15, 1.1], [-2.0, 1.3, -0.8, 0.3]] {
+        let mut state = JointState::new(&robot);
+        state.positions.copy_from_slice(&positions);
+        let mass = robot.mass_matrix(&state);
+        assert_eq!(mass.shape(), (4, 4));
+        assert!((&mass - mass.transpose()).amax() < 1e-6, "{}", mass);
+        let eigenvalues = mass.symmetric_eigenvalues();
+        assert!(eigenvalues.iter().all(|&e| e > 0.0), "{}", eigenvalues);
+    }
+}
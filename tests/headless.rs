@@ -0,0 +1,32 @@
+extern crate nalgebra_glm as glm;
+use wgpu_robotic_simulator::physics::{Body, FreeBody, PhysicsProgram, PhysicsWorld, Shape};
+
+#[test]
+fn records_every_step() {
+    let mut world = PhysicsWorld::new();
+    let ball = world.add_body(Body::new(
+        Shape::Sphere { radius: 0.5 },
+        FreeBody::at(glm::vec3(0.0, 0.0, 10.0), 1.0),
+    ));
+    let states = world.run_headless(30, 0.01);
+    assert_eq!(states.len(), 31);
+    assert!((states[30].time - 0.3).abs() < 1e-5);
+    // free fall under semi-implicit Euler
+    let z = states[30].body(ball).unwrap().posn.z;
+    let expected = 10.0 - 9.81 * 0.01 * 0.01 * (30.0 * 31.0 / 2.0);
+    assert!((z - expected).abs() < 1e-3, "{} != {}", z, expected);
+}
+
+#[test]
+fn ball_comes_to_rest_on_plane() {
+    let mut world = PhysicsWorld::new();
+    world.add_body(Body::fixed(Shape::Plane, glm::Vec3::zeros()));
+    let ball = world.add_body(Body::new(
+        Shape::Sphere { radius: 0.5 },
+        FreeBody::at(glm::vec3(0.0, 0.0, 2.0), 1.0),
+    ));
+    let states = world.run_headless(240, 1.0 / 60.0);
+    let last = states.last().unwrap().body(ball).unwrap();
+    assert!((last.posn.z - 0.5).abs() < 0.02, "ball at {}", last.posn.z);
+    assert!(last.vel.norm() < 0.05);
+}
@@ -0,0 +1,121 @@
+use std::str::FromStr;
+extern crate nalgebra_glm as glm;
+use wgpu_robotic_simulator::geometry::Transform;
+use wgpu_robotic_simulator::ik::{IkMode, IkSolver, IkStatus};
+use wgpu_robotic_simulator::joint_state::JointState;
+use wgpu_robotic_simulator::urdf::RobotDescriptor;
+
+const ARM: &str = r#"<?xml version="1.0"?>
+<robot name="arm">
+  <link name="base"/>
+  <link name="upper"/>
+  <link name="lower"/>
+  <link name="slider"/>
+  <link name="hand"/>
+  <joint name="yaw" type="revolute">
+    <parent link="base"/>
+    <child link="upper"/>
+    <origin xyz="0 0 0.2" rpy="0 0 0"/>
+    <axis xyz="0 0 1"/>
+    <limit lower="-3" upper="3" effort="10" velocity="1"/>
+  </joint>
+  <joint name="pitch" type="revolute">
+    <parent link="upper"/>
+    <child link="lower"/>
+    <origin xyz="0.1 0 0.3" rpy="0.3 0 0.2"/>
+    <axis xyz="0 1 0"/>
+    <limit lower="-3" upper="3" effort="10" velocity="1"/>
+  </joint>
+  <joint name="extend" type="prismatic">
+    <parent link="lower"/>
+    <child link="slider"/>
+    <origin xyz="0 0 0.25" rpy="0 0.4 0"/>
+    <axis xyz="1 0 0"/>
+    <limit lower="-1" upper="1" effort="10" velocity="1"/>
+  </joint>
+  <joint name="roll" type="continuous">
+    <parent link="slider"/>
+    <child link="hand"/>
+    <origin xyz="0.2 0.05 0" rpy="0 0 0"/>
+    <axis xyz="1 0 0"/>
+  </joint>
+</robot>"#;
+
+// pose of the hand in the base frame at `positions`
+fn hand_at(robot: &RobotDescriptor, positions: &[f32]) -> Transform {
+    let mut state = JointState::new(robot);
+    state.positions.copy_from_slice(positions);
+    robot.relative_pose(&state, "base", "hand").unwrap()
+}
+
+#[test]
+fn reaches_a_reachable_pose() {
+    let robot = RobotDescriptor::from_str(ARM).unwrap();
+    let target = hand_at(&robot, &[0.4, -0.7, 0.15, 1.1]);
+    let solution = IkSolver::new(IkMode::Pose)
+        .solve(&robot, "hand", "base", &target, &JointState::new(&robot))
+        .unwrap();
+    assert!(solution.converged(), "{:?}", solution);
+    assert!(solution.position_error <= 1e-3);
+    assert!(solution.orientation_error <= 1e-3);
+    let pose = robot
+        .relative_pose(&solution.state, "base", "hand")
+        .unwrap();
+    assert!((pose.translation() - target.translation()).norm() <= 1e-3);
+    assert!((pose.rotation() - target.rotation()).abs().max() <= 2e-3);
+}
+
+#[test]
+fn position_mode_ignores_orientation() {
+    let robot = RobotDescriptor::from_str(ARM).unwrap();
+    let reachable = hand_at(&robot, &[0.4, -0.7, 0.15, 1.1]);
+    // the same point, in an orientation the arm cannot take there
+    let target = Transform::new(reachable.translation(), glm::vec3(1.0, -1.2, 2.0));
+    let initial = JointState::new(&robot);
+    let pose = IkSolver::new(IkMode::Pose)
+        .solve(&robot, "hand", "base", &target, &initial)
+        .unwrap();
+    assert!(!pose.converged());
+
+    let solution = IkSolver::new(IkMode::Position)
+        .solve(&robot, "hand", "base", &target, &initial)
+        .unwrap();
+    assert!(solution.converged(), "{:?}", solution);
+    assert_eq!(solution.orientation_error, 0.0);
+    let reached = robot
+        .relative_pose(&solution.state, "base", "hand")
+        .unwrap();
+    assert!((reached.translation() - target.translation()).norm() <= 1e-3);
+}
+
+#[test]
+fn stays_within_joint_limits() {
+    let urdf = ARM.replacen(r#"lower="-3" upper="3""#, r#"lower="-0.2" upper="0.2""#, 1);
+    let robot = RobotDescriptor::from_str(&urdf).unwrap();
+    // needs 1 rad of yaw
+    let target = hand_at(&robot, &[1.0, -0.7, 0.15, 1.1]);
+    let mut initial = JointState::new(&robot);
+    initial.positions[0] = 2.0;
+    let solution = IkSolver::new(IkMode::Position)
+        .solve(&robot, "hand", "base", &target, &initial)
+        .unwrap();
+    assert!(!solution.converged());
+    let bounds = [(-0.2, 0.2), (-3.0, 3.0), (-1.0, 1.0)];
+    for ((name, q), (lower, upper)) in solution.state.iter_positions().zip(bounds) {
+        assert!((lower..=upper).contains(&q), "{} at {}", name, q);
+    }
+}
+
+#[test]
+fn unreachable_target() {
+    let robot = RobotDescriptor::from_str(ARM).unwrap();
+    let target = Transform::new(glm::vec3(10.0, 0.0, 0.0), glm::Vec3::zeros());
+    let solution = IkSolver::new(IkMode::Position)
+        .with_max_iterations(50)
+        .solve(&robot, "hand", "base", &target, &JointState::new(&robot))
+        .unwrap();
+    assert_eq!(solution.status, IkStatus::MaxIterations);
+    assert!(!solution.converged());
+    assert_eq!(solution.iterations, 50);
+    assert!(solution.position_error > 7.0);
+}
@@ -0,0 +1,97 @@
+use std::str::FromStr;
+extern crate nalgebra_glm as glm;
+use wgpu_robotic_simulator::joint_state::JointState;
+use wgpu_robotic_simulator::urdf::RobotDescriptor;
+
+const ARM: &str = r#"<?xml version="1.0"?>
+<robot name="arm">
+  <link name="base"/>
+  <link name="upper"/>
+  <link name="lower"/>
+  <link name="slider"/>
+  <link name="hand"/>
+  <joint name="yaw" type="revolute">
+    <parent link="base"/>
+    <child link="upper"/>
+    <origin xyz="0 0 0.2" rpy="0 0 0"/>
+    <axis xyz="0 0 1"/>
+    <limit lower="-3" upper="3" effort="10" velocity="1"/>
+  </joint>
+  <joint name="pitch" type="revolute">
+    <parent link="upper"/>
+    <child link="lower"/>
+    <origin xyz="0.1 0 0.3" rpy="0.3 0 0.2"/>
+    <axis xyz="0 1 0"/>
+    <limit lower="-3" upper="3" effort="10" velocity="1"/>
+  </joint>
+  <joint name="extend" type="prismatic">
+    <parent link="lower"/>
+    <child link="slider"/>
+    <origin xyz="0 0 0.25" rpy="0 0.4 0"/>
+    <axis xyz="1 0 0"/>
+    <limit lower="-1" upper="1" effort="10" velocity="1"/>
+  </joint>
+  <joint name="roll" type="continuous">
+    <parent link="slider"/>
+    <child link="hand"/>
+    <origin xyz="0.2 0.05 0" rpy="0 0 0"/>
+    <axis xyz="1 0 0"/>
+  </joint>
+</robot>"#;
+
+// central differences of the relative pose against the analytic Jacobian
+fn check_against_finite_differences(tip: &str, base: &str) {
+    let robot = RobotDescriptor::from_str(ARM).expect("unable to read urdf");
+    let mut state = JointState::new(&robot);
+    state.positions.copy_from_slice(&[0.4, -0.7, 0.15, 1.1]);
+    let jac = robot.jacobian(&state, tip, base).expect("unknown link");
+    assert_eq!(jac.ncols(), 4);
+
+    let eps = 1e-3;
+    for i in 0..state.dof() {
+        let pose_at = |dq: f32| {
+            let mut s = state.clone();
+            s.positions[i] += dq;
+            robot.relative_pose(&s, base, tip).unwrap()
+        };
+        let (plus, minus) = (pose_at(eps), pose_at(-eps));
+        let linear = (plus.translation() - minus.translation()) / (2.0 * eps);
+        // skew part of dR * R^T gives the angular velocity
+        let w = (plus.rotation() - minus.rotation()) / (2.0 * eps)
+            * pose_at(0.0).rotation().transpose();
+        let angular = glm::vec3(
+            w[(2, 1)] - w[(1, 2)],
+            w[(0, 2)] - w[(2, 0)],
+            w[(1, 0)] - w[(0, 1)],
+        ) * 0.5;
+        for k in 0..3 {
+            assert!(
+                (jac[(k, i)] - linear[k]).abs() < 1e-2,
+                "linear {k} of joint {i}"
+            );
+            assert!(
+                (jac[(k + 3, i)] - angular[k]).abs() < 1e-2,
+                "angular {k} of joint {i}"
+            );
+        }
+    }
+}
+
+#[test]
+fn jacobian_matches_finite_differences() {
+    check_against_finite_differences("hand", "base");
+}
+
+#[test]
+fn jacobian_relative_to_intermediate_link() {
+    check_against_finite_differences("hand", "upper");
+    check_against_finite_differences("upper", "hand");
+}
+
+#[test]
+fn jacobian_unknown_link() {
+    let robot = RobotDescriptor::from_str(ARM).unwrap();
+    assert!(robot
+        .jacobian(&JointState::new(&robot), "hand", "nope")
+        .is_none());
+}
@@ -0,0 +1,387 @@
+use std::str::FromStr;
+use wgpu_robotic_simulator::joint_state::{
+    JointError, JointState, LimitKind, LimitPolicy, LimitViolation,
+};
+use wgpu_robotic_simulator::urdf::RobotDescriptor;
+
+// fixed, actuated and mimic joints interleaved, so the state order differs
+// from the joint order
+const ARM: &str = r#"<?xml version="1.0"?>
+<robot name="arm">
+  <link name="world"/>
+  <link name="base"/>
+  <link name="upper"/>
+  <link name="lower"/>
+  <link name="slide"/>
+  <link name="finger"/>
+  <joint name="mount" type="fixed">
+    <parent link="world"/>
+    <child link="base"/>
+  </joint>
+  <joint name="shoulder" type="continuous">
+    <parent link="base"/>
+    <child link="upper"/>
+    <axis xyz="0 0 1"/>
+  </joint>
+  <joint name="elbow" type="revolute">
+    <parent link="upper"/>
+    <child link="lower"/>
+    <axis xyz="0 1 0"/>
+    <limit lower="-2" upper="2" effort="10" velocity="1"/>
+  </joint>
+  <joint name="finger_follow" type="revolute">
+    <parent link="lower"/>
+    <child link="finger"/>
+    <axis xyz="0 1 0"/>
+    <mimic joint="elbow" multiplier="0.5"/>
+  </joint>
+  <joint name="rail" type="prismatic">
+    <parent link="lower"/>
+    <child link="slide"/>
+    <axis xyz="1 0 0"/>
+    <limit lower="0" upper="0.5" effort="10" velocity="1"/>
+  </joint>
+</robot>"#;
+
+#[test]
+fn state_follows_actuated_joints_in_file_order() {
+    let robot = RobotDescriptor::from_str(ARM).unwrap();
+    let state = JointState::new(&robot);
+    assert_eq!(robot.dof(), 3);
+    assert_eq!(state.dof(), 3);
+    assert_eq!(state.names(), ["shoulder", "elbow", "rail"]);
+    assert_eq!(state.index("shoulder"), Some(0));
+    assert_eq!(state.index("elbow"), Some(1));
+    assert_eq!(state.index("rail"), Some(2));
+    assert_eq!(state.index("mount"), None);
+    assert_eq!(state.index("finger_follow"), None);
+    assert_eq!(state.positions, [0.0; 3]);
+    assert_eq!(state.velocities, [0.0; 3]);
+    assert_eq!(state.efforts, [0.0; 3]);
+}
+
+#[test]
+fn state_accessors_by_name() {
+    let mut state = JointState::from_names(["a", "b"]);
+    state.set_position("b", 1.5).unwrap();
+    state.set_velocity("a", -0.5).unwrap();
+    state.set_effort("b", 3.0).unwrap();
+    assert_eq!(state.positions, [0.0, 1.5]);
+    assert_eq!(state.position("b"), Some(1.5));
+    assert_eq!(state.velocity("a"), Some(-0.5));
+    assert_eq!(state.effort("b"), Some(3.0));
+    assert_eq!(state.position("c"), None);
+    assert_eq!(
+        state.set_position("c", 1.0),
+        Err(JointError::UnknownJoint("c".into()))
+    );
+    assert_eq!(
+        state.iter_positions().collect::<Vec<_>>(),
+        [("a", 0.0), ("b", 1.5)]
+    );
+}
+
+#[test]
+fn set_by_name_leaves_other_joints() {
+    let mut robot = RobotDescriptor::from_str(ARM).unwrap();
+    robot
+        .set_joint_positions_by_name([("rail", 0.25), ("shoulder", 1.0)])
+        .unwrap();
+    // positions are indexed by joint, the mimic joint follows elbow
+    assert_eq!(robot.joint_positions(), [0.0, 1.0, 0.0, 0.0, 0.25]);
+    robot.set_joint_positions_by_name([("elbow", 0.5)]).unwrap();
+    assert_eq!(robot.joint_positions(), [0.0, 1.0, 0.5, 0.25, 0.25]);
+
+    let state = robot.joint_state();
+    assert_eq!(state.positions, [1.0, 0.5, 0.25]);
+    assert_eq!(state.position("elbow"), Some(0.5));
+}
+
+#[test]
+fn set_by_name_rejects_unknown_and_passive_joints() {
+    let mut robot = RobotDescriptor::from_str(ARM).unwrap();
+    assert_eq!(
+        robot.set_joint_positions_by_name([("shoulder", 1.0), ("wrist", 1.0)]),
+        Err(JointError::UnknownJoint("wrist".into()))
+    );
+    assert_eq!(
+        robot.set_joint_positions_by_name([("mount", 1.0)]),
+        Err(JointError::NotActuated("mount".into()))
+    );
+    assert_eq!(
+        robot.set_joint_positions_by_name([("finger_follow", 1.0)]),
+        Err(JointError::NotActuated("finger_follow".into()))
+    );
+    // nothing is applied when any name is rejected
+    assert_eq!(robot.joint_positions(), [0.0; 5]);
+}
+
+#[test]
+fn joint_state_round_trip() {
+    let mut robot = RobotDescriptor::from_str(ARM).unwrap();
+    let mut state = JointState::new(&robot);
+    state.set_position("elbow", -1.0).unwrap();
+    state.set_position("rail", 0.1).unwrap();
+    robot.set_joint_state(&state).unwrap();
+    assert_eq!(robot.joint_state().positions, state.positions);
+
+    // a state for another robot is rejected by name
+    let other = JointState::from_names(["elbow", "gripper"]);
+    assert_eq!(
+        robot.set_joint_state(&other),
+        Err(JointError::UnknownJoint("gripper".into()))
+    );
+}
+
+// the first two joints of an xarm, with its position limits
+const XARM: &str = r#"<?xml version="1.0"?>
+<robot name="xarm">
+  <link name="link_base"/>
+  <link name="link1"/>
+  <link name="link2"/>
+  <joint name="joint1" type="revolute">
+    <parent link="link_base"/>
+    <child link="link1"/>
+    <origin xyz="0 0 0.267" rpy="0 0 0"/>
+    <axis xyz="0 0 1"/>
+    <limit lower="-6.28318530718" upper="6.28318530718" effort="50.0" velocity="3.0"/>
+  </joint>
+  <joint name="joint2" type="revolute">
+    <parent link="link1"/>
+    <child link="link2"/>
+    <origin xyz="0 0 0" rpy="-1.5708 0 0"/>
+    <axis xyz="0 0 1"/>
+    <limit lower="-2.059" upper="2.0944" effort="50.0" velocity="3.0"/>
+  </joint>
+</robot>"#;
+
+fn xarm(policy: LimitPolicy) -> RobotDescriptor {
+    let mut robot = RobotDescriptor::from_str(XARM).unwrap();
+    robot.limit_policy = policy;
+    robot
+}
+
+fn joint2_position(value: f32) -> LimitViolation {
+    LimitViolation {
+        joint: "joint2".into(),
+        kind: LimitKind::Position,
+        value,
+        lower: -2.059,
+        upper: 2.0944,
+    }
+}
+
+#[test]
+fn within_limits_reports_nothing() {
+    for policy in [LimitPolicy::Allow, LimitPolicy::Clamp, LimitPolicy::Reject] {
+        let mut robot = xarm(policy);
+        assert_eq!(robot.set_joint_position(&[6.0, -2.0], false), Ok(vec![]));
+        assert_eq!(robot.joint_positions(), [6.0, -2.0]);
+    }
+}
+
+#[test]
+fn allow_moves_past_the_limit() {
+    let mut robot = xarm(LimitPolicy::Allow);
+    assert_eq!(robot.limit_policy, RobotDescriptor::default().limit_policy);
+    let violations = robot.set_joint_position(&[0.5, 2.5], false).unwrap();
+    assert_eq!(violations, [joint2_position(2.5)]);
+    assert_eq!(robot.joint_positions(), [0.5, 2.5]);
+}
+
+#[test]
+fn clamp_saturates_at_the_limit() {
+    let mut robot = xarm(LimitPolicy::Clamp);
+    let violations = robot.set_joint_position(&[0.5, 2.5], false).unwrap();
+    assert_eq!(violations, [joint2_position(2.5)]);
+    assert_eq!(robot.joint_positions(), [0.5, 2.0944]);
+    // relative moves are checked against the resulting position
+    let violations = robot.set_joint_position(&[0.0, -4.5], true).unwrap();
+    assert_eq!(violations.len(), 1);
+    assert!((violations[0].value - (2.0944 - 4.5)).abs() < 1e-6);
+    assert_eq!(robot.joint_positions(), [0.5, -2.059]);
+}
+
+#[test]
+fn reject_leaves_every_joint() {
+    let mut robot = xarm(LimitPolicy::Reject);
+    robot.set_joint_position(&[0.5, 1.0], false).unwrap();
+    assert_eq!(
+        robot.set_joint_position(&[1.0, -2.5], false),
+        Err(JointError::LimitExceeded(joint2_position(-2.5)))
+    );
+    assert_eq!(robot.joint_positions(), [0.5, 1.0]);
+    assert_eq!(
+        robot.set_joint_positions_by_name([("joint2", 3.0)]),
+        Err(JointError::LimitExceeded(joint2_position(3.0)))
+    );
+    assert_eq!(robot.joint_positions(), [0.5, 1.0]);
+}
+
+#[test]
+fn wrong_length_command() {
+    let mut robot = xarm(LimitPolicy::Clamp);
+    assert_eq!(
+        robot.set_joint_position(&[0.0], false),
+        Err(JointError::WrongLength {
+            expected: 2,
+            got: 1
+        })
+    );
+}
+
+#[test]
+fn velocity_limits_on_timed_moves() {
+    // 1 rad in 0.1 s is 10 rad/s, over the 3 rad/s limit
+    let velocity = LimitViolation {
+        joint: "joint1".into(),
+        kind: LimitKind::Velocity,
+        value: 10.0,
+        lower: -3.0,
+        upper: 3.0,
+    };
+
+    let mut robot = xarm(LimitPolicy::Allow);
+    let violations = robot
+        .set_joint_position_timed(&[1.0, 0.0], false, 0.1)
+        .unwrap();
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0], velocity);
+    assert_eq!(robot.joint_positions(), [1.0, 0.0]);
+
+    let mut robot = xarm(LimitPolicy::Clamp);
+    let violations = robot
+        .set_joint_position_timed(&[1.0, 0.0], false, 0.1)
+        .unwrap();
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0], velocity);
+    assert!((robot.joint_positions()[0] - 0.3).abs() < 1e-6);
+    // the same move over a second is slow enough
+    assert_eq!(
+        robot.set_joint_position_timed(&[1.0, 0.0], false, 1.0),
+        Ok(vec![])
+    );
+
+    let mut robot = xarm(LimitPolicy::Reject);
+    assert_eq!(
+        robot.set_joint_position_timed(&[1.0, 0.0], false, 0.1),
+        Err(JointError::LimitExceeded(velocity))
+    );
+    assert_eq!(robot.joint_positions(), [0.0, 0.0]);
+    // untimed moves never check velocity
+    assert_eq!(robot.set_joint_position(&[1.0, 0.0], false), Ok(vec![]));
+}
+
+#[test]
+fn clamped_velocity_then_position() {
+    // joint2 is moved towards 3 rad at most 0.3 rad per step, and stops at
+    // its upper limit
+    let mut robot = xarm(LimitPolicy::Clamp);
+    for _ in 0..10 {
+        robot
+            .set_joint_position_timed(&[0.0, 3.0], false, 0.1)
+            .unwrap();
+    }
+    assert_eq!(robot.joint_positions()[1], 2.0944);
+}
+
+// a gripper whose two fingers follow a drive joint; the second finger follows
+// the first, and is travel limited before the drive is
+const GRIPPER: &str = r#"<?xml version="1.0"?>
+<robot name="gripper">
+  <link name="palm"/>
+  <link name="drive_link"/>
+  <link name="left"/>
+  <link name="right"/>
+  <joint name="right_finger" type="prismatic">
+    <parent link="palm"/>
+    <child link="right"/>
+    <axis xyz="0 -1 0"/>
+    <limit lower="0" upper="0.03" effort="20" velocity="0.1"/>
+    <mimic joint="left_finger" multiplier="0.5" offset="0.01"/>
+  </joint>
+  <joint name="left_finger" type="prismatic">
+    <parent link="palm"/>
+    <child link="left"/>
+    <axis xyz="0 1 0"/>
+    <limit lower="0" upper="0.04" effort="20" velocity="0.1"/>
+    <mimic joint="drive" multiplier="2"/>
+  </joint>
+  <joint name="drive" type="revolute">
+    <parent link="palm"/>
+    <child link="drive_link"/>
+    <axis xyz="0 0 1"/>
+    <limit lower="-1" upper="1" effort="20" velocity="1"/>
+  </joint>
+</robot>"#;
+
+fn gripper(policy: LimitPolicy) -> RobotDescriptor {
+    let mut robot = RobotDescriptor::from_str(GRIPPER).unwrap();
+    robot.limit_policy = policy;
+    robot
+}
+
+fn assert_positions(robot: &RobotDescriptor, expected: [f32; 3]) {
+    let q = robot.joint_positions();
+    assert!(
+        q.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-6),
+        "{:?} != {:?}",
+        q,
+        expected
+    );
+}
+
+#[test]
+fn mimic_chains_follow_their_source() {
+    let mut robot = gripper(LimitPolicy::Clamp);
+    // the mimic entries are ignored
+    robot.set_joint_position(&[5.0, 5.0, 0.01], false).unwrap();
+    // drive 0.01, left 0.02, right 0.5 * 0.02 + 0.01
+    assert_positions(&robot, [0.02, 0.02, 0.01]);
+    robot.set_joint_positions_by_name([("drive", 0.0)]).unwrap();
+    assert_positions(&robot, [0.01, 0.0, 0.0]);
+}
+
+#[test]
+fn mimic_joints_are_limited_after_following() {
+    // drive 0.015 puts left at 0.03, and right at 0.025, all within limits
+    let mut robot = gripper(LimitPolicy::Clamp);
+    assert_eq!(
+        robot.set_joint_position(&[0.0, 0.0, 0.015], false),
+        Ok(vec![])
+    );
+
+    // drive 0.03 puts left at 0.06, past its 0.04 travel; the right finger
+    // follows the clamped left finger
+    let violations = robot.set_joint_position(&[0.0, 0.0, 0.03], false).unwrap();
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].joint, "left_finger");
+    assert_eq!(violations[0].kind, LimitKind::Position);
+    assert!((violations[0].value - 0.06).abs() < 1e-6);
+    assert_positions(&robot, [0.03, 0.04, 0.03]);
+
+    let mut robot = gripper(LimitPolicy::Allow);
+    let violations = robot.set_joint_position(&[0.0, 0.0, 0.03], false).unwrap();
+    let names: Vec<&str> = violations.iter().map(|v| v.joint.as_str()).collect();
+    assert_eq!(names, ["left_finger", "right_finger"]);
+    assert_positions(&robot, [0.04, 0.06, 0.03]);
+
+    let mut robot = gripper(LimitPolicy::Reject);
+    assert!(matches!(
+        robot.set_joint_position(&[0.0, 0.0, 0.03], false),
+        Err(JointError::LimitExceeded(v)) if v.joint == "left_finger"
+    ));
+    assert_positions(&robot, [0.0, 0.0, 0.0]);
+}
+
+#[test]
+fn mimic_joints_are_velocity_limited() {
+    // 0.02 rad of drive moves the fingers 0.04 and 0.03 m in 0.1 s, both
+    // well over their 0.1 m/s, while the drive itself is slow enough
+    let mut robot = gripper(LimitPolicy::Allow);
+    let violations = robot
+        .set_joint_position_timed(&[0.0, 0.0, 0.02], false, 0.1)
+        .unwrap();
+    assert_eq!(violations.len(), 2);
+    assert!(violations.iter().all(|v| v.kind == LimitKind::Velocity));
+}
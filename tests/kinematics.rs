@@ -0,0 +1,294 @@
+use std::f32::consts::FRAC_PI_2;
+use std::str::FromStr;
+extern crate nalgebra_glm as glm;
+use wgpu_robotic_simulator::geometry::Transform;
+use wgpu_robotic_simulator::joint_state::JointState;
+use wgpu_robotic_simulator::kinematics::LinkFrame;
+use wgpu_robotic_simulator::urdf::{RobotDescriptor, UrdfError, UrdfErrorKind};
+
+// a robot from a list of links and (name, parent, child) fixed joints
+fn robot(links: &[&str], joints: &[(&str, &str, &str)]) -> String {
+    let mut s = String::from("<robot name=\"r\">\n");
+    for l in links {
+        s += &format!("  <link name=\"{}\"/>\n", l);
+    }
+    for (name, parent, child) in joints {
+        s += &format!(
+            "  <joint name=\"{}\" type=\"fixed\">\n    <parent link=\"{}\"/>\n    <child link=\"{}\"/>\n  </joint>\n",
+            name, parent, child
+        );
+    }
+    s + "</robot>"
+}
+
+fn parse_err(s: &str) -> UrdfError {
+    RobotDescriptor::from_str(s).expect_err("tree should be rejected")
+}
+
+#[test]
+fn tree_order_and_root() {
+    // declared out of order, joints before their parents
+    let robot = RobotDescriptor::from_str(&robot(
+        &["hand", "base", "arm", "head"],
+        &[
+            ("wrist", "arm", "hand"),
+            ("shoulder", "base", "arm"),
+            ("neck", "base", "head"),
+        ],
+    ))
+    .unwrap();
+    let tree = robot.tree();
+    assert_eq!(tree.root(), 1);
+    assert_eq!(robot.root_link().link_name, "base");
+    assert_eq!(tree.parent_joint(1), None);
+    assert_eq!(tree.parent_joint(0), Some(0));
+    assert_eq!(tree.child_joints(1), [1, 2]);
+    assert_eq!(tree.child_joints(0), [] as [usize; 0]);
+    assert_eq!(tree.order(), [1, 2, 3, 0]);
+}
+
+#[test]
+fn single_link_robot() {
+    let robot = RobotDescriptor::from_str(&robot(&["base"], &[])).unwrap();
+    assert_eq!(robot.tree().root(), 0);
+    assert_eq!(robot.tree().order(), [0]);
+}
+
+#[test]
+fn no_links() {
+    let err = parse_err("<robot name=\"r\">\n</robot>");
+    assert_eq!(err.kind, UrdfErrorKind::MissingElement("link".into()));
+    assert_eq!(err.line, 2);
+}
+
+#[test]
+fn multiple_parents() {
+    let err = parse_err(&robot(
+        &["a", "b", "c"],
+        &[("ab", "a", "b"), ("ac", "a", "c"), ("cb", "c", "b")],
+    ));
+    assert_eq!(err.kind, UrdfErrorKind::MultipleParents("b".into()));
+    // the second joint claiming the link
+    assert_eq!(err.path, "robot[r]/joint[cb]/child");
+    assert_eq!((err.line, err.column), (15, 5));
+}
+
+#[test]
+fn multiple_roots() {
+    let err = parse_err(&robot(
+        &["a", "b", "c", "d"],
+        &[("ab", "a", "b"), ("cd", "c", "d")],
+    ));
+    assert_eq!(
+        err.kind,
+        UrdfErrorKind::MultipleRoots(vec!["a".into(), "c".into()])
+    );
+    // at the second root
+    assert_eq!(err.path, "robot[r]/link[c]");
+    assert_eq!((err.line, err.column), (4, 3));
+}
+
+#[test]
+fn disconnected_link() {
+    let err = parse_err(&robot(&["a", "b", "loose"], &[("ab", "a", "b")]));
+    assert_eq!(err.kind, UrdfErrorKind::DisconnectedLink("loose".into()));
+    assert_eq!(err.path, "robot[r]/link[loose]");
+    assert_eq!((err.line, err.column), (4, 3));
+}
+
+#[test]
+fn cycle() {
+    let err = parse_err(&robot(
+        &["base", "a", "b", "c"],
+        &[("base_a", "base", "a"), ("bc", "b", "c"), ("cb", "c", "b")],
+    ));
+    assert_eq!(
+        err.kind,
+        UrdfErrorKind::KinematicCycle(vec!["b".into(), "c".into()])
+    );
+    assert_eq!(err.path, "robot[r]/link[b]");
+    assert_eq!(err.line, 4);
+}
+
+#[test]
+fn cycle_without_root() {
+    let err = parse_err(&robot(&["a", "b"], &[("ab", "a", "b"), ("ba", "b", "a")]));
+    assert_eq!(
+        err.kind,
+        UrdfErrorKind::KinematicCycle(vec!["a".into(), "b".into()])
+    );
+    assert_eq!(err.path, "robot[r]/link[a]");
+}
+
+// planar two link arm in the xy plane: 1 m upper arm, 0.5 m forearm ending
+// in a fixed tool frame, with a mimic joint spinning a marker at the tool
+const TWO_LINK: &str = r#"<?xml version="1.0"?>
+<robot name="planar">
+  <link name="base"/>
+  <link name="upper"/>
+  <link name="fore">
+    <visual>
+      <origin xyz="0.25 0 0" rpy="0 0 0"/>
+      <geometry><box size="0.5 0.05 0.05"/></geometry>
+    </visual>
+    <inertial>
+      <origin xyz="0.2 0 0" rpy="0 0 0"/>
+      <mass value="1"/>
+      <inertia ixx="0.01" iyy="0.01" izz="0.01"/>
+    </inertial>
+  </link>
+  <link name="tool"/>
+  <link name="marker"/>
+  <joint name="shoulder" type="revolute">
+    <parent link="base"/>
+    <child link="upper"/>
+    <axis xyz="0 0 1"/>
+    <limit lower="-3" upper="3" effort="1" velocity="1"/>
+  </joint>
+  <joint name="elbow" type="revolute">
+    <parent link="upper"/>
+    <child link="fore"/>
+    <origin xyz="1 0 0" rpy="0 0 0"/>
+    <axis xyz="0 0 1"/>
+    <limit lower="-3" upper="3" effort="1" velocity="1"/>
+  </joint>
+  <joint name="tool_mount" type="fixed">
+    <parent link="fore"/>
+    <child link="tool"/>
+    <origin xyz="0.5 0 0" rpy="0 0 0"/>
+  </joint>
+  <joint name="marker_spin" type="continuous">
+    <parent link="tool"/>
+    <child link="marker"/>
+    <axis xyz="0 0 1"/>
+    <mimic joint="elbow" multiplier="2"/>
+  </joint>
+</robot>"#;
+
+fn state(robot: &RobotDescriptor, shoulder: f32, elbow: f32) -> JointState {
+    let mut state = JointState::new(robot);
+    state.set_position("shoulder", shoulder).unwrap();
+    state.set_position("elbow", elbow).unwrap();
+    state
+}
+
+fn assert_near(a: glm::Vec3, b: glm::Vec3) {
+    assert!((a - b).norm() < 1e-5, "{} != {}", a, b);
+}
+
+fn assert_same(a: &Transform, b: &Transform) {
+    assert!((*a - *b).tmatrix.abs().max() < 1e-5, "{}\n!=\n{}", a, b);
+}
+
+#[test]
+fn two_link_tool_pose() {
+    let robot = RobotDescriptor::from_str(TWO_LINK).unwrap();
+    let at_zero = robot.link_pose(&JointState::new(&robot), "tool").unwrap();
+    assert_near(at_zero.translation(), glm::vec3(1.5, 0.0, 0.0));
+
+    // shoulder up, elbow back: the forearm points along +x again, 1 m up
+    let tool = robot
+        .link_pose(&state(&robot, FRAC_PI_2, -FRAC_PI_2), "tool")
+        .unwrap();
+    assert_near(tool.translation(), glm::vec3(0.5, 1.0, 0.0));
+    assert_near(tool.rotation() * glm::Vec3::x(), glm::Vec3::x());
+
+    // both up: the forearm points along -x
+    let tool = robot
+        .link_pose(&state(&robot, FRAC_PI_2, FRAC_PI_2), "tool")
+        .unwrap();
+    assert_near(tool.translation(), glm::vec3(-0.5, 1.0, 0.0));
+    assert_near(tool.rotation() * glm::Vec3::x(), -glm::Vec3::x());
+
+    assert!(robot
+        .link_pose(&JointState::new(&robot), "gripper")
+        .is_none());
+}
+
+#[test]
+fn frame_pose_applies_the_link_origins() {
+    let robot = RobotDescriptor::from_str(TWO_LINK).unwrap();
+    let state = state(&robot, FRAC_PI_2, 0.0);
+    let pose = |frame| {
+        robot
+            .frame_pose(&state, "fore", frame)
+            .unwrap()
+            .translation()
+    };
+    assert_near(pose(LinkFrame::Link), glm::vec3(0.0, 1.0, 0.0));
+    assert_near(pose(LinkFrame::Visual), glm::vec3(0.0, 1.25, 0.0));
+    assert_near(pose(LinkFrame::Inertial), glm::vec3(0.0, 1.2, 0.0));
+    // no collision element, so the link frame
+    assert_near(pose(LinkFrame::Collision), glm::vec3(0.0, 1.0, 0.0));
+}
+
+#[test]
+fn relative_pose_between_links() {
+    let robot = RobotDescriptor::from_str(TWO_LINK).unwrap();
+    let state = state(&robot, 0.7, -0.3);
+    // the shoulder does not change where the tool is seen from the upper arm
+    let tool_in_upper = robot.relative_pose(&state, "upper", "tool").unwrap();
+    let expected = robot
+        .relative_pose(&self::state(&robot, -1.2, -0.3), "upper", "tool")
+        .unwrap();
+    assert_same(&tool_in_upper, &expected);
+    assert_near(
+        tool_in_upper.translation(),
+        glm::vec3(1.0 + 0.5 * 0.3f32.cos(), -0.5 * 0.3f32.sin(), 0.0),
+    );
+    let identity = robot.relative_pose(&state, "fore", "fore").unwrap();
+    assert_same(&identity, &Transform::default());
+    assert!(robot.relative_pose(&state, "fore", "gripper").is_none());
+}
+
+#[test]
+fn mimic_joints_follow_in_forward_kinematics() {
+    let robot = RobotDescriptor::from_str(TWO_LINK).unwrap();
+    let frames = robot.forward_kinematics(&state(&robot, 0.0, 0.4));
+    let tool = robot.link_index("tool").unwrap();
+    let marker = robot.link_index("marker").unwrap();
+    let spin = frames[tool].inverse() * frames[marker];
+    // twice the elbow angle about z
+    assert_near(
+        spin.rotation() * glm::Vec3::x(),
+        glm::vec3(0.8f32.cos(), 0.8f32.sin(), 0.0),
+    );
+}
+
+#[test]
+fn parallel_matches_serial() {
+    let robot = RobotDescriptor::from_str(TWO_LINK).unwrap();
+    let states: Vec<JointState> = (0..64)
+        .map(|i| state(&robot, i as f32 * 0.1 - 3.0, 3.0 - i as f32 * 0.07))
+        .collect();
+    let parallel = robot.par_forward_kinematics(&states);
+    assert_eq!(parallel.len(), states.len());
+    for (state, frames) in states.iter().zip(&parallel) {
+        let serial = robot.forward_kinematics(state);
+        assert_eq!(frames.len(), robot.links.len());
+        for (p, s) in frames.iter().zip(&serial) {
+            assert_same(p, s);
+        }
+    }
+}
+
+#[test]
+fn forward_kinematics_leaves_the_robot() {
+    let mut robot = RobotDescriptor::from_str(TWO_LINK).unwrap();
+    robot
+        .set_joint_position(&[0.2, 0.3, 0.0, 0.0], false)
+        .unwrap();
+    let before = robot.joint_positions().to_vec();
+    robot.forward_kinematics(&state(&robot, 1.0, 1.0));
+    assert_eq!(robot.joint_positions(), before);
+    // and agrees with the robot's own joint positions
+    let tool = robot.link_pose(&robot.joint_state(), "tool").unwrap();
+    assert_near(
+        tool.translation(),
+        glm::vec3(
+            0.2f32.cos() + 0.5 * 0.5f32.cos(),
+            0.2f32.sin() + 0.5 * 0.5f32.sin(),
+            0.0,
+        ),
+    );
+}
@@ -0,0 +1,111 @@
+extern crate nalgebra_glm as glm;
+use std::f32::consts::PI;
+use std::str::FromStr;
+use wgpu_robotic_simulator::geometry::Transform;
+use wgpu_robotic_simulator::physics::{Body, PhysicsProgram, PhysicsWorld, Shape};
+use wgpu_robotic_simulator::raycast::RaycastScene;
+use wgpu_robotic_simulator::sensor::{Lidar, LidarConfig};
+use wgpu_robotic_simulator::urdf::{RobotDescriptor, SensorKind};
+
+const BASE: &str = r#"<?xml version="1.0"?>
+<robot name="base">
+  <link name="base"/>
+</robot>"#;
+
+// four walls 2 m away from the origin, inside faces
+fn room() -> RaycastScene {
+    let mut world = PhysicsWorld::new();
+    for (x, y) in [(2.5, 0.0), (-2.5, 0.0), (0.0, 2.5), (0.0, -2.5)] {
+        world.add_body(Body::fixed(
+            Shape::Cuboid {
+                half_extents: glm::vec3(0.5, 0.5, 0.5) + glm::vec3(y, x, 0.0).abs(),
+            },
+            glm::vec3(x, y, 0.0),
+        ));
+    }
+    RaycastScene::from_world(&world)
+}
+
+#[test]
+fn racecar_carries_a_lidar() {
+    let robot = RobotDescriptor::from_str(include_str!("../assets/racecar.urdf")).unwrap();
+    let sensor = robot.sensor("lidar").expect("lidar declared");
+    assert_eq!(sensor.link, robot.link_index("body_link").unwrap());
+    assert_eq!(sensor.update_rate, Some(10.0));
+    let Some(SensorKind::Ray { horizontal, .. }) = sensor.kind else {
+        panic!("not a ray sensor: {:?}", sensor.kind);
+    };
+    assert_eq!(horizontal.samples, 360);
+    let lidar = Lidar::from_urdf(&robot, "lidar").unwrap();
+    assert_eq!(lidar.ray_count(), 360);
+    assert!((lidar.pose(&robot).translation() - glm::vec3(0.3, 0.0, 0.3)).norm() < 1e-6);
+}
+
+#[test]
+fn ranges_to_the_walls() {
+    let robot = RobotDescriptor::from_str(BASE).unwrap();
+    // 45 degrees apart, starting straight back
+    let config = LidarConfig::planar(9, 2.0 * PI, 0.1, 10.0);
+    let lidar = Lidar::new(&robot, "base", Transform::default(), config).unwrap();
+    let scan = lidar.scan(&robot, &room());
+    assert_eq!(scan.ranges.len(), 9);
+    for (i, range) in scan.ranges.iter().enumerate() {
+        let expected = if i % 2 == 0 { 2.0 } else { 2.0 * 2f32.sqrt() };
+        assert!((range - expected).abs() < 1e-4, "ray {}: {}", i, range);
+    }
+    // straight ahead, in the link frame
+    assert!((scan.points[4] - glm::vec3(2.0, 0.0, 0.0)).norm() < 1e-4);
+
+    // the walls are out of reach
+    let config = LidarConfig::planar(9, 2.0 * PI, 0.1, 1.5);
+    let lidar = Lidar::new(&robot, "base", Transform::default(), config).unwrap();
+    let scan = lidar.scan(&robot, &room());
+    assert!(scan.ranges.iter().all(|r| r.is_infinite()));
+    assert!(scan.points.is_empty());
+}
+
+#[test]
+fn points_in_the_link_frame() {
+    let robot = RobotDescriptor::from_str(BASE).unwrap();
+    let mut config = LidarConfig::planar(1, 0.0, 0.1, 10.0);
+    config.vertical.min_angle = -PI / 4.0;
+    // looking 45 degrees down from 0.5 m up, facing -y
+    let offset = Transform::new(glm::vec3(0.0, 0.0, 0.5), glm::vec3(0.0, 0.0, -PI / 2.0));
+    let lidar = Lidar::new(&robot, "base", offset, config).unwrap();
+    let mut scene = room();
+    scene.add_plane(glm::Vec3::zeros(), glm::Vec3::z(), None);
+    let scan = lidar.scan(&robot, &scene);
+    assert!((scan.ranges[0] - 0.5 * 2f32.sqrt()).abs() < 1e-4);
+    assert!((scan.points[0] - glm::vec3(0.0, -0.5, 0.0)).norm() < 1e-4);
+}
+
+#[test]
+fn gaussian_noise() {
+    let robot = RobotDescriptor::from_str(BASE).unwrap();
+    let mut config = LidarConfig::planar(4000, PI / 4.0, 0.1, 10.0);
+    config.noise_stddev = 0.05;
+    config.seed = Some(42);
+    let lidar = Lidar::new(&robot, "base", Transform::default(), config).unwrap();
+    let scan = lidar.scan(&robot, &room());
+    // against the wall ahead, at 2 / cos(angle)
+    let errors: Vec<f32> = scan
+        .ranges
+        .iter()
+        .enumerate()
+        .map(|(i, r)| {
+            let angle = -PI / 8.0 + PI / 4.0 * i as f32 / 3999.0;
+            r - 2.0 / angle.cos()
+        })
+        .collect();
+    let n = errors.len() as f32;
+    let mean = errors.iter().sum::<f32>() / n;
+    let stddev = (errors.iter().map(|e| (e - mean).powi(2)).sum::<f32>() / n).sqrt();
+    assert!(mean.abs() < 0.01, "mean {}", mean);
+    assert!((stddev - 0.05).abs() < 0.005, "stddev {}", stddev);
+
+    // the same noise on every sweep, whatever thread casts each ray
+    assert_eq!(lidar.scan(&robot, &room()).ranges, scan.ranges);
+    config.seed = Some(43);
+    let lidar = Lidar::new(&robot, "base", Transform::default(), config).unwrap();
+    assert_ne!(lidar.scan(&robot, &room()).ranges, scan.ranges);
+}
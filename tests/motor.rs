@@ -0,0 +1,249 @@
+extern crate nalgebra_glm as glm;
+use std::str::FromStr;
+use wgpu_robotic_simulator::articulated::ArticulatedBody;
+use wgpu_robotic_simulator::constraint::{JointConstraint, JointKind, JointMotor, MotorMode};
+use wgpu_robotic_simulator::geometry::Transform;
+use wgpu_robotic_simulator::joint_state::JointError;
+use wgpu_robotic_simulator::physics::{Body, FreeBody, PhysicsProgram, PhysicsWorld, Shape};
+use wgpu_robotic_simulator::urdf::RobotDescriptor;
+
+// an arm turning about z on a post bolted to the world, so gravity does not
+// load the motor
+const TURNTABLE: &str = r#"<?xml version="1.0"?>
+<robot name="turntable">
+  <link name="post"/>
+  <link name="arm">
+    <inertial>
+      <origin xyz="0.2 0 0" rpy="0 0 0"/>
+      <mass value="1"/>
+      <inertia ixx="0.001" ixy="0" ixz="0" iyy="0.01" iyz="0" izz="0.01"/>
+    </inertial>
+  </link>
+  <joint name="turn" type="continuous">
+    <parent link="post"/>
+    <child link="arm"/>
+    <origin xyz="0 0 1" rpy="0 0 0"/>
+    <axis xyz="0 0 1"/>
+    <limit effort="2" velocity="1"/>
+  </joint>
+</robot>"#;
+
+fn turntable() -> (PhysicsWorld, ArticulatedBody) {
+    let mut robot = RobotDescriptor::from_str(TURNTABLE).unwrap();
+    robot.build();
+    let mut world = PhysicsWorld::new();
+    let body = ArticulatedBody::spawn(&mut world, &robot, Transform::default());
+    (world, body)
+}
+
+#[test]
+fn position_motor_holds_target() {
+    let mut world = PhysicsWorld::new();
+    let anchor = world.add_body(Body::fixed(
+        Shape::Sphere { radius: 0.1 },
+        glm::Vec3::zeros(),
+    ));
+    let bob = world.add_body(Body::new(
+        Shape::Sphere { radius: 0.1 },
+        FreeBody::at(glm::vec3(1.0, 0.0, 0.0), 1.0),
+    ));
+    let joint = world.add_joint(
+        JointConstraint::new(
+            JointKind::Revolute {
+                axis: glm::vec3(0.0, 1.0, 0.0),
+            },
+            anchor,
+            Transform::default(),
+            bob,
+            Transform::new(glm::vec3(-1.0, 0.0, 0.0), glm::Vec3::zeros()),
+        )
+        .with_motor(JointMotor {
+            mode: MotorMode::Position {
+                position: 0.5,
+                velocity: 0.0,
+            },
+            stiffness: 1000.0,
+            damping: 60.0,
+            ..Default::default()
+        }),
+    );
+    for _ in 0..180 {
+        world.step();
+    }
+    let joint = world.joint(joint).unwrap();
+    // gravity holds it off the target by torque / stiffness
+    let sag = 9.81 * 0.5f32.cos() / 1000.0;
+    assert!((joint.position(&world).unwrap() - 0.5 - sag).abs() < 1e-3);
+    assert!((joint.applied_effort() + 9.81 * 0.5f32.cos()).abs() < 0.05);
+}
+
+#[test]
+fn max_effort_caps_applied_effort() {
+    let (mut world, body) = turntable();
+    body.set_gains(&mut world, "turn", 1000.0, 10.0).unwrap();
+    body.set_position_target(&mut world, "turn", 3.0).unwrap();
+    let mut efforts = Vec::new();
+    for _ in 0..60 {
+        world.step();
+        efforts.push(body.joint_state(&world).effort("turn").unwrap());
+    }
+    // the gains ask for far more than the limit at first
+    assert!((efforts[0] - 2.0).abs() < 1e-4, "{}", efforts[0]);
+    assert!(
+        efforts.iter().all(|e| e.abs() <= 2.0 + 1e-4),
+        "{:?}",
+        efforts
+    );
+}
+
+#[test]
+fn max_velocity_caps_velocity_target() {
+    let (mut world, body) = turntable();
+    body.set_velocity_target(&mut world, "turn", 5.0).unwrap();
+    for _ in 0..120 {
+        world.step();
+    }
+    let state = body.joint_state(&world);
+    let velocity = state.velocity("turn").unwrap();
+    assert!((velocity - 1.0).abs() < 1e-3, "{}", velocity);
+    // spinning steadily once at the limit
+    assert!(state.effort("turn").unwrap().abs() < 1e-3);
+    assert!((state.position("turn").unwrap() - 2.0).abs() < 0.1);
+}
+
+// a leg hanging from a post bolted to the world, rolling about x on a hip
+// far lighter than the links below it, then pitching about y at the hip
+// and the knee
+const LEG: &str = r#"<?xml version="1.0"?>
+<robot name="leg">
+  <link name="post"/>
+  <link name="hip">
+    <inertial>
+      <origin xyz="0 0.003 0" rpy="0 0 0"/>
+      <mass value="0.06"/>
+      <inertia ixx="0.000004" ixy="0" ixz="0" iyy="0.000015" iyz="0" izz="0.000015"/>
+    </inertial>
+  </link>
+  <link name="upper">
+    <inertial>
+      <origin xyz="0 0 -0.04" rpy="0 0 0"/>
+      <mass value="0.13"/>
+      <inertia ixx="0.00008" ixy="0" ixz="0" iyy="0.00009" iyz="0" izz="0.000015"/>
+    </inertial>
+  </link>
+  <link name="lower">
+    <inertial>
+      <origin xyz="0 0 -0.05" rpy="0 0 0"/>
+      <mass value="0.1"/>
+      <inertia ixx="0.00009" ixy="0" ixz="0" iyy="0.00009" iyz="0" izz="0.00001"/>
+    </inertial>
+  </link>
+  <joint name="hip_roll" type="revolute">
+    <parent link="post"/>
+    <child link="hip"/>
+    <origin xyz="0 0 1" rpy="0 0 0"/>
+    <axis xyz="1 0 0"/>
+    <limit lower="-0.6" upper="0.6"/>
+  </joint>
+  <joint name="hip_pitch" type="revolute">
+    <parent link="hip"/>
+    <child link="upper"/>
+    <axis xyz="0 1 0"/>
+    <limit lower="-3" upper="3"/>
+  </joint>
+  <joint name="knee" type="revolute">
+    <parent link="upper"/>
+    <child link="lower"/>
+    <origin xyz="0 0 -0.08" rpy="0 0 0"/>
+    <axis xyz="0 1 0"/>
+    <limit lower="-3" upper="3"/>
+  </joint>
+</robot>"#;
+
+#[test]
+fn position_motors_bring_a_limb_to_rest() {
+    let mut robot = RobotDescriptor::from_str(LEG).unwrap();
+    robot.build();
+    let mut world = PhysicsWorld::new();
+    let body = ArticulatedBody::spawn(&mut world, &robot, Transform::default());
+    for (name, target) in [("hip_roll", 0.2), ("hip_pitch", 0.6), ("knee", -1.2)] {
+        body.set_gains(&mut world, name, 50.0, 1.0).unwrap();
+        body.set_position_target(&mut world, name, target).unwrap();
+    }
+    for _ in 0..180 {
+        world.step();
+    }
+    // the hip motor moves the whole leg, not just the hip
+    let mut fastest: f32 = 0.0;
+    for _ in 0..60 {
+        world.step();
+        let velocities = body.joint_velocities(&world);
+        fastest = velocities.iter().fold(fastest, |m, v| m.max(v.abs()));
+    }
+    assert!(fastest < 1e-3, "{}", fastest);
+    let q = body.joint_positions(&world);
+    assert!((q[0] - 0.2).abs() < 0.05, "{:?}", q);
+}
+
+// the turntable with a second arm on top, turning against the first
+const MIMIC: &str = r#"<?xml version="1.0"?>
+<robot name="turntable">
+  <link name="post"/>
+  <link name="arm">
+    <inertial>
+      <origin xyz="0.2 0 0" rpy="0 0 0"/>
+      <mass value="1"/>
+      <inertia ixx="0.001" ixy="0" ixz="0" iyy="0.01" iyz="0" izz="0.01"/>
+    </inertial>
+  </link>
+  <link name="top">
+    <inertial>
+      <origin xyz="0.1 0 0" rpy="0 0 0"/>
+      <mass value="0.2"/>
+      <inertia ixx="0.0001" ixy="0" ixz="0" iyy="0.001" iyz="0" izz="0.001"/>
+    </inertial>
+  </link>
+  <joint name="counter" type="revolute">
+    <parent link="arm"/>
+    <child link="top"/>
+    <origin xyz="0 0 0.1" rpy="0 0 0"/>
+    <axis xyz="0 0 1"/>
+    <limit lower="-3" upper="3" effort="5" velocity="10"/>
+    <mimic joint="turn" multiplier="-2" offset="0.1"/>
+  </joint>
+  <joint name="turn" type="continuous">
+    <parent link="post"/>
+    <child link="arm"/>
+    <origin xyz="0 0 1" rpy="0 0 0"/>
+    <axis xyz="0 0 1"/>
+    <limit effort="20" velocity="2"/>
+  </joint>
+</robot>"#;
+
+#[test]
+fn mimic_joint_follows_its_source() {
+    let mut robot = RobotDescriptor::from_str(MIMIC).unwrap();
+    robot.build();
+    let mut world = PhysicsWorld::new();
+    let body = ArticulatedBody::spawn(&mut world, &robot, Transform::default());
+    body.set_gains(&mut world, "turn", 100.0, 20.0).unwrap();
+    body.set_position_target(&mut world, "turn", 0.6).unwrap();
+    let mut worst: f32 = 0.0;
+    for _ in 0..240 {
+        world.step();
+        let q = body.joint_positions(&world);
+        worst = worst.max((q[0] - (-2.0 * q[1] + 0.1)).abs());
+    }
+    let q = body.joint_positions(&world);
+    assert!((q[1] - 0.6).abs() < 0.01, "{:?}", q);
+    assert!((q[0] + 1.1).abs() < 1e-3, "{:?}", q);
+    // it keeps up while the source moves
+    assert!(worst < 0.15, "{}", worst);
+
+    // only the joint it mimics moves it
+    assert_eq!(
+        body.set_position_target(&mut world, "counter", 0.0),
+        Err(JointError::NotActuated("counter".into()))
+    );
+    assert!(body.joint_state(&world).position("counter").is_none());
+}
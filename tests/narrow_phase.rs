@@ -0,0 +1,171 @@
+extern crate nalgebra_glm as glm;
+use wgpu_robotic_simulator::geometry::{Polyhedron, Transform};
+use wgpu_robotic_simulator::narrow_phase::{
+    collide, gjk, gjk_epa, ContactManifold, Manifold, Proximity,
+};
+use wgpu_robotic_simulator::physics::{Body, PhysicsProgram, PhysicsWorld, Shape};
+
+fn at(x: f32, y: f32, z: f32) -> Transform {
+    Transform::new(glm::vec3(x, y, z), glm::Vec3::zeros())
+}
+
+fn hull(points: Vec<glm::Vec3>) -> Shape {
+    Shape::ConvexMesh {
+        points,
+        mesh: Polyhedron::default(),
+    }
+}
+
+// unit cube, centred
+fn cube_hull() -> Shape {
+    let mut points = Vec::new();
+    for i in 0..8 {
+        let corner = |bit: usize| if i & bit == 0 { -0.5 } else { 0.5 };
+        points.push(glm::vec3(corner(1), corner(2), corner(4)));
+    }
+    hull(points)
+}
+
+// |x| + |y| + |z| <= 1
+fn octahedron_hull() -> Shape {
+    let mut points = Vec::new();
+    for axis in [glm::Vec3::x(), glm::Vec3::y(), glm::Vec3::z()] {
+        points.push(axis);
+        points.push(-axis);
+    }
+    hull(points)
+}
+
+fn resting_boxes() -> (Shape, Transform, Shape) {
+    let ground = Shape::Cuboid {
+        half_extents: glm::vec3(2.0, 2.0, 0.5),
+    };
+    let block = Shape::Cuboid {
+        half_extents: glm::vec3(0.5, 0.5, 0.5),
+    };
+    (ground, Transform::default(), block)
+}
+
+#[test]
+fn sphere_sphere_depth_and_normal() {
+    let a = Shape::Sphere { radius: 1.0 };
+    let b = Shape::Sphere { radius: 0.5 };
+    let manifold = collide(&a, &at(0.0, 0.0, 0.0), &b, &at(0.6, 0.0, 0.8)).unwrap();
+    // centres 1 m apart
+    assert!((manifold.normal - glm::vec3(0.6, 0.0, 0.8)).norm() < 1e-6);
+    assert_eq!(manifold.points.len(), 1);
+    let contact = manifold.points[0];
+    assert!((contact.depth - 0.5).abs() < 1e-6);
+    assert!((contact.point_a - glm::vec3(0.6, 0.0, 0.8)).norm() < 1e-6);
+    assert!((contact.point_b - glm::vec3(0.3, 0.0, 0.4)).norm() < 1e-6);
+
+    assert!(collide(&a, &at(0.0, 0.0, 0.0), &b, &at(1.6, 0.0, 0.0)).is_none());
+}
+
+#[test]
+fn box_resting_on_box_face() {
+    let (ground, tg, block) = resting_boxes();
+    // sunk 0.05 into the top face, off centre
+    let tb = at(0.1, 0.2, 0.95);
+    let Manifold { normal, points } = collide(&ground, &tg, &block, &tb).unwrap();
+    assert!((normal - glm::Vec3::z()).norm() < 1e-6);
+    assert_eq!(points.len(), 4);
+    for p in &points {
+        assert!((p.depth - 0.05).abs() < 1e-5, "{:?}", p);
+        // the corners of the bottom face
+        let corner = p.point_b - glm::vec3(0.1, 0.2, 0.45);
+        assert!((corner.x.abs() - 0.5).abs() < 1e-5, "{:?}", p);
+        assert!((corner.y.abs() - 0.5).abs() < 1e-5, "{:?}", p);
+        assert!(corner.z.abs() < 1e-5, "{:?}", p);
+    }
+    let mut corners: Vec<_> = points
+        .iter()
+        .map(|p| (p.point_b.x > 0.1, p.point_b.y > 0.2))
+        .collect();
+    corners.sort();
+    corners.dedup();
+    assert_eq!(corners.len(), 4);
+}
+
+#[test]
+fn gjk_separated_hulls() {
+    let cube = cube_hull();
+    let proximity = gjk(&cube, &at(0.0, 0.0, 0.0), &cube, &at(2.0, 0.3, -0.2));
+    let Proximity::Separated {
+        distance,
+        point_a,
+        point_b,
+    } = proximity
+    else {
+        panic!("overlapping: {:?}", proximity);
+    };
+    assert!((distance - 1.0).abs() < 1e-4, "{}", distance);
+    assert!((point_a.x - 0.5).abs() < 1e-4);
+    assert!((point_b.x - 1.5).abs() < 1e-4);
+    assert!((glm::distance(&point_a, &point_b) - distance).abs() < 1e-4);
+
+    // the octahedron's tip reaches into the cube
+    let octahedron = octahedron_hull();
+    assert_eq!(
+        gjk(&cube, &at(0.0, 0.0, 0.0), &octahedron, &at(1.3, 0.0, 0.0)),
+        Proximity::Intersecting
+    );
+}
+
+#[test]
+fn epa_depth_of_overlapping_hulls() {
+    let (cube, octahedron) = (cube_hull(), octahedron_hull());
+    // 0.2 m of the tip inside the +x face; every other direction needs more
+    let manifold = gjk_epa(&cube, &at(0.0, 0.0, 0.0), &octahedron, &at(1.3, 0.1, 0.05)).unwrap();
+    assert!(
+        (manifold.normal - glm::Vec3::x()).norm() < 1e-3,
+        "{:?}",
+        manifold
+    );
+    let depth = manifold.points.iter().map(|p| p.depth).fold(0.0, f32::max);
+    assert!((depth - 0.2).abs() < 1e-3, "{}", depth);
+
+    // two cubes overlapping by 0.25 along y
+    let manifold = gjk_epa(&cube, &at(0.0, 0.0, 0.0), &cube, &at(0.1, -0.75, 0.2)).unwrap();
+    assert!(
+        (manifold.normal + glm::Vec3::y()).norm() < 1e-3,
+        "{:?}",
+        manifold
+    );
+    let depth = manifold.points.iter().map(|p| p.depth).fold(0.0, f32::max);
+    assert!((depth - 0.25).abs() < 1e-3, "{}", depth);
+}
+
+#[test]
+fn manifold_keeps_impulses_across_small_motions() {
+    let mut world = PhysicsWorld::new();
+    let (ground, tg, block) = resting_boxes();
+    let a = world.add_body(Body::fixed(ground.clone(), glm::Vec3::zeros()));
+    let b = world.add_body(Body::fixed(block.clone(), glm::vec3(0.0, 0.0, 0.95)));
+    let mut manifold = ContactManifold::new(a, b);
+
+    let tb = at(0.0, 0.0, 0.95);
+    manifold.update(collide(&ground, &tg, &block, &tb).unwrap(), &tg, &tb);
+    assert_eq!(manifold.points.len(), 4);
+    // impulses a solver would have left, told apart by the corner
+    let impulse = |p: &glm::Vec3| 1.0 + (p.x > 0.0) as u8 as f32 + 2.0 * (p.y > 0.0) as u8 as f32;
+    for p in &mut manifold.points {
+        p.normal_impulse = impulse(&p.local_a);
+        p.tangent_impulse = [0.1 * p.normal_impulse, -0.1];
+    }
+
+    // a few millimetres of drift and a slight turn
+    let tb = Transform::new(glm::vec3(0.004, -0.003, 0.949), glm::vec3(0.0, 0.0, 0.005));
+    manifold.update(collide(&ground, &tg, &block, &tb).unwrap(), &tg, &tb);
+    assert_eq!(manifold.points.len(), 4);
+    for p in &manifold.points {
+        assert_eq!(p.normal_impulse, impulse(&p.local_a), "{:?}", p);
+        assert_eq!(p.tangent_impulse, [0.1 * p.normal_impulse, -0.1]);
+    }
+
+    // moved too far to be the same contacts
+    let tb = at(0.2, 0.0, 0.95);
+    manifold.update(collide(&ground, &tg, &block, &tb).unwrap(), &tg, &tb);
+    assert_eq!(manifold.points.len(), 4);
+    assert!(manifold.points.iter().all(|p| p.normal_impulse == 0.0));
+}
@@ -0,0 +1,43 @@
+extern crate nalgebra_glm as glm;
+use wgpu_robotic_simulator::geometry::Polyhedron;
+
+// one triangle for each way of writing a face vertex
+const FACES: &str = "# four triangles
+o quad
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vn 0 0 2
+f 1 2 3
+f 1/1 3/3 4/1
+f 1//1 2//1 3//1
+f 1/1/1 3/3/1 4/1/1
+";
+
+#[test]
+fn face_vertex_forms() {
+    let path = std::env::temp_dir().join(format!("faces-{}.obj", std::process::id()));
+    std::fs::write(&path, FACES).unwrap();
+    let poly = Polyhedron::from(path.to_str().unwrap().to_owned());
+    std::fs::remove_file(&path).unwrap();
+
+    let corners = [
+        glm::vec3(0.0, 0.0, 0.0),
+        glm::vec3(1.0, 0.0, 0.0),
+        glm::vec3(1.0, 1.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+    ];
+    let expected = [[0, 1, 2], [0, 2, 3], [0, 1, 2], [0, 2, 3]];
+    assert_eq!(poly.verts.len(), 12);
+    for (tri, face) in poly.verts.chunks(3).zip(expected) {
+        for (v, i) in tri.iter().zip(face) {
+            assert_eq!(v.position, corners[i]);
+        }
+        // normals are unit length, facing +z
+        assert!((tri[0].normal - glm::Vec3::z()).norm() < 1e-6);
+    }
+}
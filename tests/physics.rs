@@ -0,0 +1,70 @@
+extern crate nalgebra_glm as glm;
+use wgpu_robotic_simulator::physics::{
+    Body, BodyHandle, FreeBody, PhysicsProgram, PhysicsWorld, Shape,
+};
+
+// a power of two, so whole steps add up exactly
+const DT: f32 = 1.0 / 64.0;
+
+// a ball falling in an otherwise empty world
+fn falling_ball() -> (PhysicsWorld, BodyHandle) {
+    let mut world = PhysicsWorld::new();
+    world.dt = DT;
+    let ball = world.add_body(Body::new(
+        Shape::Sphere { radius: 0.1 },
+        FreeBody::at(glm::vec3(0.0, 0.0, 1.0), 1.0),
+    ));
+    (world, ball)
+}
+
+fn height(world: &PhysicsWorld, ball: BodyHandle) -> f32 {
+    world.body(ball).unwrap().dynamics.posn.z
+}
+
+#[test]
+fn advance_runs_whole_steps() {
+    let (mut world, ball) = falling_ball();
+    assert_eq!(world.advance(3.0 * DT), 3);
+    assert_eq!(world.alpha(), 0.0);
+    let before = height(&world, ball);
+
+    assert_eq!(world.advance(1.5 * DT), 1);
+    assert_eq!(world.alpha(), 0.5);
+    let after = height(&world, ball);
+    assert!(after < before);
+    // rendered halfway between the last two steps
+    let z = world.interpolated_transform(ball).unwrap().translation().z;
+    assert!((z - (before + after) / 2.0).abs() < 1e-6, "{}", z);
+}
+
+#[test]
+fn advance_falls_behind_after_max_steps() {
+    let (mut world, _) = falling_ball();
+    assert_eq!(world.advance(1.0), world.max_steps);
+    assert_eq!(world.alpha(), 1.0);
+    assert_eq!(world.advance(0.0), 1);
+}
+
+#[test]
+fn zero_dt_stands_still() {
+    let (mut world, ball) = falling_ball();
+    world.dt = 0.0;
+    assert_eq!(world.advance(0.1), 0);
+    assert_eq!(world.alpha(), 0.0);
+    let t = world.interpolated_transform(ball).unwrap();
+    assert_eq!(t.translation(), glm::vec3(0.0, 0.0, 1.0));
+    // and picks up again once it has a step length
+    world.dt = DT;
+    assert_eq!(world.advance(DT), 1);
+    assert!(height(&world, ball) < 1.0);
+}
+
+#[test]
+fn zero_dt_step_does_nothing() {
+    let (mut world, ball) = falling_ball();
+    world.dt = 0.0;
+    world.step();
+    let body = world.body(ball).unwrap();
+    assert_eq!(body.dynamics.posn, glm::vec3(0.0, 0.0, 1.0));
+    assert!(body.dynamics.vel.iter().all(|v| *v == 0.0));
+}
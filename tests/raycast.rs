@@ -0,0 +1,104 @@
+extern crate nalgebra_glm as glm;
+use std::str::FromStr;
+use wgpu_robotic_simulator::physics::{Body, FreeBody, PhysicsProgram, PhysicsWorld, Shape};
+use wgpu_robotic_simulator::raycast::RaycastScene;
+use wgpu_robotic_simulator::urdf::RobotDescriptor;
+
+// a box on a post, 0.5 m up
+const SIGN: &str = r#"<?xml version="1.0"?>
+<robot name="sign">
+  <link name="post">
+    <visual>
+      <origin xyz="0 0 0.25" rpy="0 0 0"/>
+      <geometry><cylinder length="0.5" radius="0.02"/></geometry>
+    </visual>
+  </link>
+  <link name="board">
+    <visual>
+      <geometry><box size="0.4 0.02 0.2"/></geometry>
+    </visual>
+  </link>
+  <joint name="mount" type="fixed">
+    <parent link="post"/>
+    <child link="board"/>
+    <origin xyz="0 0 0.6" rpy="0 0 0"/>
+  </joint>
+</robot>"#;
+
+#[test]
+fn ground_height_under_a_box() {
+    let mut world = PhysicsWorld::new();
+    let ground = world.add_body(Body::fixed(Shape::Plane, glm::vec3(0.0, 0.0, -1.0)));
+    let crate_ = world.add_body(Body::fixed(
+        Shape::Cuboid {
+            half_extents: glm::vec3(0.5, 0.5, 0.5),
+        },
+        glm::Vec3::zeros(),
+    ));
+    let down = glm::vec3(0.0, 0.0, -1.0);
+
+    let hit = world
+        .raycast(glm::vec3(0.2, 0.1, 5.0), down, 100.0)
+        .expect("box below");
+    assert_eq!(hit.body, Some(crate_));
+    assert!((hit.distance - 4.5).abs() < 1e-4);
+    assert!((hit.point.z - 0.5).abs() < 1e-4);
+    assert!((hit.normal - glm::Vec3::z()).norm() < 1e-4);
+
+    let hit = world
+        .raycast(glm::vec3(2.0, 0.0, 5.0), down, 100.0)
+        .expect("ground below");
+    assert_eq!(hit.body, Some(ground));
+    assert!((hit.point.z + 1.0).abs() < 1e-4);
+
+    // out of reach, and pointing away
+    assert!(world.raycast(glm::vec3(2.0, 0.0, 5.0), down, 5.0).is_none());
+    assert!(world
+        .raycast(glm::vec3(2.0, 0.0, 5.0), -down, 100.0)
+        .is_none());
+}
+
+#[test]
+fn normals_face_the_ray() {
+    let mut world = PhysicsWorld::new();
+    world.add_body(Body::new(
+        Shape::Sphere { radius: 1.0 },
+        FreeBody::at(glm::vec3(5.0, 0.0, 0.0), 1.0),
+    ));
+    let hit = world
+        .raycast(glm::Vec3::zeros(), glm::vec3(2.0, 0.0, 0.0), 10.0)
+        .unwrap();
+    // against the tessellated sphere
+    assert!((hit.distance - 4.0).abs() < 0.02, "{}", hit.distance);
+    assert!(hit.normal.x < -0.95);
+}
+
+#[test]
+fn many_rays_match_single_rays() {
+    let mut robot = RobotDescriptor::from_str(SIGN).unwrap();
+    robot.build();
+    let scene = RaycastScene::from_robot(&robot);
+    let rays: Vec<_> = (0..64)
+        .map(|i| {
+            let z = (i as f32 + 0.5) / 64.0;
+            (glm::vec3(0.0, -1.0, z), glm::vec3(0.0, 1.0, 0.0))
+        })
+        .collect();
+    let hits = scene.raycast_many(&rays, 10.0);
+    assert_eq!(hits.len(), rays.len());
+    for (&(origin, dir), hit) in rays.iter().zip(&hits) {
+        assert_eq!(*hit, scene.raycast(origin, dir, 10.0));
+        let expected = match origin.z {
+            z if z < 0.5 => robot.link_index("post"),
+            z if (0.5..=0.7).contains(&z) => robot.link_index("board"),
+            _ => None,
+        };
+        assert_eq!(hit.and_then(|h| h.link), expected, "at {}", origin.z);
+    }
+    // the face of the board
+    let board = scene
+        .raycast(glm::vec3(0.1, -1.0, 0.6), glm::vec3(0.0, 1.0, 0.0), 10.0)
+        .unwrap();
+    assert!((board.distance - 0.99).abs() < 1e-4);
+    assert!((board.normal + glm::Vec3::y()).norm() < 1e-4);
+}
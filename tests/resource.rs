@@ -0,0 +1,143 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use wgpu_robotic_simulator::resource::ResourceResolver;
+use wgpu_robotic_simulator::urdf::{RobotDescriptor, UrdfErrorKind};
+
+// lays out a scratch directory per test:
+//   <root>/ws/arm_description/meshes/link.stl
+//   <root>/other/arm_description/meshes/link.stl
+//   <root>/urdf/robot.urdf, <root>/urdf/local.stl
+fn workspace(test: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!(
+        "wgpu_robotic_simulator_resource_{}_{}",
+        test,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&root);
+    for dir in [
+        "ws/arm_description/meshes",
+        "other/arm_description/meshes",
+        "urdf",
+    ] {
+        fs::create_dir_all(root.join(dir)).unwrap();
+    }
+    let cube = fs::read("assets/meshes/3D_model_of_a_Cube.stl").unwrap();
+    for file in [
+        "ws/arm_description/meshes/link.stl",
+        "other/arm_description/meshes/link.stl",
+        "urdf/local.stl",
+    ] {
+        fs::write(root.join(file), &cube).unwrap();
+    }
+    root
+}
+
+#[test]
+fn package_from_search_path() {
+    let root = workspace("search_path");
+    let resolver = ResourceResolver::new().with_search_path(root.join("ws"));
+    assert_eq!(
+        resolver.resolve("package://arm_description/meshes/link.stl", None),
+        Some(root.join("ws/arm_description/meshes/link.stl"))
+    );
+    // the package exists but the file does not
+    assert_eq!(
+        resolver.resolve("package://arm_description/meshes/gripper.stl", None),
+        None
+    );
+    assert_eq!(
+        resolver.resolve("package://leg_description/meshes/link.stl", None),
+        None
+    );
+}
+
+#[test]
+fn package_path_searched_in_order() {
+    let root = workspace("package_path");
+    let paths = std::env::join_paths([root.join("other"), root.join("ws")]).unwrap();
+    let resolver = ResourceResolver::from_package_path(paths.to_str().unwrap());
+    assert_eq!(
+        resolver.resolve("package://arm_description/meshes/link.stl", None),
+        Some(root.join("other/arm_description/meshes/link.stl"))
+    );
+    // an entry may also name the package directory itself
+    let resolver =
+        ResourceResolver::from_package_path(root.join("ws/arm_description").to_str().unwrap());
+    assert_eq!(
+        resolver.resolve("package://arm_description/meshes/link.stl", None),
+        Some(root.join("ws/arm_description/meshes/link.stl"))
+    );
+}
+
+#[test]
+fn registered_package_wins_over_search_path() {
+    let root = workspace("with_package");
+    // no other test reads ROS_PACKAGE_PATH
+    std::env::set_var("ROS_PACKAGE_PATH", root.join("ws"));
+    let resolver = ResourceResolver::from_env();
+    assert_eq!(
+        resolver.resolve("package://arm_description/meshes/link.stl", None),
+        Some(root.join("ws/arm_description/meshes/link.stl"))
+    );
+    let resolver = resolver.with_package("arm_description", root.join("other/arm_description"));
+    assert_eq!(
+        resolver.resolve("package://arm_description/meshes/link.stl", None),
+        Some(root.join("other/arm_description/meshes/link.stl"))
+    );
+}
+
+#[test]
+fn relative_and_file_paths() {
+    let root = workspace("relative");
+    let resolver = ResourceResolver::new();
+    let urdf_dir = root.join("urdf");
+    assert_eq!(
+        resolver.resolve("local.stl", Some(&urdf_dir)),
+        Some(urdf_dir.join("local.stl"))
+    );
+    // falls back to the working directory
+    assert_eq!(
+        resolver.resolve("assets/meshes/teapot.stl", Some(&urdf_dir)),
+        Some(PathBuf::from("assets/meshes/teapot.stl"))
+    );
+    assert_eq!(resolver.resolve("local.stl", None), None);
+    let absolute = urdf_dir.join("local.stl");
+    let uri = format!("file://{}", absolute.display());
+    assert_eq!(resolver.resolve(&uri, Some(Path::new("/"))), Some(absolute));
+    assert_eq!(resolver.resolve("package://no_slash", None), None);
+}
+
+const URDF: &str = r#"<?xml version="1.0"?>
+<robot name="arm">
+  <link name="base">
+    <visual>
+      <geometry><mesh filename="MESH"/></geometry>
+    </visual>
+  </link>
+</robot>"#;
+
+#[test]
+fn urdf_meshes_go_through_the_resolver() {
+    let root = workspace("from_file");
+    let write_urdf = |mesh: &str| {
+        let path = root.join("urdf/robot.urdf");
+        fs::write(&path, URDF.replace("MESH", mesh)).unwrap();
+        path
+    };
+    let resolver = ResourceResolver::new().with_search_path(root.join("ws"));
+
+    let path = write_urdf("package://arm_description/meshes/link.stl");
+    assert!(RobotDescriptor::from_file(&path, &resolver).is_ok());
+    // relative to the urdf file, not the working directory
+    let path = write_urdf("local.stl");
+    assert!(RobotDescriptor::from_file(&path, &resolver).is_ok());
+
+    let path = write_urdf("package://leg_description/meshes/link.stl");
+    let err = RobotDescriptor::from_file(&path, &resolver).unwrap_err();
+    assert_eq!(
+        err.kind,
+        UrdfErrorKind::InvalidMesh("package://leg_description/meshes/link.stl".into())
+    );
+    assert_eq!(err.path, "robot[arm]/link[base]/visual/geometry/mesh");
+    assert_eq!(err.line, 5);
+}
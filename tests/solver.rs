@@ -0,0 +1,135 @@
+extern crate nalgebra_glm as glm;
+use wgpu_robotic_simulator::physics::{
+    Body, BodyHandle, FreeBody, Material, PhysicsProgram, PhysicsWorld, Shape,
+};
+
+// drops a ball of radius 0.2 from `drop` above a plane, returning how high
+// it rises after the first bounce
+fn bounce_height(restitution: f32, drop: f32) -> f32 {
+    let mut world = PhysicsWorld::new();
+    world.dt = 1.0 / 240.0;
+    let material = Material {
+        friction: 0.5,
+        restitution,
+    };
+    world.add_body(Body::fixed(Shape::Plane, glm::Vec3::zeros()).with_material(material));
+    let ball = world.add_body(
+        Body::new(
+            Shape::Sphere { radius: 0.2 },
+            FreeBody::at(glm::vec3(0.0, 0.0, 0.2 + drop), 1.0),
+        )
+        .with_material(material),
+    );
+    let mut bounced = false;
+    let mut peak: f32 = 0.0;
+    for _ in 0..960 {
+        world.step();
+        let ball = &world.body(ball).unwrap().dynamics;
+        bounced |= ball.vel.z > 0.0;
+        if bounced {
+            peak = peak.max(ball.posn.z - 0.2);
+            if ball.vel.z < 0.0 {
+                break;
+            }
+        }
+    }
+    peak
+}
+
+// how far a box slides in two seconds on a plane tilted by `slope`, with
+// gravity tilted instead of the plane
+fn slide(slope: f32, friction: f32) -> f32 {
+    let mut world = PhysicsWorld::new();
+    world.gravity = 9.81 * glm::vec3(slope.sin(), 0.0, -slope.cos());
+    let material = Material {
+        friction,
+        restitution: 0.0,
+    };
+    world.add_body(Body::fixed(Shape::Plane, glm::Vec3::zeros()).with_material(material));
+    let block = world.add_body(
+        Body::new(
+            Shape::Cuboid {
+                half_extents: glm::vec3(0.2, 0.2, 0.1),
+            },
+            FreeBody::at(glm::vec3(0.0, 0.0, 0.1), 1.0),
+        )
+        .with_material(material),
+    );
+    for _ in 0..120 {
+        world.step();
+    }
+    world.body(block).unwrap().dynamics.posn.x
+}
+
+// how far the top of a stack of four boxes strays in two seconds
+fn stack_drift(warm_starting: bool, velocity_iterations: usize) -> f32 {
+    let mut world = PhysicsWorld::new();
+    world.solver.warm_starting = warm_starting;
+    world.solver.velocity_iterations = velocity_iterations;
+    world.add_body(Body::fixed(Shape::Plane, glm::Vec3::zeros()));
+    let boxes: Vec<BodyHandle> = (0..4)
+        .map(|i| {
+            world.add_body(Body::new(
+                Shape::Cuboid {
+                    half_extents: glm::vec3(0.25, 0.25, 0.25),
+                },
+                FreeBody::at(glm::vec3(0.0, 0.0, 0.25 + 0.5 * i as f32), 1.0),
+            ))
+        })
+        .collect();
+    for _ in 0..120 {
+        world.step();
+    }
+    let top = world.body(boxes[3]).unwrap();
+    (top.dynamics.posn - glm::vec3(0.0, 0.0, 1.75)).norm()
+}
+
+#[test]
+fn ball_bounces_to_restitution_squared() {
+    for (restitution, drop) in [(0.6, 1.0), (0.8, 2.0), (0.5, 0.5)] {
+        let height = bounce_height(restitution, drop);
+        let expected = restitution * restitution * drop;
+        assert!(
+            (height - expected).abs() < 0.05 * expected,
+            "{} != {}",
+            height,
+            expected
+        );
+    }
+    // hitting at 0.31 m/s, under the restitution threshold
+    assert_eq!(bounce_height(0.6, 0.005), 0.0);
+}
+
+#[test]
+fn box_sticks_below_friction_angle() {
+    let friction: f32 = 0.5;
+    let angle = friction.atan();
+    for slope in [angle - 0.15, angle - 0.05] {
+        assert!(slide(slope, friction).abs() < 0.005, "slope {}", slope);
+    }
+    for slope in [angle + 0.05, angle + 0.15] {
+        // sliding at g (sin - mu cos) for two seconds
+        let expected = 0.5 * 9.81 * (slope.sin() - friction * slope.cos()) * 4.0;
+        let distance = slide(slope, friction);
+        assert!(
+            (distance - expected).abs() < 0.05 * expected,
+            "slope {}: {} != {}",
+            slope,
+            distance,
+            expected
+        );
+    }
+}
+
+#[test]
+fn warm_starting_settles_stack_in_fewer_iterations() {
+    // fewest velocity iterations that keep the top box within 2 cm
+    let fewest = |warm_starting: bool| {
+        [2, 4, 6, 8, 10, 15, 20, 30, 50]
+            .into_iter()
+            .find(|&iterations| stack_drift(warm_starting, iterations) < 0.02)
+    };
+    let warm = fewest(true).expect("warm started stack never settles");
+    let cold = fewest(false).expect("cold started stack never settles");
+    assert!(warm < cold, "{} >= {}", warm, cold);
+}
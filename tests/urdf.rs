@@ -0,0 +1,398 @@
+use std::f32::consts::FRAC_PI_2;
+use std::str::FromStr;
+extern crate nalgebra_glm as glm;
+use wgpu_robotic_simulator::resource::ResourceResolver;
+use wgpu_robotic_simulator::urdf::{RobotDescriptor, UrdfError, UrdfErrorKind};
+
+// wraps a joint body in a two link robot, so each test only has to spell out
+// the part which is broken
+fn with_joint(joint: &str) -> String {
+    format!(
+        r#"<?xml version="1.0"?>
+<robot name="arm">
+  <link name="base"/>
+  <link name="tip"/>
+  <joint name="elbow" type="revolute">
+{}
+  </joint>
+</robot>"#,
+        joint
+    )
+}
+
+fn parse_err(s: &str) -> UrdfError {
+    RobotDescriptor::from_str(s).expect_err("document should not parse")
+}
+
+#[test]
+fn well_formed_joint_parses() {
+    let robot = RobotDescriptor::from_str(&with_joint(
+        r#"    <parent link="base"/>
+    <child link="tip"/>
+    <axis xyz="0 0 1"/>"#,
+    ))
+    .unwrap();
+    assert_eq!(robot.links.len(), 2);
+    assert_eq!(robot.joints.len(), 1);
+}
+
+#[test]
+fn missing_attribute() {
+    let err = parse_err(&with_joint(
+        r#"    <parent/>
+    <child link="tip"/>"#,
+    ));
+    assert_eq!(err.kind, UrdfErrorKind::MissingAttribute("link".into()));
+    assert_eq!(err.path, "robot[arm]/joint[elbow]/parent");
+    assert_eq!((err.line, err.column), (6, 5));
+}
+
+#[test]
+fn bad_float() {
+    let err = parse_err(&with_joint(
+        r#"    <parent link="base"/>
+    <child link="tip"/>
+    <origin xyz="0 0 abc"/>"#,
+    ));
+    assert_eq!(err.kind, UrdfErrorKind::InvalidNumber("abc".into()));
+    assert_eq!(err.path, "robot[arm]/joint[elbow]/origin");
+    assert_eq!((err.line, err.column), (8, 5));
+}
+
+#[test]
+fn unknown_element() {
+    let err = parse_err(&with_joint(
+        r#"    <parent link="base"/>
+    <child link="tip"/>
+      <gearbox ratio="3"/>"#,
+    ));
+    assert_eq!(
+        err.kind,
+        UrdfErrorKind::UnsupportedElement("gearbox".into())
+    );
+    assert_eq!(err.path, "robot[arm]/joint[elbow]/gearbox");
+    assert_eq!((err.line, err.column), (8, 7));
+}
+
+#[test]
+fn unknown_link() {
+    let err = parse_err(&with_joint(
+        r#"    <parent link="base"/>
+    <child link="hand"/>"#,
+    ));
+    assert_eq!(err.kind, UrdfErrorKind::UnknownLink("hand".into()));
+    assert_eq!(err.path, "robot[arm]/joint[elbow]/child");
+    assert_eq!((err.line, err.column), (7, 5));
+}
+
+#[test]
+fn invalid_joint_type() {
+    let err = parse_err(
+        r#"<robot name="arm">
+  <link name="base"/>
+  <joint name="elbow" type="hinge"/>
+</robot>"#,
+    );
+    assert_eq!(
+        err.kind,
+        UrdfErrorKind::InvalidValue {
+            attribute: "type".into(),
+            value: "hinge".into()
+        }
+    );
+    assert_eq!(err.path, "robot[arm]/joint[elbow]");
+    assert_eq!((err.line, err.column), (3, 3));
+}
+
+#[test]
+fn missing_parent() {
+    let err = parse_err(&with_joint(r#"    <child link="tip"/>"#));
+    assert_eq!(err.kind, UrdfErrorKind::MissingElement("parent".into()));
+    // reported once the joint has been closed
+    assert_eq!(err.path, "robot[arm]");
+    assert_eq!(err.line, 7);
+}
+
+#[test]
+fn not_a_robot() {
+    let err = parse_err("<?xml version=\"1.0\"?>\n<world/>");
+    assert_eq!(err.kind, UrdfErrorKind::MissingElement("robot".into()));
+    assert_eq!(err.path, "world");
+    assert_eq!((err.line, err.column), (2, 1));
+}
+
+#[test]
+fn truncated_document() {
+    let err = parse_err(
+        r#"<robot name="arm">
+  <link name="base">"#,
+    );
+    assert!(matches!(err.kind, UrdfErrorKind::Xml(_)), "{:?}", err.kind);
+    assert_eq!(err.path, "robot[arm]/link[base]");
+    assert_eq!(err.line, 2);
+}
+
+// the transmission is declared before the joint it drives, as in many ROS
+// descriptions
+const TRANSMISSION: &str = r#"<?xml version="1.0"?>
+<robot name="arm">
+  <link name="base"/>
+  <link name="tip"/>
+  <transmission name="elbow_trans">
+    <type>transmission_interface/SimpleTransmission</type>
+    <joint name="elbow">
+      <hardwareInterface>hardware_interface/EffortJointInterface</hardwareInterface>
+    </joint>
+    <actuator name="elbow_motor">
+      <mechanicalReduction>50</mechanicalReduction>
+    </actuator>
+  </transmission>
+  <transmission name="wrist_trans" type="pr2_mechanism_model/SimpleTransmission">
+    <actuator name="wrist_motor"/>
+    <joint name="WRIST"/>
+    <mechanicalReduction>2</mechanicalReduction>
+  </transmission>
+  <joint name="elbow" type="revolute">
+    <parent link="base"/>
+    <child link="tip"/>
+    <axis xyz="0 0 1"/>
+  </joint>
+</robot>"#;
+
+#[test]
+fn transmission_joints_and_actuators() {
+    let robot = RobotDescriptor::from_str(&TRANSMISSION.replace("WRIST", "elbow")).unwrap();
+    assert_eq!(robot.transmissions.len(), 2);
+
+    let elbow = robot.transmission_for_joint("elbow").unwrap();
+    assert_eq!(elbow.name.as_deref(), Some("elbow_trans"));
+    assert_eq!(
+        elbow.transmission_type.as_deref(),
+        Some("transmission_interface/SimpleTransmission")
+    );
+    assert_eq!(elbow.joints, ["elbow"]);
+    assert_eq!(elbow.actuators, ["elbow_motor"]);
+    assert_eq!(elbow.mechanical_reduction, 50.0);
+    assert_eq!(
+        elbow.hardware_interface.as_deref(),
+        Some("hardware_interface/EffortJointInterface")
+    );
+    assert_eq!(elbow.actuator_to_joint_effort(0.5), 25.0);
+    assert_eq!(elbow.joint_to_actuator_effort(25.0), 0.5);
+
+    // the older attribute form
+    let wrist = &robot.transmissions[1];
+    assert_eq!(
+        wrist.transmission_type.as_deref(),
+        Some("pr2_mechanism_model/SimpleTransmission")
+    );
+    assert_eq!(wrist.actuators, ["wrist_motor"]);
+    assert_eq!(wrist.mechanical_reduction, 2.0);
+    assert!(wrist.hardware_interface.is_none());
+    assert!(robot.transmission_for_joint("shoulder").is_none());
+}
+
+#[test]
+fn transmission_for_unknown_joint() {
+    let err = parse_err(&TRANSMISSION.replace("WRIST", "wrist"));
+    assert_eq!(err.kind, UrdfErrorKind::UnknownJoint("wrist".into()));
+    // reported at the reference rather than at the end of the document
+    assert_eq!(
+        err.path,
+        "robot[arm]/transmission[wrist_trans]/joint[wrist]"
+    );
+    assert_eq!((err.line, err.column), (16, 5));
+}
+
+const GRIPPER: &str = r#"<?xml version="1.0"?>
+<robot name="gripper">
+  <link name="palm"/>
+  <link name="left"/>
+  <link name="right"/>
+  <joint name="left_finger" type="prismatic">
+    <parent link="palm"/>
+    <child link="left"/>
+    <axis xyz="0 1 0"/>
+    <limit lower="0" upper="0.04" effort="20" velocity="0.1"/>
+    <safety_controller soft_lower_limit="0.001" soft_upper_limit="0.039" k_position="100" k_velocity="10"/>
+    <calibration rising="0.02"/>
+  </joint>
+  <joint name="right_finger" type="prismatic">
+    <parent link="palm"/>
+    <child link="right"/>
+    <axis xyz="0 -1 0"/>
+    <limit lower="0" upper="0.04" effort="20" velocity="0.1"/>
+    <mimic joint="MIMIC" multiplier="-1" offset="0.01"/>
+    <safety_controller k_velocity="5"/>
+    <calibration falling="-0.5" rising="0.25"/>
+  </joint>
+</robot>"#;
+
+#[test]
+fn mimic_safety_controller_and_calibration() {
+    let robot = RobotDescriptor::from_str(&GRIPPER.replace("MIMIC", "left_finger")).unwrap();
+    let left = &robot.joints[robot.joint_index("left_finger").unwrap()];
+    let right = &robot.joints[robot.joint_index("right_finger").unwrap()];
+
+    assert!(left.mimic().is_none());
+    let mimic = right.mimic().unwrap();
+    assert_eq!(mimic.joint, "left_finger");
+    assert_eq!((mimic.multiplier, mimic.offset), (-1.0, 0.01));
+
+    let safety = left.safety_controller().unwrap();
+    assert_eq!(
+        (
+            safety.soft_lower_limit,
+            safety.soft_upper_limit,
+            safety.k_position,
+            safety.k_velocity
+        ),
+        (0.001, 0.039, 100.0, 10.0)
+    );
+    // everything but k_velocity is optional
+    let safety = right.safety_controller().unwrap();
+    assert_eq!(
+        (
+            safety.soft_lower_limit,
+            safety.soft_upper_limit,
+            safety.k_position,
+            safety.k_velocity
+        ),
+        (0.0, 0.0, 0.0, 5.0)
+    );
+
+    let calibration = left.calibration().unwrap();
+    assert_eq!(
+        (calibration.rising, calibration.falling),
+        (Some(0.02), None)
+    );
+    let calibration = right.calibration().unwrap();
+    assert_eq!(
+        (calibration.rising, calibration.falling),
+        (Some(0.25), Some(-0.5))
+    );
+}
+
+#[test]
+fn mimic_defaults() {
+    let robot = RobotDescriptor::from_str(&GRIPPER.replace(
+        r#"joint="MIMIC" multiplier="-1" offset="0.01""#,
+        r#"joint="left_finger""#,
+    ))
+    .unwrap();
+    let mimic = robot.joints[1].mimic().unwrap();
+    assert_eq!((mimic.multiplier, mimic.offset), (1.0, 0.0));
+}
+
+#[test]
+fn mimic_of_unknown_joint() {
+    let err = parse_err(&GRIPPER.replace("MIMIC", "thumb"));
+    assert_eq!(err.kind, UrdfErrorKind::UnknownJoint("thumb".into()));
+    assert_eq!(err.path, "robot[gripper]/joint[right_finger]/mimic");
+    assert_eq!(err.line, 19);
+}
+
+#[test]
+fn safety_controller_requires_k_velocity() {
+    let err = parse_err(&GRIPPER.replace(r#" k_velocity="5""#, ""));
+    assert_eq!(
+        err.kind,
+        UrdfErrorKind::MissingAttribute("k_velocity".into())
+    );
+    assert_eq!(
+        err.path,
+        "robot[gripper]/joint[right_finger]/safety_controller"
+    );
+    assert_eq!((err.line, err.column), (20, 5));
+}
+
+// a leg in the style of drake's models, with a capsule shin ending in a ball
+// foot, and a frame at the foot
+const LEG: &str = r#"<?xml version="1.0"?>
+<robot name="leg">
+  <link name="hip"/>
+  <link name="shin">
+    <collision>
+      <origin xyz="0 0 -0.1" rpy="0 0 0"/>
+      <geometry><capsule length="0.2" radius="0.02"/></geometry>
+    </collision>
+    <collision>
+      <origin xyz="0 0 -0.2" rpy="0 0 0"/>
+      <geometry><sphere radius="0.025"/></geometry>
+    </collision>
+  </link>
+  <joint name="knee" type="revolute">
+    <parent link="hip"/>
+    <child link="shin"/>
+    <origin xyz="0 0 -0.3" rpy="0 0 0"/>
+    <axis xyz="0 1 0"/>
+    <limit lower="-3" upper="3" effort="10" velocity="1"/>
+  </joint>
+  <frame name="foot" link="shin" xyz="0 0 -0.2" rpy="0 0 0"/>
+</robot>"#;
+
+#[test]
+fn capsule_geometry() {
+    let robot = RobotDescriptor::from_str(LEG).unwrap();
+    let shin = &robot.links[robot.link_index("shin").unwrap()];
+    // the capsule, then the foot
+    assert_eq!(shin.collisions.len(), 2);
+    let verts = shin.collision().unwrap().geometry.verts();
+    assert!(!verts.is_empty());
+    let top = verts.iter().map(|v| v.position.z).fold(f32::MIN, f32::max);
+    let bottom = verts.iter().map(|v| v.position.z).fold(f32::MAX, f32::min);
+    let radius = verts
+        .iter()
+        .map(|v| v.position.xy().norm())
+        .fold(0.0, f32::max);
+    // hemispheres on both ends of the cylinder
+    assert!((top - 0.12).abs() < 1e-6, "{}", top);
+    assert!((bottom + 0.12).abs() < 1e-6, "{}", bottom);
+    assert!((radius - 0.02).abs() < 1e-6, "{}", radius);
+}
+
+#[test]
+fn frames_follow_their_link() {
+    let mut robot = RobotDescriptor::from_str(LEG).unwrap();
+    let foot = robot.frame("foot").expect("foot frame");
+    assert_eq!(foot.link, robot.link_index("shin").unwrap());
+    assert!(robot.frame("hand").is_none());
+
+    robot.build();
+    let at_rest = robot.frame_transform("foot").unwrap().translation();
+    assert!((at_rest - glm::vec3(0.0, 0.0, -0.5)).norm() < 1e-6);
+
+    // swinging the shin forward about the knee
+    robot.set_joint_position(&[FRAC_PI_2], false).unwrap();
+    robot.build();
+    let swung = robot.frame_transform("foot").unwrap().translation();
+    assert!(
+        (swung - glm::vec3(-0.2, 0.0, -0.3)).norm() < 1e-6,
+        "{}",
+        swung
+    );
+}
+
+#[test]
+fn frame_on_unknown_link() {
+    let urdf = LEG.replace(r#"link="shin" xyz"#, r#"link="thigh" xyz"#);
+    let err = RobotDescriptor::from_str(&urdf).unwrap_err();
+    assert!(matches!(err.kind, UrdfErrorKind::UnknownLink(ref l) if l == "thigh"));
+}
+
+#[test]
+fn unreadable_file_has_no_position() {
+    let err = RobotDescriptor::from_file("assets/no_such.urdf", &ResourceResolver::default())
+        .unwrap_err();
+    assert!(matches!(err.kind, UrdfErrorKind::Io(_)), "{:?}", err.kind);
+    assert_eq!(err.path, "assets/no_such.urdf");
+    assert_eq!((err.line, err.column), (0, 0));
+    assert!(
+        err.to_string().starts_with("unable to read file"),
+        "{}",
+        err
+    );
+
+    let err = parse_err("<world/>");
+    assert!(err.to_string().starts_with("1:"), "{}", err);
+}